@@ -1,19 +1,24 @@
+use std::path::Path;
+
 use clap::Parser;
 
-use cli::cli_util::{
-    are_all_source_files_present, error_with_ack, get_repo_url, standard_cli_check,
-};
-use cli::supported::get_supported_inputs;
+use cli::cli_util::{are_all_source_files_present, standard_cli_check};
+use cli::error::ConfigError;
+use engine::ffmpeg_logs::LogRetention;
+use engine::telemetry::DEFAULT_MAX_BACKGROUND_LOAD;
+use environment::env::check_environment_setup;
+use ffmpeg::decoder::Decoder;
+use permutation::permutation::DEFAULT_VBV_BUFFER_SECONDS;
 
 #[derive(Parser)]
 pub struct BenchmarkCli {
-    /// lists the supported/implemented supported that this tool supports
+    /// lists the encoders this tool supports, and whether each one can run on this machine
     #[arg(short, long)]
     pub list_supported_encoders: bool,
     /// the encoder you wish to benchmark: [h264_nvenc, hevc_nvenc, etc]
     #[arg(short, long, value_name = "encoder_name", default_value = "encoder")]
     pub encoder: String,
-    /// the source file you wish to benchmark; if not provided, will run standard benchmark on all supported resolutions
+    /// the source file you wish to benchmark (y4m, or mp4/mkv/mov/ivf which get decoded to y4m first); if not provided, will run standard benchmark on all supported resolutions
     #[arg(short, long, value_name = "source.y4m", default_value = "")]
     pub source_file: String,
     /// the directory you wish the benchmark to look for your encoder files; can be used with --source_file/-s if you wish. Does NOT support spaces in directories
@@ -22,15 +27,67 @@ pub struct BenchmarkCli {
     /// the directory you wish for the logs this tool produces to go into; defaults to the current directory. Does NOT support spaces in directories
     #[arg(long, value_name = "folder/to/log/output", default_value = "")]
     pub log_output_directory: String,
+    /// which ffmpeg logs to keep in the log output directory once a permutation is done: failures, all or none
+    #[arg(long, value_name = "failures|all|none", default_value = "failures")]
+    pub ffmpeg_logs: LogRetention,
+    /// % of cpu that may be busy right before an encode; above it the encode's results are flagged with [B]
+    #[arg(long, value_name = "PERCENT", default_value = "20")]
+    pub max_background_load: f64,
+    /// runs a permutation again, once, when background load was present during it
+    #[arg(long)]
+    pub rerun_on_background_load: bool,
+    /// runs every encode this many times and reports the mean and spread of their FPS
+    #[arg(long, value_name = "N", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub trials: u32,
+    /// runs every encode once more before its trials, without counting it, so the GPU's clocks have settled
+    #[arg(long)]
+    pub warmup: bool,
+    /// comma separated streaming platforms to check every permutation against: twitch, youtube-live, kick or rtmp
+    #[arg(long, value_name = "twitch,kick", value_delimiter = ',')]
+    pub platform: Vec<String>,
+    /// only runs what at least one of the platforms would take
+    #[arg(long, requires = "platform")]
+    pub restrict_to_platform: bool,
+    /// writes each encode to a file next to the source and checks the bitrate it came out at: its average, peaks and a vbv buffer check
+    #[arg(long)]
+    pub check_bitrate: bool,
+    /// how many seconds of the target bitrate the decoder's buffer holds, for the cbr vbv check
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "1",
+        requires = "check_bitrate"
+    )]
+    pub vbv_buffer: f64,
+    /// writes each encode to a file next to the source and checks the encoder applied its profile, pixel format, b frame and keyframe settings
+    #[arg(long)]
+    pub verify_settings: bool,
+    /// a directory of <platform>.profile files, used over the built-in ones when platforms change their rules
+    #[arg(long, value_name = "folder/to/profiles", default_value = "")]
+    pub platform_directory: String,
     /// logs useful information to help troubleshooting
     #[arg(short, long)]
     pub verbose: bool,
     /// the GPU you wish to run the encode on; defaults to the first/only GPU found in your system
     #[arg(short, long, default_value = "0")]
     pub gpu: u8,
+    /// comma separated existing files to benchmark decoding on, instead of running an encode benchmark; the files are left untouched
+    #[arg(long, value_name = "a.mp4,b.mkv", value_delimiter = ',')]
+    pub decode_files: Vec<String>,
+    /// comma separated decoders to benchmark the files with: software, a hwaccel (i.e. cuda, vaapi) or a decoder (i.e. hevc_cuvid); defaults to software and every hwaccel ffmpeg has
+    #[arg(
+        long,
+        value_name = "software,cuda",
+        value_delimiter = ',',
+        requires = "decode_files"
+    )]
+    pub decoders: Vec<Decoder>,
     /// whether to run decode benchmark as well; defaults to off, as this will take up more storage space
     #[arg(short, long)]
     pub decode: bool,
+    /// hashes every frame each hardware decode produces and compares them against a software decode of the same file; a decoder that gets any wrong is marked with [W]
+    #[arg(long)]
+    pub verify_decode: bool,
     /// adds in '-pix_fmt yuv420p10le' to force 10-bit encoding
     #[arg(short, long)]
     pub ten_bit: bool,
@@ -40,6 +97,12 @@ pub struct BenchmarkCli {
     /// opt-out of using b frames for either H264 or HEVC encoders; currently only supported for Nvidia GPUs
     #[arg(short, long)]
     pub no_b_frame: bool,
+    /// stop instead of warning when the source files' storage is too slow to read them in realtime
+    #[arg(long)]
+    pub fail_on_slow_storage: bool,
+    /// copies each source file into memory (/dev/shm) before encoding it, so storage speed doesn't affect the results
+    #[arg(long)]
+    pub stage_in_memory: bool,
 }
 
 impl BenchmarkCli {
@@ -47,6 +110,10 @@ impl BenchmarkCli {
         self.was_ui_opened = true;
     }
 
+    pub fn was_ui_opened(&self) -> bool {
+        return self.was_ui_opened;
+    }
+
     // used when taking user input for the benchmark
     pub fn new() -> Self {
         return Self {
@@ -55,38 +122,61 @@ impl BenchmarkCli {
             source_file: String::from(""),
             files_directory: String::from(""),
             log_output_directory: String::from(""),
+            ffmpeg_logs: LogRetention::default(),
+            max_background_load: DEFAULT_MAX_BACKGROUND_LOAD,
+            rerun_on_background_load: false,
+            trials: 1,
+            warmup: false,
+            platform: Vec::new(),
+            restrict_to_platform: false,
+            check_bitrate: false,
+            vbv_buffer: DEFAULT_VBV_BUFFER_SECONDS,
+            verify_settings: false,
+            platform_directory: String::from(""),
             verbose: false,
             gpu: 0,
+            decode_files: Vec::new(),
+            decoders: Vec::new(),
             decode: false,
+            verify_decode: false,
             ten_bit: false,
             was_ui_opened: false,
             no_b_frame: false,
+            fail_on_slow_storage: false,
+            stage_in_memory: false,
         };
     }
 
-    pub fn validate(&mut self) {
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        // decoding existing files doesn't involve an encoder or the standard source files
+        if !self.decode_files.is_empty() {
+            check_environment_setup()?;
+            for file in &self.decode_files {
+                if !Path::new(file).exists() {
+                    return Err(ConfigError::DecodeFileMissing(file.clone()));
+                }
+            }
+
+            return Ok(());
+        }
+
         standard_cli_check(
             self.list_supported_encoders,
             &self.encoder,
             &self.source_file,
             &self.files_directory,
-            self.was_ui_opened,
-        );
+        )?;
 
         // if you did not provide a source file, we'll be running on all expected files
         if self.source_file.is_empty() && !are_all_source_files_present(&self.files_directory) {
-            println!("You're missing some video source files to run the standard benchmark; you should have the following: \n{:?}", get_supported_inputs());
-            println!(
-                "Please download the ones you are missing from the project's readme section: {}",
-                get_repo_url()
-            );
-            println!("If you want to run the tool against a specific resolution/fps, download just that source file and specify it with '-s'");
-            error_with_ack(self.was_ui_opened);
+            return Err(ConfigError::MissingSourceFiles);
         }
 
         if self.source_file.is_empty() && !self.files_directory.is_empty() {
             // internally map the source_file and source_files_directory together
             self.source_file = format!("{}/{}", self.files_directory, self.source_file);
         }
+
+        return Ok(());
     }
 }
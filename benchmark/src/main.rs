@@ -4,25 +4,39 @@ use std::{env, panic};
 use clap::Parser;
 use text_io::read;
 
-use cli::cli_util::{is_dev, log_cli_header, pause};
-use cli::supported::{get_supported_encoders, get_supported_inputs};
+use cli::cli_util::{collect_host_fingerprint, error_with_ack, is_dev, log_cli_header, pause};
+use cli::supported::{get_supported_encoders, get_supported_inputs, get_usable_encoders};
 use codecs::amf::Amf;
 use codecs::apple_silicon::Apple;
+use codecs::av1_amf::AV1Amf;
+use codecs::av1_nvenc::AV1Nvenc;
 use codecs::av1_qsv::AV1QSV;
 use codecs::get_vendor_for_codec;
+use codecs::gop::Gop;
 use codecs::nvenc::Nvenc;
 use codecs::permute::Permute;
 use codecs::qsv::QSV;
 use codecs::vendor::Vendor;
 use engine::benchmark_engine::BenchmarkEngine;
+use engine::error::EngineError;
+use environment::encoders::get_ffmpeg_hwaccels;
+use environment::load::get_load_sampler;
+use ffmpeg::decoder::Decoder;
 use ffmpeg::metadata::MetaData;
+use ffmpeg::source::prepare_source;
+use gpus::device::Device;
 use gpus::get_gpus;
+use gpus::telemetry::get_sampler_for;
 use permutation::permutation::Permutation;
+use permutation::platform::{load_platform_profiles, PlatformProfile};
 
 use crate::benchmark_cli::BenchmarkCli;
 
 mod benchmark_cli;
 
+// stands in for the encoder of a decode benchmark, and names its results file
+static DECODE_ONLY: &str = "decode";
+
 fn main() {
     let result = panic::catch_unwind(|| {
         benchmark();
@@ -39,8 +53,7 @@ fn benchmark() {
     log_cli_header(String::from("Encoder Benchmark"));
     let mut cli = BenchmarkCli::new();
 
-    // check how many Nvidia GPU's there are
-    // eventually will add support for checking AMD/Intel GPU's too
+    // check how many GPU's there are, of any vendor
     let gpus = get_gpus();
 
     // if no args were provided, they will be prompted from the user
@@ -53,20 +66,74 @@ fn benchmark() {
         cli = BenchmarkCli::parse();
     }
 
-    cli.validate();
+    if let Err(e) = cli.validate() {
+        println!("Error: {}", e);
+        error_with_ack(cli.was_ui_opened());
+    }
+
+    if !cli.decode_files.is_empty() {
+        decode_benchmark(&cli);
+        return;
+    }
+
+    let platforms = match load_platform_profiles(&cli.platform, &cli.platform_directory) {
+        Ok(platforms) => platforms,
+        Err(e) => {
+            println!("Error: {}", e);
+            error_with_ack(cli.was_ui_opened());
+            return;
+        }
+    };
 
     let input_files = get_input_files(cli.source_file.clone(), cli.files_directory.clone());
     let mut engine = BenchmarkEngine::new(cli.log_output_directory.clone());
+    engine.set_ffmpeg_log_retention(cli.ffmpeg_logs);
+    engine.set_host_fingerprint(collect_host_fingerprint());
+    if let Some(sampler) = get_sampler_for(get_vendor_for_codec(&cli.encoder), cli.gpu) {
+        engine.set_telemetry_sampler(sampler);
+    }
+    if let Some(sampler) = get_load_sampler() {
+        engine.set_load_sampler(sampler);
+    }
+    engine.set_background_load_check(cli.max_background_load, cli.rerun_on_background_load);
+    engine.set_trials(cli.trials, cli.warmup);
+    engine.set_platforms(platforms.clone(), cli.restrict_to_platform);
 
     // prepare permutations for the engine to run over
     for input in input_files {
+        // compressed sources get decoded up front so decoding isn't part of the encode measurement
+        let input = match prepare_source(&input, cli.verbose) {
+            Ok(input) => input,
+            Err(e) => {
+                println!("Error: {}", e);
+                error_with_ack(cli.was_ui_opened());
+                return;
+            }
+        };
         let mut permutation = Permutation::new(input, cli.encoder.clone());
         let settings = get_benchmark_settings_for(&cli);
-        let bitrate = get_bitrate_for(&permutation.get_metadata(), cli.encoder.clone());
+        let metadata = match permutation.get_metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("Error: {}", e);
+                error_with_ack(cli.was_ui_opened());
+                return;
+            }
+        };
+        let bitrate = get_bitrate_for(&metadata, cli.encoder.clone());
 
         permutation.bitrate = bitrate;
         permutation.encoder_settings = settings;
         permutation.verbose = cli.verbose;
+        permutation.fail_on_slow_storage = cli.fail_on_slow_storage;
+        permutation.stage_in_memory = cli.stage_in_memory;
+        permutation.check_bitrate = cli.check_bitrate;
+        permutation.vbv_buffer = cli.vbv_buffer;
+        permutation.verify_settings = cli.verify_settings;
+        permutation.no_b_frame = cli.no_b_frame;
+        if cli.restrict_to_platform {
+            fit_to_platform(&mut permutation, &metadata, &platforms);
+        }
 
         // tell this encode run that we'll want to preserve the file output
         if cli.decode {
@@ -82,20 +149,85 @@ fn benchmark() {
         if cli.decode {
             let mut decode_permutation = permutation.clone();
             decode_permutation.decode_run = true;
+            decode_permutation.verify_decode = cli.verify_decode;
             engine.add(decode_permutation);
         }
     }
 
-    engine.run();
+    run_engine(&mut engine);
+}
+
+// benchmarks every decoder against every one of the existing files, leaving the files as they are
+fn decode_benchmark(cli: &BenchmarkCli) {
+    let mut engine = BenchmarkEngine::new(cli.log_output_directory.clone());
+    engine.set_ffmpeg_log_retention(cli.ffmpeg_logs);
+    engine.set_host_fingerprint(collect_host_fingerprint());
+    // there's no encoder to tell the vendor by, so the first gpu with the chosen index is sampled
+    if let Some(gpu) = get_gpus().into_iter().find(|gpu| gpu.index == cli.gpu) {
+        if let Some(sampler) = get_sampler_for(gpu.vendor, cli.gpu) {
+            engine.set_telemetry_sampler(sampler);
+        }
+    }
+    if let Some(sampler) = get_load_sampler() {
+        engine.set_load_sampler(sampler);
+    }
+    engine.set_background_load_check(cli.max_background_load, cli.rerun_on_background_load);
+    engine.set_trials(cli.trials, cli.warmup);
+
+    let decoders = get_decoders(cli);
+    for file in &cli.decode_files {
+        for decoder in &decoders {
+            let mut permutation = Permutation::new(file.clone(), String::from(DECODE_ONLY));
+            permutation.decode_run = true;
+            permutation.decode_only = true;
+            permutation.decoder = Some(decoder.clone());
+            permutation.verify_decode = cli.verify_decode;
+            permutation.verbose = cli.verbose;
+            permutation.fail_on_slow_storage = cli.fail_on_slow_storage;
+            permutation.stage_in_memory = cli.stage_in_memory;
+            engine.add(permutation);
+        }
+    }
+
+    run_engine(&mut engine);
+}
+
+// the cpu, along with every hwaccel this ffmpeg build has, unless the user picked their own
+fn get_decoders(cli: &BenchmarkCli) -> Vec<Decoder> {
+    if !cli.decoders.is_empty() {
+        return cli.decoders.clone();
+    }
+
+    let mut decoders = vec![Decoder::Software];
+    for hwaccel in get_ffmpeg_hwaccels().unwrap_or_default() {
+        decoders.push(Decoder::Hwaccel(hwaccel));
+    }
+
+    return decoders;
+}
+
+fn run_engine(engine: &mut BenchmarkEngine) {
+    match engine.run() {
+        Ok(()) => {}
+        Err(EngineError::Cancelled) => {
+            println!("Ctrl-C acknowledged, program exiting...");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            error_with_ack(true);
+        }
+    }
+
     pause();
 }
 
-fn read_user_input(cli: &mut BenchmarkCli, gpus: Vec<String>) {
+fn read_user_input(cli: &mut BenchmarkCli, gpus: Vec<Device>) {
     // if more than 1 GPU is identified, ask for the user to choose which one
     if gpus.len() > 1 {
         loop {
-            let str_vec = gpus.iter().map(|s| &**s).collect();
-            print_options(str_vec);
+            let descriptions: Vec<String> = gpus.iter().map(|gpu| gpu.to_string()).collect();
+            print_options(descriptions.iter().map(|s| &**s).collect());
             print!("Choose GPU [0-{}]: ", gpus.len() - 1);
             let input: String = read!("{}");
 
@@ -107,7 +239,8 @@ fn read_user_input(cli: &mut BenchmarkCli, gpus: Vec<String>) {
                 if value as usize >= gpus.len() {
                     println!("Invalid input, try again...");
                 } else {
-                    cli.gpu = value;
+                    // encoders count their '-gpu' among the cards of their own vendor
+                    cli.gpu = gpus[value as usize].index;
                     println!();
                     break;
                 }
@@ -115,12 +248,19 @@ fn read_user_input(cli: &mut BenchmarkCli, gpus: Vec<String>) {
         }
     }
 
+    // only offer the encoders that can actually run here
+    let mut encoders = get_usable_encoders();
+    if encoders.is_empty() {
+        println!("Warning: none of the supported encoders passed a test encode on this machine, listing all of them anyway");
+        encoders = get_supported_encoders()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+    }
+
     loop {
-        print_options(get_supported_encoders().to_vec());
-        print!(
-            "Choose encoder [0-{}]: ",
-            get_supported_encoders().len() - 1
-        );
+        print_options(encoders.iter().map(|s| &**s).collect());
+        print!("Choose encoder [0-{}]: ", encoders.len() - 1);
         let input: String = read!("{}");
 
         if !is_numeric(&input) {
@@ -128,10 +268,10 @@ fn read_user_input(cli: &mut BenchmarkCli, gpus: Vec<String>) {
         } else {
             let value: usize = input.parse().unwrap();
 
-            if value >= get_supported_encoders().len() {
+            if value >= encoders.len() {
                 println!("Invalid input, try again...");
             } else {
-                cli.encoder = String::from(get_supported_encoders()[value]);
+                cli.encoder = encoders[value].clone();
                 break;
             }
         }
@@ -252,13 +392,23 @@ fn get_benchmark_settings_for(cli: &BenchmarkCli) -> String {
 
     return match vendor {
         Vendor::Nvidia => {
-            let nvenc = Nvenc::new(cli.encoder == "hevc_nvenc", cli.gpu, cli.no_b_frame);
-            nvenc.get_benchmark_settings()
+            if cli.encoder.contains("av1") {
+                let nvenc_av1 = AV1Nvenc::new(cli.gpu);
+                nvenc_av1.get_benchmark_settings()
+            } else {
+                let nvenc = Nvenc::new(cli.encoder == "hevc_nvenc", cli.gpu, cli.no_b_frame);
+                nvenc.get_benchmark_settings()
+            }
         }
 
         Vendor::AMD => {
-            let amf = Amf::new(cli.encoder == "hevc_amf", cli.gpu);
-            amf.get_benchmark_settings()
+            if cli.encoder.contains("av1") {
+                let amf_av1 = AV1Amf::new(cli.gpu);
+                amf_av1.get_benchmark_settings()
+            } else {
+                let amf = Amf::new(cli.encoder == "hevc_amf", cli.gpu);
+                amf.get_benchmark_settings()
+            }
         }
 
         Vendor::IntelQSV => {
@@ -289,18 +439,51 @@ fn get_benchmark_settings_for(cli: &BenchmarkCli) -> String {
     };
 }
 
-fn get_bitrate_for(metadata: &MetaData, string: String) -> u32 {
-    if string.contains("nvenc") {
-        return *Nvenc::get_resolution_to_bitrate_map(metadata.fps)
-            .get(&metadata.get_res())
-            .unwrap();
-    } else {
-        return *Amf::get_resolution_to_bitrate_map(metadata.fps)
-            .get(&metadata.get_res())
-            .unwrap();
+// streams the resolution the way the first platform that takes it wants it, bringing the bitrate down to its limit
+fn fit_to_platform(
+    permutation: &mut Permutation,
+    metadata: &MetaData,
+    platforms: &Vec<PlatformProfile>,
+) {
+    for platform in platforms {
+        if !platform.takes_codec(&permutation.encoder) {
+            continue;
+        }
+
+        if let Some(max_bitrate) = platform.get_max_bitrate(metadata.height, metadata.fps.as_f64())
+        {
+            permutation.gop = Some(Gop::new(platform.keyframe_interval));
+            permutation.bitrate = permutation.bitrate.min(max_bitrate.floor() as u32);
+            return;
+        }
     }
 }
 
+fn get_bitrate_for(metadata: &MetaData, encoder: String) -> u32 {
+    let (width, height, fps) = (metadata.width, metadata.height, metadata.fps.as_f64());
+
+    return match get_vendor_for_codec(&encoder) {
+        Vendor::Nvidia => {
+            if encoder.contains("av1") {
+                AV1Nvenc::get_target_bitrate(width, height, fps)
+            } else {
+                Nvenc::get_target_bitrate(width, height, fps)
+            }
+        }
+        Vendor::IntelQSV => {
+            if encoder.contains("av1") {
+                AV1QSV::get_target_bitrate(width, height, fps)
+            } else {
+                QSV::get_target_bitrate(width, height, fps)
+            }
+        }
+        Vendor::Apple => Apple::get_target_bitrate(width, height, fps),
+        Vendor::AMD if encoder.contains("av1") => AV1Amf::get_target_bitrate(width, height, fps),
+        // amf bitrates are the highest of the bunch, so they're a safe fallback
+        Vendor::AMD | Vendor::Unknown => Amf::get_target_bitrate(width, height, fps),
+    };
+}
+
 fn get_input_files(source_file: String, source_files_directory: String) -> Vec<String> {
     if source_file.is_empty() {
         return get_supported_inputs()
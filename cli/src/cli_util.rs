@@ -5,9 +5,12 @@ use std::{env, fs};
 
 use figlet_rs::FIGfont;
 
-use environment::env::fail_if_environment_not_setup;
+use environment::encoders::{get_ffmpeg_hwaccels, probe_encoder};
+use environment::env::check_environment_setup;
+use environment::fingerprint::HostFingerprint;
 
-use crate::supported::{get_supported_encoders, get_supported_inputs, is_encoder_supported};
+use crate::error::ConfigError;
+use crate::supported::{get_encoder_capabilities, get_supported_inputs, is_encoder_supported};
 
 pub fn is_dev() -> bool {
     let args: Vec<String> = env::args().collect();
@@ -23,11 +26,16 @@ pub fn get_video_files(source_file_directory: &String) -> Vec<String> {
         "."
     };
 
-    let paths = fs::read_dir(locale).unwrap();
+    // a directory we can't read has no video files in it as far as we're concerned
+    let paths = match fs::read_dir(locale) {
+        Ok(paths) => paths,
+        Err(_) => return Vec::new(),
+    };
+
     return paths
         .filter_map(|e| e.ok())
-        .filter(|p| p.file_type().unwrap().is_file())
-        .map(|p| p.file_name().to_str().unwrap().to_string())
+        .filter(|p| p.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|p| p.file_name().to_string_lossy().to_string())
         .collect::<Vec<String>>();
 }
 
@@ -48,29 +56,30 @@ pub fn standard_cli_check(
     encoder: &String,
     source_file: &String,
     source_files_directory: &String,
-    was_ui_opened: bool,
-) {
-    fail_if_environment_not_setup();
+) -> Result<(), ConfigError> {
+    check_environment_setup()?;
 
+    // listing the encoders is all the user asked for, so this isn't an error
     if show_encoders {
-        println!("Supported supported: {:?}", get_supported_encoders());
+        log_encoder_capabilities();
         dont_disappear::any_key_to_continue::default();
         std::process::exit(0);
     }
 
     // this means no encoder was specified
     if encoder == "encoder" {
-        println!("Error: Please provide one of the supported supported via '-e encoder_name'; for a list of supported supported use the '-l' argument");
-        error_with_ack(was_ui_opened);
+        return Err(ConfigError::NoEncoder);
     }
 
     // check if specified encoder is supported by the tool
     if !is_encoder_supported(&encoder) {
-        println!(
-            "Error: [{}] is not a supported encoder at the moment",
-            encoder
-        );
-        error_with_ack(was_ui_opened);
+        return Err(ConfigError::UnsupportedEncoder(encoder.clone()));
+    }
+
+    // supported by the tool doesn't mean this machine's ffmpeg and drivers can run it
+    let capability = probe_encoder(encoder);
+    if !capability.is_usable() {
+        return Err(ConfigError::UnusableEncoder(capability));
     }
 
     // determine whether the specified file exists or not; taking source file directory into account
@@ -81,8 +90,20 @@ pub fn standard_cli_check(
     };
 
     if !source_file.is_empty() && !Path::new(effective_file_path.as_str()).exists() {
-        println!("Error: [{}] source file does not exist; if you want to use one of the provided source files, download them from the project's readme:\n{}", effective_file_path, get_repo_url());
-        error_with_ack(was_ui_opened);
+        return Err(ConfigError::SourceFileMissing(effective_file_path));
+    }
+
+    return Ok(());
+}
+
+fn log_encoder_capabilities() {
+    println!("Supported encoders:");
+    for capability in get_encoder_capabilities() {
+        println!("  {}", capability.to_string());
+    }
+
+    if let Some(hwaccels) = get_ffmpeg_hwaccels() {
+        println!("Hardware decoders available to ffmpeg: {:?}", hwaccels);
     }
 }
 
@@ -126,6 +147,18 @@ fn log_tool_title_figlet(title: String) {
     fs::remove_file(small_font_file_name).expect("Not able to delete tmp file");
 }
 
+// details about this machine that end up next to the results, logged up front as well
+pub fn collect_host_fingerprint() -> HostFingerprint {
+    let host = HostFingerprint::collect(&load_version());
+    println!("Host:");
+    for line in host.to_lines() {
+        println!("  {}", line);
+    }
+
+    println!();
+    return host;
+}
+
 fn log_header() {
     println!("Version: {}", load_version());
     println!("Source code: {}\n", get_repo_url());
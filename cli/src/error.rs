@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt;
+
+use environment::encoders::EncoderCapability;
+use environment::error::EnvironmentError;
+
+use crate::cli_util::get_repo_url;
+use crate::supported::get_supported_inputs;
+
+// problems with what the user asked the tool to run
+#[derive(Debug)]
+pub enum ConfigError {
+    Environment(EnvironmentError),
+    // no encoder was specified
+    NoEncoder,
+    UnsupportedEncoder(String),
+    // supported by the tool, but not by this machine's ffmpeg build or hardware
+    UnusableEncoder(EncoderCapability),
+    // the encoder and the rate control it doesn't have
+    UnsupportedRateControl(String, String),
+    // the option and the encoder it doesn't apply to
+    UnsupportedOption(String, String),
+    SourceFileMissing(String),
+    // one of the existing files to benchmark decoding on
+    DecodeFileMissing(String),
+    NoSourceFile,
+    // the standard benchmark needs every supported source file present
+    MissingSourceFiles,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ConfigError::Environment(e) => write!(f, "{}", e),
+            ConfigError::NoEncoder => write!(f, "Please provide one of the supported encoders via '-e encoder_name'; for a list of supported encoders use the '-l' argument"),
+            ConfigError::UnsupportedEncoder(encoder) => write!(f, "[{}] is not a supported encoder at the moment", encoder),
+            ConfigError::UnusableEncoder(capability) => write!(f, "The encoder can't be used on this machine; {}\nUse the '-l' argument to see which encoders can", capability.to_string()),
+            ConfigError::UnsupportedRateControl(encoder, rate_control) => write!(f, "[{}] doesn't support the [{}] rate control", encoder, rate_control),
+            ConfigError::UnsupportedOption(option, encoder) => write!(f, "[{}] doesn't apply to the [{}] encoder", option, encoder),
+            ConfigError::SourceFileMissing(file) => write!(f, "[{}] source file does not exist; if you want to use one of the provided source files, download them from the project's readme:\n{}", file, get_repo_url()),
+            ConfigError::DecodeFileMissing(file) => write!(f, "[{}] does not exist, so there's nothing to decode", file),
+            ConfigError::NoSourceFile => write!(f, "No source file was provided to run on, please specify an input file"),
+            ConfigError::MissingSourceFiles => {
+                writeln!(f, "You're missing some video source files to run the standard benchmark; you should have the following: \n{:?}", get_supported_inputs())?;
+                writeln!(f, "Please download the ones you are missing from the project's readme section: {}", get_repo_url())?;
+                write!(f, "If you want to run the tool against a specific resolution/fps, download just that source file and specify it with '-s'")
+            }
+        };
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<EnvironmentError> for ConfigError {
+    fn from(e: EnvironmentError) -> Self {
+        return ConfigError::Environment(e);
+    }
+}
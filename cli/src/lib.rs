@@ -1,2 +1,3 @@
 pub mod cli_util;
+pub mod error;
 pub mod supported;
@@ -1,8 +1,12 @@
-const SUPPORTED_ENCODERS: [&'static str; 10] = [
+use environment::encoders::{probe_encoders, EncoderCapability};
+
+const SUPPORTED_ENCODERS: [&'static str; 12] = [
     "h264_nvenc",
     "hevc_nvenc",
+    "av1_nvenc",
     "h264_amf",
     "hevc_amf",
+    "av1_amf",
     "h264_qsv",
     "hevc_qsv",
     "av1_qsv",
@@ -26,10 +30,24 @@ pub fn is_encoder_supported(potential_encoder: &String) -> bool {
     return SUPPORTED_ENCODERS.contains(&potential_encoder.as_str());
 }
 
-pub fn get_supported_encoders() -> [&'static str; 10] {
+pub fn get_supported_encoders() -> [&'static str; 12] {
     return SUPPORTED_ENCODERS;
 }
 
 pub fn get_supported_inputs() -> [&'static str; 8] {
     return ENCODE_FILES;
 }
+
+// every supported encoder, along with whether this machine can actually run it
+pub fn get_encoder_capabilities() -> Vec<EncoderCapability> {
+    println!("Checking which encoders can run on this machine...");
+    return probe_encoders(&SUPPORTED_ENCODERS);
+}
+
+pub fn get_usable_encoders() -> Vec<String> {
+    return get_encoder_capabilities()
+        .into_iter()
+        .filter(|capability| capability.is_usable())
+        .map(|capability| capability.encoder)
+        .collect();
+}
@@ -1,16 +1,15 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
 use crate::permute::Permute;
-use crate::resolutions::map_res_to_bitrate;
+use crate::rate_control::RateControl;
+use crate::vendor::Vendor;
 
 pub struct Amf {
     usages: Vec<&'static str>,
     qualities: Vec<&'static str>,
     profiles: Vec<&'static str>,
     profile_tiers: Vec<&'static str>,
-    rate_controls: Vec<&'static str>,
+    rate_control: RateControl,
     // might be able to make this the size we're expecting
     permutations: Vec<String>,
     index: i32,
@@ -28,9 +27,9 @@ impl Amf {
             } else {
                 vec!["main", "high", "constrained_baseline", "constrained_high"]
             },
-            // leaving out vbr rate controls as these are not ideal for game streaming
             profile_tiers: get_amf_profile_tiers(is_hevc),
-            rate_controls: vec!["cbr"],
+            // cbr unless asked otherwise, as it's what game streaming needs
+            rate_control: RateControl::Cbr,
             permutations: Vec::new(),
             // starts at -1, so that first next() will return the first element
             index: -1,
@@ -48,6 +47,10 @@ impl Amf {
         );
     }
 
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
     fn has_next(&self) -> bool {
         return self.index != (self.permutations.len() - 1) as i32;
     }
@@ -76,7 +79,7 @@ struct AmfSettings {
     quality: &'static str,
     profile: &'static str,
     profile_tier: &'static str,
-    rate_control: &'static str,
+    rate_control: RateControl,
     gpu: u8,
 }
 
@@ -95,10 +98,8 @@ impl AmfSettings {
             args.push_str(self.profile_tier);
         }
 
-        args.push_str(" -rc ");
-        args.push_str(self.rate_control);
-        // always set this to constant bit rate to ensure reliable stream
-        args.push_str(" -cbr true");
+        args.push(' ');
+        args.push_str(&self.rate_control.get_encoder_args(Vendor::AMD).unwrap());
         args.push_str(" -gpu ");
         args.push_str(self.gpu.to_string().as_str());
 
@@ -134,19 +135,13 @@ impl Permute for Amf {
         self.permutations.clear();
 
         let mut permutations = if self.profile_tiers.is_empty() {
-            vec![
-                &self.usages,
-                &self.qualities,
-                &self.profiles,
-                &self.rate_controls,
-            ]
+            vec![&self.usages, &self.qualities, &self.profiles]
         } else {
             vec![
                 &self.usages,
                 &self.qualities,
                 &self.profiles,
                 &self.profile_tiers,
-                &self.rate_controls,
             ]
         }
         .into_iter()
@@ -164,13 +159,12 @@ impl Permute for Amf {
             } else {
                 ""
             };
-            let rc_index = if !self.profile_tiers.is_empty() { 4 } else { 3 };
             let settings = AmfSettings {
                 usage: unwrapped_perm.get(0).unwrap(),
                 quality: unwrapped_perm.get(1).unwrap(),
                 profile: unwrapped_perm.get(2).unwrap(),
                 profile_tier,
-                rate_control: unwrapped_perm.get(rc_index).unwrap(),
+                rate_control: self.rate_control,
                 gpu: self.gpu,
             };
 
@@ -193,20 +187,9 @@ impl Permute for Amf {
         return &self.permutations;
     }
 
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32> {
-        let mut map: HashMap<String, u32> = HashMap::new();
-
+    fn get_calibration_bitrates() -> [u32; 4] {
         // bitrates are within 5Mb/s of each other, using higher one
         // note: these are the 60fps bitrate values
-        let mut bitrates: [u32; 4] = [20, 35, 50, 85];
-
-        // 120 fps is effectively double the bitrate
-        if fps == 120 {
-            bitrates.iter_mut().for_each(|b| *b = *b * 2);
-        }
-
-        map_res_to_bitrate(&mut map, bitrates);
-
-        return map;
+        return [20, 35, 50, 85];
     }
 }
@@ -1,9 +1,6 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
 use crate::permute::Permute;
-use crate::resolutions::map_res_to_bitrate;
 
 // TODO: add in values to permutation for -realtime, -prio_speed
 // all available encoders, h264, hevc, and prores have similar shared options other than profiles
@@ -163,20 +160,9 @@ impl Permute for Apple {
         return &self.permutations;
     }
 
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32> {
-        let mut map: HashMap<String, u32> = HashMap::new();
-
+    fn get_calibration_bitrates() -> [u32; 4] {
         // TODO: need to update this for apple silicon
-        let mut bitrates: [u32; 4] = [10, 20, 25, 55];
-
-        // 120 fps is effectively double the bitrate
-        if fps == 120 {
-            bitrates.iter_mut().for_each(|b| *b = *b * 2);
-        }
-
-        map_res_to_bitrate(&mut map, bitrates);
-
-        return map;
+        return [10, 20, 25, 55];
     }
 }
 
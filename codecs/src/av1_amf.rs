@@ -0,0 +1,206 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::amf::Amf;
+use crate::permute::Permute;
+use crate::rate_control::RateControl;
+use crate::resolutions::derive_av1_calibration;
+use crate::vendor::Vendor;
+
+// only rdna3 (7000 series) and newer GPUs have an av1 encoder
+pub struct AV1Amf {
+    usages: Vec<&'static str>,
+    qualities: Vec<&'static str>,
+    rate_control: RateControl,
+    // might be able to make this the size we're expecting
+    permutations: Vec<String>,
+    index: i32,
+    gpu: u8,
+}
+
+impl AV1Amf {
+    pub fn new(gpu: u8) -> Self {
+        Self {
+            usages: get_av1_amf_usages(),
+            qualities: get_av1_amf_quality(),
+            // cbr unless asked otherwise, as it's what game streaming needs
+            rate_control: RateControl::Cbr,
+            permutations: Vec::new(),
+            // starts at -1, so that first next() will return the first element
+            index: -1,
+            gpu,
+        }
+    }
+
+    pub fn get_benchmark_settings(&self) -> String {
+        return format!(
+            "-usage ultralowlatency -quality speed -profile:v main -align {} -rc cbr -cbr true -gpu {}",
+            AV1_AMF_ALIGNMENT, self.gpu
+        );
+    }
+
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
+    fn has_next(&self) -> bool {
+        return self.index != (self.permutations.len() - 1) as i32;
+    }
+}
+
+// the av1 encoder works in 64x16 blocks, so without this 1080p comes out padded to 1082p;
+// 1080p keeps that alignment everywhere except for 1080p itself
+static AV1_AMF_ALIGNMENT: &str = "1080p";
+
+fn get_av1_amf_usages() -> Vec<&'static str> {
+    return vec![
+        "transcoding",
+        "ultralowlatency",
+        "lowlatency",
+        "webcam",
+        "high_quality",
+        "lowlatency_high_quality",
+    ];
+}
+
+fn get_av1_amf_quality() -> Vec<&'static str> {
+    return vec!["speed", "balanced", "quality", "high_quality"];
+}
+
+#[derive(Copy, Clone)]
+struct AV1AmfSettings {
+    usage: &'static str,
+    quality: &'static str,
+    rate_control: RateControl,
+    gpu: u8,
+}
+
+impl fmt::Display for AV1AmfSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut args = String::new();
+        args.push_str("-usage ");
+        args.push_str(self.usage);
+        args.push_str(" -quality ");
+        args.push_str(self.quality);
+        args.push_str(" -profile:v main -align ");
+        args.push_str(AV1_AMF_ALIGNMENT);
+        args.push(' ');
+        args.push_str(&self.rate_control.get_encoder_args(Vendor::AMD).unwrap());
+        args.push_str(" -gpu ");
+        args.push_str(self.gpu.to_string().as_str());
+
+        return write!(f, "{}", args);
+    }
+}
+
+impl Iterator for AV1Amf {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.has_next() {
+            return None;
+        }
+
+        self.index += 1;
+
+        let usize_index = self.index as usize;
+        return Option::from((
+            usize_index,
+            self.permutations.get(usize_index).unwrap().to_string(),
+        ));
+    }
+}
+
+impl Permute for AV1Amf {
+    fn init(&mut self) -> &Vec<String> {
+        // reset index, otherwise we won't be able to iterate at all
+        self.index = -1;
+
+        // clear the vectors if there were entries before
+        self.permutations.clear();
+
+        let permutations = vec![&self.usages, &self.qualities]
+            .into_iter()
+            .multi_cartesian_product();
+
+        for unwrapped_perm in permutations {
+            let settings = AV1AmfSettings {
+                usage: unwrapped_perm.first().unwrap(),
+                quality: unwrapped_perm.get(1).unwrap(),
+                rate_control: self.rate_control,
+                gpu: self.gpu,
+            };
+
+            self.permutations.push(settings.to_string());
+        }
+
+        return &self.permutations;
+    }
+
+    fn run_standard_only(&mut self) -> &Vec<String> {
+        // reset index, otherwise we won't be able to iterate at all
+        self.index = -1;
+
+        // clear the vectors if there were entries before
+        self.permutations.clear();
+
+        self.permutations.push(self.get_benchmark_settings());
+        return &self.permutations;
+    }
+
+    fn get_calibration_bitrates() -> [u32; 4] {
+        return derive_av1_calibration(Amf::get_calibration_bitrates());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::av1_amf::AV1Amf;
+    use crate::permute::Permute;
+    use crate::rate_control::RateControl;
+
+    #[test]
+    fn iterate_to_end_test() {
+        let mut av1_amf = AV1Amf::new(0);
+        let perm_count = av1_amf.init().len();
+
+        let mut total = 0;
+        for (_usize, _string) in av1_amf.by_ref() {
+            total += 1
+        }
+
+        assert_eq!(total, perm_count);
+    }
+
+    #[test]
+    fn total_permutations_test() {
+        let mut av1_amf = AV1Amf::new(0);
+        av1_amf.init();
+        assert_eq!(
+            av1_amf.init().len(),
+            av1_amf.usages.len() * av1_amf.qualities.len()
+        );
+    }
+
+    #[test]
+    fn settings_test() {
+        let mut av1_amf = AV1Amf::new(1);
+        assert_eq!(
+            av1_amf.init()[0],
+            "-usage transcoding -quality speed -profile:v main -align 1080p -rc cbr -cbr true -gpu 1"
+        );
+
+        av1_amf.set_rate_control(RateControl::Cqp(28));
+        assert!(av1_amf.init()[0].contains("-align 1080p -rc cqp -qp_i 28"));
+    }
+
+    #[test]
+    fn benchmark_settings_test() {
+        let av1_amf = AV1Amf::new(0);
+        assert!(av1_amf
+            .get_benchmark_settings()
+            .contains("-usage ultralowlatency -quality speed"));
+        assert!(av1_amf.get_benchmark_settings().contains("-align 1080p"));
+    }
+}
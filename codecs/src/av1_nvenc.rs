@@ -0,0 +1,199 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::nvenc::Nvenc;
+use crate::permute::Permute;
+use crate::rate_control::RateControl;
+use crate::resolutions::derive_av1_calibration;
+use crate::vendor::Vendor;
+
+// only ada lovelace (40 series) and newer GPUs have an av1 encoder
+pub struct AV1Nvenc {
+    presets: Vec<&'static str>,
+    tunes: Vec<&'static str>,
+    tiers: Vec<&'static str>,
+    rate_control: RateControl,
+    // might be able to make this the size we're expecting
+    permutations: Vec<String>,
+    index: i32,
+    gpu: u8,
+}
+
+impl AV1Nvenc {
+    pub fn new(gpu: u8) -> Self {
+        Self {
+            presets: get_av1_nvenc_presets(),
+            tunes: get_av1_nvenc_tunes(),
+            // av1 only has the main profile, its tiers are what differ
+            tiers: vec!["0", "1"],
+            // cbr unless asked otherwise, as it's what game streaming needs
+            rate_control: RateControl::Cbr,
+            permutations: Vec::new(),
+            // starts at -1, so that first next() will return the first element
+            index: -1,
+            gpu,
+        }
+    }
+
+    pub fn get_benchmark_settings(&self) -> String {
+        return format!(
+            "-preset p1 -tune ll -profile:v main -tier 0 -rc cbr -cbr true -gpu {}",
+            self.gpu
+        );
+    }
+
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
+    fn has_next(&self) -> bool {
+        return self.index != (self.permutations.len() - 1) as i32;
+    }
+}
+
+fn get_av1_nvenc_presets() -> Vec<&'static str> {
+    return vec!["p1", "p2", "p3", "p4", "p5", "p6", "p7"];
+}
+
+fn get_av1_nvenc_tunes() -> Vec<&'static str> {
+    return vec!["hq", "ll", "ull"];
+}
+
+#[derive(Copy, Clone)]
+struct AV1NvencSettings {
+    preset: &'static str,
+    tune: &'static str,
+    tier: &'static str,
+    rate_control: RateControl,
+    gpu: u8,
+}
+
+impl fmt::Display for AV1NvencSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut args = String::new();
+        args.push_str("-preset ");
+        args.push_str(self.preset);
+        args.push_str(" -tune ");
+        args.push_str(self.tune);
+        args.push_str(" -profile:v main -tier ");
+        args.push_str(self.tier);
+        args.push(' ');
+        args.push_str(&self.rate_control.get_encoder_args(Vendor::Nvidia).unwrap());
+        args.push_str(" -gpu ");
+        args.push_str(self.gpu.to_string().as_str());
+
+        return write!(f, "{}", args);
+    }
+}
+
+impl Iterator for AV1Nvenc {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.has_next() {
+            return None;
+        }
+
+        self.index += 1;
+
+        let usize_index = self.index as usize;
+        return Option::from((
+            usize_index,
+            self.permutations.get(usize_index).unwrap().to_string(),
+        ));
+    }
+}
+
+impl Permute for AV1Nvenc {
+    fn init(&mut self) -> &Vec<String> {
+        // reset index, otherwise we won't be able to iterate at all
+        self.index = -1;
+
+        // clear the vectors if there were entries before
+        self.permutations.clear();
+
+        let permutations = vec![&self.presets, &self.tunes, &self.tiers]
+            .into_iter()
+            .multi_cartesian_product();
+
+        for unwrapped_perm in permutations {
+            let settings = AV1NvencSettings {
+                preset: unwrapped_perm.first().unwrap(),
+                tune: unwrapped_perm.get(1).unwrap(),
+                tier: unwrapped_perm.get(2).unwrap(),
+                rate_control: self.rate_control,
+                gpu: self.gpu,
+            };
+
+            self.permutations.push(settings.to_string());
+        }
+
+        return &self.permutations;
+    }
+
+    fn run_standard_only(&mut self) -> &Vec<String> {
+        // reset index, otherwise we won't be able to iterate at all
+        self.index = -1;
+
+        // clear the vectors if there were entries before
+        self.permutations.clear();
+
+        self.permutations.push(self.get_benchmark_settings());
+        return &self.permutations;
+    }
+
+    fn get_calibration_bitrates() -> [u32; 4] {
+        return derive_av1_calibration(Nvenc::get_calibration_bitrates());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::av1_nvenc::AV1Nvenc;
+    use crate::permute::Permute;
+    use crate::rate_control::RateControl;
+
+    #[test]
+    fn iterate_to_end_test() {
+        let mut av1_nvenc = AV1Nvenc::new(0);
+        let perm_count = av1_nvenc.init().len();
+
+        let mut total = 0;
+        for (_usize, _string) in av1_nvenc.by_ref() {
+            total += 1
+        }
+
+        assert_eq!(total, perm_count);
+    }
+
+    #[test]
+    fn total_permutations_test() {
+        let mut av1_nvenc = AV1Nvenc::new(0);
+        av1_nvenc.init();
+        assert_eq!(av1_nvenc.init().len(), get_expected_len(&av1_nvenc));
+    }
+
+    #[test]
+    fn settings_test() {
+        let mut av1_nvenc = AV1Nvenc::new(1);
+        assert_eq!(
+            av1_nvenc.init()[0],
+            "-preset p1 -tune hq -profile:v main -tier 0 -rc cbr -cbr true -gpu 1"
+        );
+
+        av1_nvenc.set_rate_control(RateControl::Cq(30));
+        assert!(av1_nvenc.init()[0].contains("-tier 0 -rc vbr -cq 30 -gpu 1"));
+    }
+
+    #[test]
+    fn benchmark_settings_test() {
+        let mut av1_nvenc = AV1Nvenc::new(0);
+        assert_eq!(av1_nvenc.run_standard_only().len(), 1);
+        assert!(av1_nvenc.get_benchmark_settings().contains("-preset p1"));
+    }
+
+    fn get_expected_len(av1_nvenc: &AV1Nvenc) -> usize {
+        return av1_nvenc.presets.len() * av1_nvenc.tunes.len() * av1_nvenc.tiers.len();
+    }
+}
@@ -1,15 +1,15 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
 use crate::permute::Permute;
-use crate::resolutions::map_res_to_bitrate;
+use crate::rate_control::RateControl;
+use crate::vendor::Vendor;
 
 // we'll add more options when we add in extended permutation support
 pub struct AV1QSV {
     presets: Vec<&'static str>,
     profiles: Vec<&'static str>,
     async_depth: Vec<&'static str>,
+    rate_control: RateControl,
     // might be able to make this the size we're expecting
     permutations: Vec<String>,
     index: i32,
@@ -23,6 +23,7 @@ impl AV1QSV {
             // anything lower than 4 you get less fps performance, and anything higher than 4 you don't see much return
             // (maybe 1% lows might be a bit higher by a few fps)
             async_depth: vec!["4"],
+            rate_control: RateControl::Cbr,
             permutations: Vec::new(),
             // starts at -1, so that first next() will return the first element
             index: -1,
@@ -33,6 +34,10 @@ impl AV1QSV {
         return String::from("-preset veryfast -profile:v main");
     }
 
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
     fn has_next(&self) -> bool {
         return self.index != (self.permutations.len() - 1) as i32;
     }
@@ -49,6 +54,7 @@ struct AV1QSVSettings {
     preset: &'static str,
     profile: &'static str,
     async_depth: &'static str,
+    rate_control: RateControl,
 }
 
 impl AV1QSVSettings {
@@ -61,6 +67,16 @@ impl AV1QSVSettings {
         args.push_str(" -async_depth ");
         args.push_str(self.async_depth);
 
+        // cbr and vbr are picked by the bitrate options alone
+        let rate_control_args = self
+            .rate_control
+            .get_encoder_args(Vendor::IntelQSV)
+            .unwrap();
+        if !rate_control_args.is_empty() {
+            args.push(' ');
+            args.push_str(&rate_control_args);
+        }
+
         return args;
     }
 }
@@ -106,6 +122,7 @@ impl Permute for AV1QSV {
                 preset: unwrapped_perm.get(0).unwrap(),
                 profile: unwrapped_perm.get(1).unwrap(),
                 async_depth: unwrapped_perm.get(2).unwrap(),
+                rate_control: self.rate_control,
             };
 
             self.permutations.push(settings.to_string());
@@ -127,21 +144,10 @@ impl Permute for AV1QSV {
         return &self.permutations;
     }
 
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32> {
-        let mut map: HashMap<String, u32> = HashMap::new();
-
+    fn get_calibration_bitrates() -> [u32; 4] {
         // bitrates are within 5Mb/s of each other, using higher one
         // note: these are the 60fps bitrate values
         // TODO: add in bitrate values here after running the tool
-        let mut bitrates: [u32; 4] = [20, 30, 35, 70];
-
-        // 120 fps is effectively double the bitrate
-        if fps == 120 {
-            bitrates.iter_mut().for_each(|b| *b = *b * 2);
-        }
-
-        map_res_to_bitrate(&mut map, bitrates);
-
-        return map;
+        return [20, 30, 35, 70];
     }
 }
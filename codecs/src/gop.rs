@@ -0,0 +1,99 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vendor::Vendor;
+
+// a fixed keyframe interval, in seconds like streaming platforms ask for it (twitch and youtube want 2)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gop {
+    pub seconds: f64,
+}
+
+impl Gop {
+    pub fn new(seconds: f64) -> Self {
+        return Self { seconds };
+    }
+
+    // the interval in frames of a source at the given fps, never less than every frame
+    pub fn to_frames(&self, fps: f64) -> u32 {
+        return ((self.seconds * fps).round() as u32).max(1);
+    }
+}
+
+// the options that make the encoder place a keyframe every given number of frames, and only then;
+// scene cuts would otherwise insert keyframes of their own and shift the interval
+pub fn get_keyframe_args(vendor: Vendor, frames: u32) -> String {
+    let mut args = format!("-g {} -keyint_min {} -sc_threshold 0", frames, frames);
+    match vendor {
+        Vendor::Nvidia => args.push_str(" -forced-idr 1 -no-scenecut 1"),
+        // every i frame is an idr frame at an interval of 0
+        Vendor::IntelQSV => args.push_str(" -idr_interval 0"),
+        Vendor::AMD | Vendor::Apple | Vendor::Unknown => {}
+    }
+
+    return args;
+}
+
+// '2' or '2s', in seconds
+impl FromStr for Gop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return s
+            .trim_end_matches('s')
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| *seconds > 0.0)
+            .map(Gop::new)
+            .ok_or(format!(
+                "[{}] is not a keyframe interval, expected seconds above 0, i.e. 2 or 0.5s",
+                s
+            ));
+    }
+}
+
+impl fmt::Display for Gop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}s", self.seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gop::{get_keyframe_args, Gop};
+    use crate::vendor::Vendor;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!("2".parse::<Gop>(), Ok(Gop::new(2.0)));
+        assert_eq!("0.5s".parse::<Gop>(), Ok(Gop::new(0.5)));
+        assert!("0".parse::<Gop>().is_err());
+        assert!("-1".parse::<Gop>().is_err());
+        assert!("2f".parse::<Gop>().is_err());
+        assert_eq!(Gop::new(2.0).to_string(), "2s");
+    }
+
+    #[test]
+    fn to_frames_test() {
+        assert_eq!(Gop::new(2.0).to_frames(60.0), 120);
+        assert_eq!(Gop::new(2.0).to_frames(60000.0 / 1001.0), 120);
+        assert_eq!(Gop::new(1.0).to_frames(29.97), 30);
+        assert_eq!(Gop::new(0.001).to_frames(30.0), 1);
+    }
+
+    #[test]
+    fn keyframe_args_test() {
+        assert_eq!(
+            get_keyframe_args(Vendor::Nvidia, 120),
+            "-g 120 -keyint_min 120 -sc_threshold 0 -forced-idr 1 -no-scenecut 1"
+        );
+        assert_eq!(
+            get_keyframe_args(Vendor::IntelQSV, 60),
+            "-g 60 -keyint_min 60 -sc_threshold 0 -idr_interval 0"
+        );
+        assert_eq!(
+            get_keyframe_args(Vendor::AMD, 240),
+            "-g 240 -keyint_min 240 -sc_threshold 0"
+        );
+    }
+}
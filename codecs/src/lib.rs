@@ -2,10 +2,14 @@ use crate::vendor::Vendor;
 
 pub mod amf;
 pub mod apple_silicon;
+pub mod av1_amf;
+pub mod av1_nvenc;
 pub mod av1_qsv;
+pub mod gop;
 pub mod nvenc;
 pub mod permute;
 pub mod qsv;
+pub mod rate_control;
 mod resolutions;
 pub mod vendor;
 
@@ -1,15 +1,19 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use itertools::Itertools;
 
 use crate::permute::Permute;
-use crate::resolutions::map_res_to_bitrate;
+use crate::rate_control::RateControl;
+use crate::vendor::Vendor;
 
 pub struct Nvenc {
     presets: Vec<&'static str>,
     tunes: Vec<&'static str>,
     profiles: Vec<&'static str>,
-    rate_controls: Vec<&'static str>,
+    rate_control: RateControl,
+    // opt-in axes on top of the ones above, as each one multiplies the number of permutations
+    extra_axes: Vec<NvencAxis>,
     // allows for opting out of using b frames for GPUs that do not support it
     no_b_frames: bool,
     // might be able to make this the size we're expecting
@@ -25,8 +29,9 @@ impl Nvenc {
             tunes: get_nvenc_tunes(),
             // this is the only difference between hevc & h264
             profiles: if is_hevc { vec!["main"] } else { vec!["high"] },
-            // leaving out vbr rate controls as these are not ideal for game streaming
-            rate_controls: vec!["cbr"],
+            // cbr unless asked otherwise, as it's what game streaming needs
+            rate_control: RateControl::Cbr,
+            extra_axes: Vec::new(),
             no_b_frames,
             permutations: Vec::new(),
             // starts at -1, so that first next() will return the first element
@@ -43,6 +48,17 @@ impl Nvenc {
         );
     }
 
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
+    // permutes over each of the given axes as well; the order they're given in doesn't matter
+    pub fn enable_axes(&mut self, axes: Vec<NvencAxis>) {
+        self.extra_axes = axes;
+        self.extra_axes.sort();
+        self.extra_axes.dedup();
+    }
+
     fn has_next(&self) -> bool {
         return self.index != (self.permutations.len() - 1) as i32;
     }
@@ -56,12 +72,105 @@ fn get_nvenc_tunes() -> Vec<&'static str> {
     return vec!["hq", "ll", "ull"];
 }
 
-#[derive(Copy, Clone)]
+// the options OBS exposes under its advanced nvenc settings
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NvencAxis {
+    Lookahead,
+    // spatial and temporal adaptive quantization, along with the spatial strength
+    Aq,
+    Multipass,
+    BFrames,
+    BRefMode,
+    WeightedPred,
+    ZeroLatency,
+}
+
+impl NvencAxis {
+    pub fn all() -> Vec<NvencAxis> {
+        return vec![
+            NvencAxis::Lookahead,
+            NvencAxis::Aq,
+            NvencAxis::Multipass,
+            NvencAxis::BFrames,
+            NvencAxis::BRefMode,
+            NvencAxis::WeightedPred,
+            NvencAxis::ZeroLatency,
+        ];
+    }
+}
+
+impl FromStr for NvencAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return NvencAxis::all()
+            .into_iter()
+            .find(|axis| axis.to_string() == s.to_lowercase())
+            .ok_or(format!(
+                "[{}] is not an nvenc axis, expected any of: {}",
+                s,
+                NvencAxis::all().iter().join(", ")
+            ));
+    }
+}
+
+impl fmt::Display for NvencAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            NvencAxis::Lookahead => write!(f, "lookahead"),
+            NvencAxis::Aq => write!(f, "aq"),
+            NvencAxis::Multipass => write!(f, "multipass"),
+            NvencAxis::BFrames => write!(f, "bf"),
+            NvencAxis::BRefMode => write!(f, "b_ref_mode"),
+            NvencAxis::WeightedPred => write!(f, "weighted_pred"),
+            NvencAxis::ZeroLatency => write!(f, "zerolatency"),
+        };
+    }
+}
+
+// every value an axis is permuted over; b frames stay off when the user opted out of them
+fn get_nvenc_axis_values(axis: NvencAxis, no_b_frames: bool) -> Vec<&'static str> {
+    return match axis {
+        NvencAxis::Lookahead => vec![
+            "-rc-lookahead 0",
+            "-rc-lookahead 8",
+            "-rc-lookahead 16",
+            "-rc-lookahead 32",
+        ],
+        NvencAxis::Aq => vec![
+            "-spatial-aq 0 -temporal-aq 0",
+            "-spatial-aq 1 -aq-strength 4",
+            "-spatial-aq 1 -aq-strength 8",
+            "-spatial-aq 1 -aq-strength 15",
+            "-temporal-aq 1",
+            "-spatial-aq 1 -aq-strength 8 -temporal-aq 1",
+        ],
+        NvencAxis::Multipass => vec![
+            "-multipass disabled",
+            "-multipass qres",
+            "-multipass fullres",
+        ],
+        NvencAxis::BFrames if no_b_frames => vec!["-bf 0"],
+        NvencAxis::BFrames => vec!["-bf 0", "-bf 1", "-bf 2", "-bf 3", "-bf 4"],
+        NvencAxis::BRefMode if no_b_frames => vec!["-b_ref_mode disabled"],
+        NvencAxis::BRefMode => vec![
+            "-b_ref_mode disabled",
+            "-b_ref_mode each",
+            "-b_ref_mode middle",
+        ],
+        NvencAxis::WeightedPred => vec!["-weighted_pred 0", "-weighted_pred 1"],
+        NvencAxis::ZeroLatency => vec!["-zerolatency 0", "-zerolatency 1"],
+    };
+}
+
+#[derive(Clone)]
 struct NvencSettings {
     preset: &'static str,
     tune: &'static str,
     profile: &'static str,
-    rate_control: &'static str,
+    rate_control: RateControl,
+    // one value of each of the enabled extra axes
+    extra_args: Vec<&'static str>,
     no_b_frame: bool,
     gpu: u8,
 }
@@ -75,15 +184,18 @@ impl NvencSettings {
         args.push_str(self.tune);
         args.push_str(" -profile:v ");
         args.push_str(self.profile);
-        args.push_str(" -rc ");
-        args.push_str(self.rate_control);
+        args.push(' ');
+        args.push_str(&self.rate_control.get_encoder_args(Vendor::Nvidia).unwrap());
+        for extra_arg in &self.extra_args {
+            args.push(' ');
+            args.push_str(extra_arg);
+        }
+
         // user may have opted out of using b frames
         if self.no_b_frame {
             args.push_str(" -b_ref_mode 0");
         }
 
-        // always set this to constant bit rate to ensure reliable stream
-        args.push_str(" -cbr true");
         args.push_str(" -gpu ");
         args.push_str(self.gpu.to_string().as_str());
 
@@ -117,14 +229,16 @@ impl Permute for Nvenc {
         // clear the vectors if there were entries before
         self.permutations.clear();
 
-        let mut permutations = vec![
-            &self.presets,
-            &self.tunes,
-            &self.profiles,
-            &self.rate_controls,
-        ]
-        .into_iter()
-        .multi_cartesian_product();
+        let mut axes = vec![
+            self.presets.clone(),
+            self.tunes.clone(),
+            self.profiles.clone(),
+        ];
+        for axis in &self.extra_axes {
+            axes.push(get_nvenc_axis_values(*axis, self.no_b_frames));
+        }
+
+        let mut permutations = axes.iter().multi_cartesian_product();
 
         loop {
             let perm = permutations.next();
@@ -137,8 +251,10 @@ impl Permute for Nvenc {
                 preset: unwrapped_perm.get(0).unwrap(),
                 tune: unwrapped_perm.get(1).unwrap(),
                 profile: unwrapped_perm.get(2).unwrap(),
-                rate_control: unwrapped_perm.get(3).unwrap(),
-                no_b_frame: self.no_b_frames,
+                rate_control: self.rate_control,
+                extra_args: unwrapped_perm.iter().skip(3).map(|arg| **arg).collect(),
+                // the b_ref_mode axis already keeps it disabled
+                no_b_frame: self.no_b_frames && !self.extra_axes.contains(&NvencAxis::BRefMode),
                 gpu: self.gpu,
             };
 
@@ -161,28 +277,18 @@ impl Permute for Nvenc {
         return &self.permutations;
     }
 
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32> {
-        let mut map: HashMap<String, u32> = HashMap::new();
-
+    fn get_calibration_bitrates() -> [u32; 4] {
         // bitrates are within 5Mb/s of each other, using higher one
         // note: these are the 60fps bitrate values
-        let mut bitrates: [u32; 4] = [10, 20, 25, 55];
-
-        // 120 fps is effectively double the bitrate
-        if fps == 120 {
-            bitrates.iter_mut().for_each(|b| *b = *b * 2);
-        }
-
-        map_res_to_bitrate(&mut map, bitrates);
-
-        return map;
+        return [10, 20, 25, 55];
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::nvenc::Nvenc;
+    use crate::nvenc::{get_nvenc_axis_values, Nvenc, NvencAxis};
     use crate::permute::Permute;
+    use crate::rate_control::RateControl;
 
     #[test]
     fn create_h264_test() {
@@ -230,10 +336,57 @@ mod tests {
         assert_eq!(nvenc.no_b_frames, true);
     }
 
+    #[test]
+    fn rate_control_test() {
+        let mut nvenc = Nvenc::new(false, 0, false);
+        assert!(nvenc.init()[0].contains("-rc cbr -cbr true"));
+
+        nvenc.set_rate_control(RateControl::Cqp(23));
+        assert_eq!(nvenc.init().len(), get_expected_len(&nvenc));
+        let settings = nvenc.init()[0].clone();
+        assert!(settings.contains("-rc constqp -qp 23"));
+        assert!(!settings.contains("-cbr true"));
+    }
+
+    #[test]
+    fn extra_axes_test() {
+        let mut nvenc = Nvenc::new(false, 0, false);
+        nvenc.enable_axes(vec![NvencAxis::Multipass, NvencAxis::Lookahead]);
+        assert_eq!(nvenc.init().len(), get_expected_len(&nvenc));
+
+        // axes are always added in the same order, no matter the order they were enabled in
+        let settings = nvenc.init()[0].clone();
+        assert!(settings.contains("-rc cbr -cbr true -rc-lookahead 0 -multipass disabled"));
+    }
+
+    #[test]
+    fn extra_axes_no_b_frames_test() {
+        let mut nvenc = Nvenc::new(false, 0, true);
+        nvenc.enable_axes(vec![NvencAxis::BFrames, NvencAxis::BRefMode]);
+        let permutations = nvenc.init().clone();
+        assert_eq!(permutations.len(), get_expected_len(&nvenc));
+        for settings in permutations {
+            assert!(settings.contains("-bf 0 -b_ref_mode disabled"));
+            assert!(!settings.contains("-b_ref_mode 0"));
+        }
+    }
+
+    #[test]
+    fn parse_axis_test() {
+        for axis in NvencAxis::all() {
+            assert_eq!(axis.to_string().parse::<NvencAxis>(), Ok(axis));
+        }
+
+        assert_eq!("AQ".parse::<NvencAxis>(), Ok(NvencAxis::Aq));
+        assert!("psycho_aq".parse::<NvencAxis>().is_err());
+    }
+
     fn get_expected_len(nvenc: &Nvenc) -> usize {
-        return nvenc.presets.len()
-            * nvenc.tunes.len()
-            * nvenc.profiles.len()
-            * nvenc.rate_controls.len();
+        let extra_len: usize = nvenc
+            .extra_axes
+            .iter()
+            .map(|axis| get_nvenc_axis_values(*axis, nvenc.no_b_frames).len())
+            .product();
+        return nvenc.presets.len() * nvenc.tunes.len() * nvenc.profiles.len() * extra_len;
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::resolutions::estimate_bitrate;
 
 pub trait Permute: Iterator {
     // calculates permutations and returns a reference of said permutations
@@ -7,6 +7,14 @@ pub trait Permute: Iterator {
     // overwrites the init values to just include permutations of standard runs
     fn run_standard_only(&mut self) -> &Vec<String>;
 
-    // takes in the fps being used; scales the necessary bitrate accordingly
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32>;
+    // the bitrates (in Mb/s) needed at 60fps for 720p, 1080p, 1440p and 4k respectively
+    fn get_calibration_bitrates() -> [u32; 4];
+
+    // takes in the resolution & fps being used; scales the necessary bitrate accordingly
+    fn get_target_bitrate(width: u32, height: u32, fps: f64) -> u32
+    where
+        Self: Sized,
+    {
+        return estimate_bitrate(Self::get_calibration_bitrates(), width, height, fps);
+    }
 }
@@ -1,13 +1,13 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
 use crate::permute::Permute;
-use crate::resolutions::map_res_to_bitrate;
+use crate::rate_control::RateControl;
+use crate::vendor::Vendor;
 
 pub struct QSV {
     presets: Vec<&'static str>,
     profiles: Vec<&'static str>,
+    rate_control: RateControl,
     // might be able to make this the size we're expecting
     permutations: Vec<String>,
     index: i32,
@@ -24,6 +24,7 @@ impl QSV {
             } else {
                 vec!["unknown", "baseline", "main", "high"]
             },
+            rate_control: RateControl::Cbr,
             permutations: Vec::new(),
             // starts at -1, so that first next() will return the first element
             index: -1,
@@ -34,6 +35,10 @@ impl QSV {
         return String::from("-preset faster -profile main");
     }
 
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = rate_control;
+    }
+
     fn has_next(&self) -> bool {
         return self.index != (self.permutations.len() - 1) as i32;
     }
@@ -49,6 +54,7 @@ fn get_qsv_presets() -> Vec<&'static str> {
 struct QSVSettings {
     preset: &'static str,
     profile: &'static str,
+    rate_control: RateControl,
 }
 
 impl QSVSettings {
@@ -59,6 +65,16 @@ impl QSVSettings {
         args.push_str(" -profile:v ");
         args.push_str(self.profile);
 
+        // cbr and vbr are picked by the bitrate options alone
+        let rate_control_args = self
+            .rate_control
+            .get_encoder_args(Vendor::IntelQSV)
+            .unwrap();
+        if !rate_control_args.is_empty() {
+            args.push(' ');
+            args.push_str(&rate_control_args);
+        }
+
         return args;
     }
 }
@@ -103,6 +119,7 @@ impl Permute for QSV {
             let settings = QSVSettings {
                 preset: unwrapped_perm.get(0).unwrap(),
                 profile: unwrapped_perm.get(1).unwrap(),
+                rate_control: self.rate_control,
             };
 
             self.permutations.push(settings.to_string());
@@ -124,21 +141,10 @@ impl Permute for QSV {
         return &self.permutations;
     }
 
-    fn get_resolution_to_bitrate_map(fps: u32) -> HashMap<String, u32> {
-        let mut map: HashMap<String, u32> = HashMap::new();
-
+    fn get_calibration_bitrates() -> [u32; 4] {
         // bitrates are within 5Mb/s of each other, using higher one
         // note: these are the 60fps bitrate values
         // NOTE: these bitrates might not apply for Arc GPU's H264/HEVC encoders
-        let mut bitrates: [u32; 4] = [20, 30, 35, 70];
-
-        // 120 fps is effectively double the bitrate
-        if fps == 120 {
-            bitrates.iter_mut().for_each(|b| *b = *b * 2);
-        }
-
-        map_res_to_bitrate(&mut map, bitrates);
-
-        return map;
+        return [20, 30, 35, 70];
     }
 }
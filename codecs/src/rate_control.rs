@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vendor::Vendor;
+
+// how far above the target bitrate vbr may peak, and how large its buffer is, as multiples of the target
+const DEFAULT_VBR_MAX_FACTOR: f64 = 1.5;
+const DEFAULT_VBR_BUFFER_FACTOR: f64 = 2.0;
+
+// how an encoder decides how many bits to spend
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RateControl {
+    // the permutation's bitrate, all the time; what streaming platforms expect
+    Cbr,
+    // the permutation's bitrate on average, peaking up to max times it with a buffer of buffer times it
+    Vbr { max: f64, buffer: f64 },
+    // a constant quantizer for every frame, i.e. what recording workflows use
+    Cqp(u8),
+    // nvenc's constant quality, on top of its vbr mode
+    Cq(u8),
+    // qsv's intelligent constant quality
+    Icq(u8),
+}
+
+impl RateControl {
+    // the bitrate doesn't drive these, so the size of the output is only known once it's encoded
+    pub fn is_quality_targeted(&self) -> bool {
+        return match self {
+            RateControl::Cbr | RateControl::Vbr { .. } => false,
+            RateControl::Cqp(_) | RateControl::Cq(_) | RateControl::Icq(_) => true,
+        };
+    }
+
+    // the encoder options that select this rate control, None when the vendor's encoders don't have it
+    pub fn get_encoder_args(&self, vendor: Vendor) -> Option<String> {
+        return match (vendor, self) {
+            (Vendor::Nvidia, RateControl::Cbr) => Some(String::from("-rc cbr -cbr true")),
+            (Vendor::Nvidia, RateControl::Vbr { .. }) => Some(String::from("-rc vbr")),
+            (Vendor::Nvidia, RateControl::Cqp(qp)) => Some(format!("-rc constqp -qp {}", qp)),
+            (Vendor::Nvidia, RateControl::Cq(cq)) => Some(format!("-rc vbr -cq {}", cq)),
+            (Vendor::AMD, RateControl::Cbr) => Some(String::from("-rc cbr -cbr true")),
+            (Vendor::AMD, RateControl::Vbr { .. }) => Some(String::from("-rc vbr_peak")),
+            (Vendor::AMD, RateControl::Cqp(qp)) => {
+                Some(format!("-rc cqp -qp_i {} -qp_p {} -qp_b {}", qp, qp, qp))
+            }
+            // qsv picks its rate control from which of the bitrate and quality options are set
+            (Vendor::IntelQSV, RateControl::Cbr | RateControl::Vbr { .. }) => Some(String::new()),
+            (Vendor::IntelQSV, RateControl::Cqp(qp)) => Some(format!("-q:v {}", qp)),
+            (Vendor::IntelQSV, RateControl::Icq(quality)) => {
+                Some(format!("-global_quality {}", quality))
+            }
+            (Vendor::Apple, RateControl::Cbr) => Some(String::new()),
+            _ => None,
+        };
+    }
+
+    // the bitrate options for an encode at the given bitrate (in Mb/s)
+    pub fn get_bitrate_args(&self, bitrate: u32) -> String {
+        return match self {
+            RateControl::Cbr => format!("-b:v {}M", bitrate),
+            RateControl::Vbr { max, buffer } => format!(
+                "-b:v {}M -maxrate {}k -bufsize {}k",
+                bitrate,
+                get_kilobits(bitrate, *max),
+                get_kilobits(bitrate, *buffer)
+            ),
+            // without a bitrate of 0, nvenc caps constant quality at its default bitrate
+            RateControl::Cq(_) => String::from("-b:v 0"),
+            RateControl::Cqp(_) | RateControl::Icq(_) => String::new(),
+        };
+    }
+}
+
+// a multiple of a bitrate (in Mb/s) in whole kbit/s; factors like 1.1 aren't exact as floats and would print as 7.700000000000001M
+fn get_kilobits(bitrate: u32, factor: f64) -> u64 {
+    return (bitrate as f64 * factor * 1000.0).round() as u64;
+}
+
+impl Default for RateControl {
+    fn default() -> Self {
+        return RateControl::Cbr;
+    }
+}
+
+// 'cbr', 'vbr', 'vbr:<max>:<buffer>', 'cqp:<qp>', 'cq:<quality>' or 'icq:<quality>'
+impl FromStr for RateControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mode = parts.next().unwrap_or("").to_lowercase();
+        let values: Vec<&str> = parts.collect();
+
+        let parse_quality = |max: u8| -> Result<u8, String> {
+            return match values.as_slice() {
+                [value] => value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|value| *value <= max)
+                    .ok_or(format!("[{}] needs a value between 0 and {}", s, max)),
+                _ => Err(format!("[{}] needs a value, i.e. {}:23", s, mode)),
+            };
+        };
+
+        return match mode.as_str() {
+            "cbr" if values.is_empty() => Ok(RateControl::Cbr),
+            "vbr" => match values.as_slice() {
+                [] => Ok(RateControl::Vbr {
+                    max: DEFAULT_VBR_MAX_FACTOR,
+                    buffer: DEFAULT_VBR_BUFFER_FACTOR,
+                }),
+                [max, buffer] => match (max.parse::<f64>(), buffer.parse::<f64>()) {
+                    (Ok(max), Ok(buffer)) if max >= 1.0 && buffer > 0.0 => {
+                        Ok(RateControl::Vbr { max, buffer })
+                    }
+                    _ => Err(format!("[{}] needs a max of at least 1 and a buffer above 0, as multiples of the bitrate", s)),
+                },
+                _ => Err(format!("[{}] should be vbr, or vbr:<max>:<buffer> as multiples of the bitrate", s)),
+            },
+            "cqp" => Ok(RateControl::Cqp(parse_quality(51)?)),
+            "cq" => Ok(RateControl::Cq(parse_quality(51)?)),
+            "icq" => Ok(RateControl::Icq(parse_quality(51)?)),
+            "crf" => Err(String::from("crf is a software encoder (x264/x265) rate control; for the same constant quality use cq with nvenc, icq with qsv or cqp with nvenc, amf or qsv")),
+            _ => Err(format!(
+                "[{}] is not a rate control, expected one of: cbr, vbr, cqp:<qp>, cq:<quality>, icq:<quality>",
+                s
+            )),
+        };
+    }
+}
+
+impl fmt::Display for RateControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            RateControl::Cbr => write!(f, "cbr"),
+            RateControl::Vbr { max, buffer } => write!(f, "vbr:{}:{}", max, buffer),
+            RateControl::Cqp(qp) => write!(f, "cqp:{}", qp),
+            RateControl::Cq(cq) => write!(f, "cq:{}", cq),
+            RateControl::Icq(quality) => write!(f, "icq:{}", quality),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_control::RateControl;
+    use crate::vendor::Vendor;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!("cbr".parse::<RateControl>(), Ok(RateControl::Cbr));
+        assert_eq!(
+            "vbr".parse::<RateControl>(),
+            Ok(RateControl::Vbr {
+                max: 1.5,
+                buffer: 2.0
+            })
+        );
+        assert_eq!(
+            "vbr:2:4".parse::<RateControl>(),
+            Ok(RateControl::Vbr {
+                max: 2.0,
+                buffer: 4.0
+            })
+        );
+        assert_eq!("cqp:23".parse::<RateControl>(), Ok(RateControl::Cqp(23)));
+        assert_eq!("CQ:19".parse::<RateControl>(), Ok(RateControl::Cq(19)));
+        assert_eq!("icq:25".parse::<RateControl>(), Ok(RateControl::Icq(25)));
+    }
+
+    #[test]
+    fn parse_invalid_test() {
+        assert!("cqp".parse::<RateControl>().is_err());
+        assert!("cqp:99".parse::<RateControl>().is_err());
+        assert!("vbr:0.5:2".parse::<RateControl>().is_err());
+        assert!("cbr:10".parse::<RateControl>().is_err());
+        assert!("crf:23".parse::<RateControl>().is_err());
+        assert!("abr".parse::<RateControl>().is_err());
+    }
+
+    #[test]
+    fn to_string_round_trip_test() {
+        for input in ["cbr", "vbr:1.5:2", "cqp:23", "cq:19", "icq:25"] {
+            assert_eq!(input.parse::<RateControl>().unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn encoder_args_test() {
+        assert_eq!(
+            RateControl::Cbr.get_encoder_args(Vendor::Nvidia).unwrap(),
+            "-rc cbr -cbr true"
+        );
+        assert_eq!(
+            RateControl::Cqp(23).get_encoder_args(Vendor::AMD).unwrap(),
+            "-rc cqp -qp_i 23 -qp_p 23 -qp_b 23"
+        );
+        assert_eq!(
+            RateControl::Icq(25)
+                .get_encoder_args(Vendor::IntelQSV)
+                .unwrap(),
+            "-global_quality 25"
+        );
+
+        // not every vendor has every rate control
+        assert!(RateControl::Cq(19).get_encoder_args(Vendor::AMD).is_none());
+        assert!(RateControl::Icq(25)
+            .get_encoder_args(Vendor::Nvidia)
+            .is_none());
+        assert!(RateControl::Cqp(23)
+            .get_encoder_args(Vendor::Apple)
+            .is_none());
+    }
+
+    #[test]
+    fn bitrate_args_test() {
+        assert_eq!(RateControl::Cbr.get_bitrate_args(10), "-b:v 10M");
+        assert_eq!(
+            "vbr".parse::<RateControl>().unwrap().get_bitrate_args(10),
+            "-b:v 10M -maxrate 15000k -bufsize 20000k"
+        );
+        assert_eq!(
+            "vbr:1.1:2"
+                .parse::<RateControl>()
+                .unwrap()
+                .get_bitrate_args(7),
+            "-b:v 7M -maxrate 7700k -bufsize 14000k"
+        );
+        assert_eq!(RateControl::Cq(19).get_bitrate_args(10), "-b:v 0");
+        assert!(RateControl::Cqp(23).get_bitrate_args(10).is_empty());
+        assert!(RateControl::Cqp(23).is_quality_targeted());
+        assert!(!RateControl::Cbr.is_quality_targeted());
+    }
+}
@@ -1,23 +1,131 @@
-use std::collections::HashMap;
-
-pub const SUPPORTED_RESOLUTIONS: [&'static str; 4] =
-    ["1280x720", "1920x1080", "2560x1440", "3840x2160"];
-
-pub fn map_res_to_bitrate(map: &mut HashMap<String, u32>, bitrates: [u32; 4]) {
-    map.insert(
-        SUPPORTED_RESOLUTIONS.get(0).unwrap().to_string(),
-        *bitrates.get(0).unwrap(),
-    );
-    map.insert(
-        SUPPORTED_RESOLUTIONS.get(1).unwrap().to_string(),
-        *bitrates.get(1).unwrap(),
-    );
-    map.insert(
-        SUPPORTED_RESOLUTIONS.get(2).unwrap().to_string(),
-        *bitrates.get(2).unwrap(),
-    );
-    map.insert(
-        SUPPORTED_RESOLUTIONS.get(3).unwrap().to_string(),
-        *bitrates.get(3).unwrap(),
-    );
+// the resolutions our calibration bitrates were measured at (all at 60fps)
+pub const CALIBRATION_RESOLUTIONS: [(u32, u32); 4] =
+    [(1280, 720), (1920, 1080), (2560, 1440), (3840, 2160)];
+
+// the frame rate the calibration bitrates were measured at
+const CALIBRATION_FPS: f64 = 60.0;
+
+// av1 reaches the same quality as hevc at roughly 30% less bitrate
+const AV1_TO_HEVC_BITRATE_RATIO: f64 = 0.7;
+
+// av1 encoders are calibrated off their vendor's hevc bitrates rather than measured on their own
+pub fn derive_av1_calibration(hevc: [u32; 4]) -> [u32; 4] {
+    // rounded up the same as target bitrates, after the floating point noise is taken off
+    return hevc.map(|bitrate| {
+        let scaled = bitrate as f64 * AV1_TO_HEVC_BITRATE_RATIO;
+        return ((scaled * 1000.0).round() / 1000.0).ceil() as u32;
+    });
+}
+
+// derives a target bitrate (in Mb/s) for any resolution & fps from the encoder's calibration bitrates
+// the calibration values tell us how efficient the codec is (bits per pixel) at each resolution,
+// which we interpolate on a log scale of pixel count and then multiply by the pixel rate of the source
+pub fn estimate_bitrate(calibration: [u32; 4], width: u32, height: u32, fps: f64) -> u32 {
+    let pixels = (width as f64) * (height as f64);
+    if pixels == 0.0 || fps <= 0.0 {
+        return 0;
+    }
+
+    let bits_per_pixel = interpolate_bits_per_pixel(calibration, pixels);
+    let bitrate = bits_per_pixel * pixels * fps / 1_000_000.0;
+
+    // always round up, it's better to slightly overshoot than starve the encoder
+    // (rounding first so floating point noise on the calibration points doesn't bump them up)
+    let rounded = (bitrate * 1000.0).round() / 1000.0;
+    return (rounded.ceil() as u32).max(1);
+}
+
+fn interpolate_bits_per_pixel(calibration: [u32; 4], pixels: f64) -> f64 {
+    // (log of pixel count, log of bits per pixel) for each calibration point
+    let points = CALIBRATION_RESOLUTIONS
+        .iter()
+        .zip(calibration.iter())
+        .map(|((w, h), bitrate)| {
+            let calibration_pixels = (*w as f64) * (*h as f64);
+            let bpp = (*bitrate as f64 * 1_000_000.0) / (calibration_pixels * CALIBRATION_FPS);
+            (calibration_pixels.ln(), bpp.ln())
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    let x = pixels.ln();
+
+    // pick the segment the resolution falls into; outside the range we extend the nearest segment
+    let mut segment = 0;
+    while segment < points.len() - 2 && x > points[segment + 1].0 {
+        segment += 1;
+    }
+
+    let (x0, y0) = points[segment];
+    let (x1, y1) = points[segment + 1];
+    let slope = (y1 - y0) / (x1 - x0);
+
+    return (y0 + slope * (x - x0)).exp();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolutions::{derive_av1_calibration, estimate_bitrate, CALIBRATION_RESOLUTIONS};
+
+    static CALIBRATION: [u32; 4] = [10, 20, 25, 55];
+
+    #[test]
+    fn calibration_points_match_test() {
+        for (i, (width, height)) in CALIBRATION_RESOLUTIONS.iter().enumerate() {
+            assert_eq!(
+                estimate_bitrate(CALIBRATION, *width, *height, 60.0),
+                CALIBRATION[i]
+            );
+        }
+    }
+
+    #[test]
+    fn double_fps_doubles_bitrate_test() {
+        for (i, (width, height)) in CALIBRATION_RESOLUTIONS.iter().enumerate() {
+            assert_eq!(
+                estimate_bitrate(CALIBRATION, *width, *height, 120.0),
+                CALIBRATION[i] * 2
+            );
+        }
+    }
+
+    #[test]
+    fn in_between_resolution_test() {
+        // 1600x900 sits between 720p and 1080p
+        let bitrate = estimate_bitrate(CALIBRATION, 1600, 900, 60.0);
+        assert!(bitrate > CALIBRATION[0] && bitrate < CALIBRATION[1]);
+    }
+
+    #[test]
+    fn ultrawide_resolution_test() {
+        // 3440x1440 sits between 1440p and 4k
+        let bitrate = estimate_bitrate(CALIBRATION, 3440, 1440, 60.0);
+        assert!(bitrate > CALIBRATION[2] && bitrate < CALIBRATION[3]);
+    }
+
+    #[test]
+    fn uncommon_fps_test() {
+        assert!(estimate_bitrate(CALIBRATION, 1920, 1080, 144.0) > CALIBRATION[1] * 2);
+        assert_eq!(
+            estimate_bitrate(CALIBRATION, 1920, 1080, 30.0),
+            CALIBRATION[1] / 2
+        );
+    }
+
+    #[test]
+    fn outside_calibration_range_test() {
+        assert!(estimate_bitrate(CALIBRATION, 640, 360, 60.0) < CALIBRATION[0]);
+        assert!(estimate_bitrate(CALIBRATION, 7680, 4320, 60.0) > CALIBRATION[3]);
+    }
+
+    #[test]
+    fn empty_metadata_test() {
+        assert_eq!(estimate_bitrate(CALIBRATION, 0, 0, 60.0), 0);
+    }
+
+    #[test]
+    fn av1_calibration_test() {
+        // 20 * 0.7 is a hair above 14 as a float, which mustn't round it up to 15
+        assert_eq!(derive_av1_calibration(CALIBRATION), [7, 14, 18, 39]);
+        assert_eq!(derive_av1_calibration([20, 35, 50, 85]), [14, 25, 35, 60]);
+    }
 }
@@ -1,42 +1,71 @@
 use std::time::{Duration, SystemTime};
 
 use compound_duration::format_dhms;
+use crossbeam_channel::Receiver;
+use ctrlc::Error;
 
+use environment::fingerprint::HostFingerprint;
+use environment::load::LoadSampler;
+use gpus::telemetry::TelemetrySampler;
 use permutation::permutation::Permutation;
+use permutation::platform::PlatformProfile;
 
-use crate::engine::{log_benchmark_header, run_encode};
+use crate::engine::{
+    check_source_storage, log_benchmark_header, log_failed_permutations, probe_sources, run_trials,
+};
+use crate::error::EngineError;
+use crate::ffmpeg_logs::{FfmpegLogs, LogRetention};
+use crate::platforms::Platforms;
 use crate::result::{log_results_to_file, PermutationResult};
+use crate::runner::{FfmpegRunner, ProcessRunner};
+use crate::staging::SourceStager;
+use crate::telemetry::Monitoring;
 use crate::threads::setup_ctrl_channel;
+use crate::trials::Trials;
 
 pub struct BenchmarkEngine {
     permutations: Vec<Permutation>,
     results: Vec<PermutationResult>,
     log_files_directory: String,
+    ffmpeg_log_retention: LogRetention,
+    host: Option<HostFingerprint>,
+    monitoring: Monitoring,
+    trials: Trials,
+    platforms: Platforms,
+    runner: Box<dyn ProcessRunner>,
 }
 
 impl BenchmarkEngine {
     pub fn new(log_files: String) -> Self {
+        return Self::with_runner(log_files, Box::new(FfmpegRunner));
+    }
+
+    pub fn with_runner(log_files: String, runner: Box<dyn ProcessRunner>) -> Self {
         return Self {
             permutations: vec![],
             results: vec![],
             log_files_directory: log_files,
+            ffmpeg_log_retention: LogRetention::default(),
+            host: None,
+            monitoring: Monitoring::default(),
+            trials: Trials::default(),
+            platforms: Platforms::default(),
+            runner,
         };
     }
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), EngineError> {
         let runtime = SystemTime::now();
         let ctrl_channel = setup_ctrl_channel();
+        probe_sources(&mut self.permutations, self.runner.as_ref())?;
+        self.platforms.restrict(&mut self.permutations)?;
+        check_source_storage(&mut self.permutations)?;
 
-        let mut calc_time: Option<Duration> = None;
-        for i in 0..self.permutations.clone().len() {
-            let permutation_start_time = SystemTime::now();
-            let permutation = self.permutations[i].clone();
-            // benchmark will not log ETA since every encode will be different
-            log_benchmark_header(i, &self.permutations, calc_time);
-            self.results
-                .push(run_encode(permutation.clone(), &ctrl_channel));
-            calc_time = Option::from(permutation_start_time.elapsed().unwrap());
-        }
+        let mut stager = SourceStager::new();
+        let outcome = self.run_permutations(&ctrl_channel, &mut stager);
+        // staged sources are released whether or not the run made it to the end
+        stager.cleanup();
+        outcome?;
 
         // produce output files and other logging here
         let runtime_str = format_dhms(runtime.elapsed().unwrap().as_secs());
@@ -47,11 +76,86 @@ impl BenchmarkEngine {
             self.permutations[0].bitrate,
             true,
             &self.log_files_directory,
-        );
+            &self.host,
+        )
+        .map_err(EngineError::Results)?;
         println!("Benchmark runtime: {}", runtime_str);
+        log_failed_permutations(&self.results);
+
+        return Ok(());
+    }
+
+    fn run_permutations(
+        &mut self,
+        ctrl_channel: &Result<Receiver<()>, Error>,
+        stager: &mut SourceStager,
+    ) -> Result<(), EngineError> {
+        let logs = FfmpegLogs::new(&self.log_files_directory, self.ffmpeg_log_retention);
+        let mut calc_time: Option<Duration> = None;
+        for i in 0..self.permutations.clone().len() {
+            let permutation_start_time = SystemTime::now();
+            let mut permutation = self.permutations[i].clone();
+            stager.prepare(&mut permutation)?;
+            // benchmark will not log ETA since every encode will be different
+            log_benchmark_header(i, &self.permutations, calc_time);
+            let mut result = run_trials(
+                permutation.clone(),
+                i,
+                ctrl_channel,
+                self.runner.as_ref(),
+                &logs,
+                &self.monitoring,
+                &self.trials,
+            )?;
+            self.platforms.check(&mut result, &permutation);
+            self.results.push(result);
+            calc_time = Option::from(permutation_start_time.elapsed().unwrap());
+        }
+
+        return Ok(());
+    }
+
+    // which of the ffmpeg logs written next to the results file are kept
+    pub fn set_ffmpeg_log_retention(&mut self, retention: LogRetention) {
+        self.ffmpeg_log_retention = retention;
+    }
+
+    // written at the top of the results file so they can be traced back to the machine they came from
+    pub fn set_host_fingerprint(&mut self, host: HostFingerprint) {
+        self.host = Some(host);
+    }
+
+    // samples the gpu while every encode runs; without one no telemetry is recorded
+    pub fn set_telemetry_sampler(&mut self, sampler: Box<dyn TelemetrySampler>) {
+        self.monitoring.set_gpu_sampler(sampler);
+    }
+
+    // samples the cpu and system load while every encode runs, and checks for background load before each
+    pub fn set_load_sampler(&mut self, sampler: Box<dyn LoadSampler>) {
+        self.monitoring.set_load_sampler(sampler);
+    }
+
+    // % of cpu busy before an encode above which its results are flagged, and optionally run again
+    pub fn set_background_load_check(&mut self, max_background_load: f64, rerun: bool) {
+        self.monitoring.max_background_load = max_background_load;
+        self.monitoring.rerun_contaminated = rerun;
+    }
+
+    // runs every encode this many times and reports the mean, after an uncounted warm-up run if asked for
+    pub fn set_trials(&mut self, count: u32, warmup: bool) {
+        self.trials = Trials { count, warmup };
+    }
+
+    // checks every result against the streaming platforms, and only runs what at least one of them takes if restricted
+    pub fn set_platforms(&mut self, profiles: Vec<PlatformProfile>, restrict: bool) {
+        self.platforms = Platforms { profiles, restrict };
     }
 
     pub fn add(&mut self, permutation: Permutation) {
         self.permutations.push(permutation);
     }
+
+    pub fn get_results(&self) -> &Vec<PermutationResult> {
+        return &self.results;
+    }
 }
@@ -0,0 +1,304 @@
+use codecs::rate_control::RateControl;
+use ffmpeg::packet::Packet;
+
+// an average this % over the target bitrate no longer counts as hitting it
+pub const MAX_OVERSHOOT: f64 = 10.0;
+
+// a leaky bucket standing in for the decoder's buffer (vbv/hrd); the encoder fills it with every
+// packet, and the connection drains it at the given rate, so it must never hold more than its size
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VbvCheck {
+    // in Mb
+    pub buffer: f64,
+    // in Mb/s
+    pub rate: f64,
+    // the fullest the buffer got, as a % of its size
+    pub max_fullness: f64,
+    pub overflowing_frames: usize,
+}
+
+impl VbvCheck {
+    pub fn run(packets: &Vec<Packet>, rate: f64, buffer: f64) -> Self {
+        let mut level: f64 = 0.0;
+        let mut max_level: f64 = 0.0;
+        let mut overflowing_frames = 0;
+        let mut previous_time = packets.first().map(|packet| packet.time).unwrap_or(0.0);
+
+        for packet in packets {
+            let drained = rate * (packet.time - previous_time).max(0.0);
+            level = (level - drained).max(0.0) + to_megabits(packet.bytes);
+            max_level = max_level.max(level);
+            // the decoder can't hold what overflowed, so the frame arrives late and the buffer starts over full
+            if level > buffer {
+                overflowing_frames += 1;
+                level = buffer;
+            }
+
+            previous_time = packet.time;
+        }
+
+        return Self {
+            buffer,
+            rate,
+            max_fullness: max_level / buffer * 100.0,
+            overflowing_frames,
+        };
+    }
+
+    pub fn has_overflowed(&self) -> bool {
+        return self.overflowing_frames > 0;
+    }
+}
+
+// what an encode actually produced, next to the bitrate it was asked for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BitrateConformance {
+    // in Mb/s, None for quality-targeted rate controls which don't aim for one
+    pub target: Option<f64>,
+    // all in Mb/s
+    pub average: f64,
+    pub peak_1s: f64,
+    pub peak_half_s: f64,
+    pub vbv: Option<VbvCheck>,
+}
+
+impl BitrateConformance {
+    // cbr_buffer is how many seconds of the target bitrate the decoder buffers;
+    // vbr brings its own buffer, which was handed to the encoder as well
+    pub fn from_packets(
+        packets: &Vec<Packet>,
+        fps: f64,
+        bitrate: u32,
+        rate_control: RateControl,
+        cbr_buffer: f64,
+    ) -> Option<Self> {
+        if packets.is_empty() || fps == 0.0 {
+            return None;
+        }
+
+        let frame_duration = 1.0 / fps;
+        let duration = packets.len() as f64 * frame_duration;
+        let bitrate = bitrate as f64;
+        let (target, vbv) = match rate_control {
+            RateControl::Cbr => (
+                Some(bitrate),
+                Some(VbvCheck::run(packets, bitrate, bitrate * cbr_buffer)),
+            ),
+            RateControl::Vbr { max, buffer } => (
+                Some(bitrate),
+                Some(VbvCheck::run(packets, bitrate * max, bitrate * buffer)),
+            ),
+            RateControl::Cqp(_) | RateControl::Cq(_) | RateControl::Icq(_) => (None, None),
+        };
+
+        return Some(Self {
+            target,
+            average: to_megabits(packets.iter().map(|packet| packet.bytes).sum()) / duration,
+            peak_1s: get_peak_bitrate(packets, 1.0, frame_duration),
+            peak_half_s: get_peak_bitrate(packets, 0.5, frame_duration),
+            vbv,
+        });
+    }
+
+    // how far the average went over the target, as a %; negative when it came in under
+    pub fn get_overshoot(&self) -> Option<f64> {
+        return self
+            .target
+            .map(|target| (self.average - target) / target * 100.0);
+    }
+
+    // hit its bitrate and never overflowed the decoder's buffer, i.e. safe to stream
+    pub fn is_conformant(&self) -> bool {
+        let overshot = self
+            .get_overshoot()
+            .map(|overshoot| overshoot > MAX_OVERSHOOT)
+            .unwrap_or(false);
+        let overflowed = self.vbv.map(|vbv| vbv.has_overflowed()).unwrap_or(false);
+
+        return !overshot && !overflowed;
+    }
+
+    // i.e. '6.31Mb/s avg (+5.2%), peaks 7.02/7.84Mb/s (1s/0.5s), VBV 6Mb 81% full'
+    pub fn to_string(&self) -> String {
+        let mut output = format!("{:.2}Mb/s avg", self.average);
+        if let Some(overshoot) = self.get_overshoot() {
+            output.push_str(format!(" ({:+.1}%)", overshoot).as_str());
+        }
+
+        output.push_str(
+            format!(
+                ", peaks {:.2}/{:.2}Mb/s (1s/0.5s)",
+                self.peak_1s, self.peak_half_s
+            )
+            .as_str(),
+        );
+
+        if let Some(vbv) = self.vbv {
+            output.push_str(
+                format!(", VBV {:.1}Mb {:.0}% full", vbv.buffer, vbv.max_fullness).as_str(),
+            );
+            if vbv.has_overflowed() {
+                output.push_str(
+                    format!(", overflowed on {} frames", vbv.overflowing_frames).as_str(),
+                );
+            }
+        }
+
+        return output;
+    }
+}
+
+// the most bits sent over any stretch of the given length, in Mb/s
+// packets are placed by their timestamps, a frame's duration apart when the timestamps are exact
+fn get_peak_bitrate(packets: &Vec<Packet>, window: f64, frame_duration: f64) -> f64 {
+    let duration = packets.len() as f64 * frame_duration;
+    // a video shorter than the window only has the one stretch to measure
+    if duration <= window {
+        return to_megabits(packets.iter().map(|packet| packet.bytes).sum()) / duration;
+    }
+
+    // timestamps get rounded by the container, so allow them to be half a frame off
+    let tolerance = frame_duration / 2.0;
+    let last_start = packets[0].time + duration - window + tolerance;
+    let mut peak: u64 = 0;
+    let mut bytes: u64 = 0;
+    let mut end = 0;
+    for start in 0..packets.len() {
+        if packets[start].time > last_start {
+            break;
+        }
+
+        while end < packets.len() && packets[end].time < packets[start].time + window - tolerance {
+            bytes += packets[end].bytes;
+            end += 1;
+        }
+
+        peak = peak.max(bytes);
+        bytes -= packets[start].bytes;
+    }
+
+    return to_megabits(peak) / window;
+}
+
+fn to_megabits(bytes: u64) -> f64 {
+    return bytes as f64 * 8.0 / 1_000_000.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use codecs::rate_control::RateControl;
+    use ffmpeg::packet::Packet;
+
+    use crate::conformance::{get_peak_bitrate, BitrateConformance, VbvCheck};
+
+    // frames at 10fps, each of the given size
+    fn get_packets(sizes: &[u64]) -> Vec<Packet> {
+        return sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| Packet::new(i as f64 / 10.0, *size, i % 10 == 0))
+            .collect();
+    }
+
+    #[test]
+    fn steady_stream_test() {
+        // 100KB a frame at 10fps is 8Mb/s
+        let packets = get_packets(&[100_000; 30]);
+        let conformance =
+            BitrateConformance::from_packets(&packets, 10.0, 8, RateControl::Cbr, 1.0).unwrap();
+
+        assert!((conformance.average - 8.0).abs() < 0.001);
+        assert!((conformance.peak_1s - 8.0).abs() < 0.001);
+        assert!((conformance.peak_half_s - 8.0).abs() < 0.001);
+        assert!(conformance.get_overshoot().unwrap().abs() < 0.001);
+        assert!(conformance.is_conformant());
+    }
+
+    #[test]
+    fn peak_test() {
+        // a burst of 3 large frames in the middle of the second second
+        let mut sizes = vec![50_000; 30];
+        sizes[14] = 500_000;
+        sizes[15] = 500_000;
+        sizes[16] = 500_000;
+        let packets = get_packets(&sizes);
+
+        // 7 small and 3 large frames in the worst second: (7 * 0.4 + 3 * 4) / 1s
+        assert!((get_peak_bitrate(&packets, 1.0, 0.1) - 14.8).abs() < 0.001);
+        // 2 small and 3 large frames in the worst half second: (2 * 0.4 + 3 * 4) / 0.5s
+        assert!((get_peak_bitrate(&packets, 0.5, 0.1) - 25.6).abs() < 0.001);
+    }
+
+    #[test]
+    fn short_stream_peak_test() {
+        let packets = get_packets(&[100_000; 3]);
+        assert!((get_peak_bitrate(&packets, 1.0, 0.1) - 8.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn overshoot_test() {
+        // 14Mb/s when 10 was asked for
+        let packets = get_packets(&[175_000; 30]);
+        let conformance =
+            BitrateConformance::from_packets(&packets, 10.0, 10, RateControl::Cbr, 1.0).unwrap();
+
+        assert!((conformance.get_overshoot().unwrap() - 40.0).abs() < 0.001);
+        assert!(conformance.vbv.unwrap().has_overflowed());
+        assert!(!conformance.is_conformant());
+    }
+
+    #[test]
+    fn vbv_test() {
+        // a 4Mb keyframe fits a 4Mb buffer once, but not twice in a row
+        let mut sizes = vec![25_000; 20];
+        sizes[0] = 500_000;
+        let fits = VbvCheck::run(&get_packets(&sizes), 4.0, 4.0);
+        assert!(!fits.has_overflowed());
+        assert!((fits.max_fullness - 100.0).abs() < 0.001);
+
+        sizes[1] = 500_000;
+        let overflows = VbvCheck::run(&get_packets(&sizes), 4.0, 4.0);
+        assert_eq!(overflows.overflowing_frames, 1);
+    }
+
+    #[test]
+    fn vbr_test() {
+        // vbr drains at its max rate, into the buffer it was given
+        let packets = get_packets(&[100_000; 30]);
+        let rate_control = RateControl::Vbr {
+            max: 1.5,
+            buffer: 2.0,
+        };
+        let conformance =
+            BitrateConformance::from_packets(&packets, 10.0, 8, rate_control, 1.0).unwrap();
+
+        let vbv = conformance.vbv.unwrap();
+        assert_eq!(vbv.rate, 12.0);
+        assert_eq!(vbv.buffer, 16.0);
+        assert!(!vbv.has_overflowed());
+    }
+
+    #[test]
+    fn quality_targeted_test() {
+        let packets = get_packets(&[175_000; 30]);
+        let conformance =
+            BitrateConformance::from_packets(&packets, 10.0, 0, RateControl::Cq(23), 1.0).unwrap();
+
+        // nothing to overshoot, or buffer to overflow
+        assert_eq!(conformance.get_overshoot(), None);
+        assert_eq!(conformance.vbv, None);
+        assert!(conformance.is_conformant());
+        assert_eq!(
+            conformance.to_string(),
+            "14.00Mb/s avg, peaks 14.00/14.00Mb/s (1s/0.5s)"
+        );
+    }
+
+    #[test]
+    fn empty_test() {
+        assert_eq!(
+            BitrateConformance::from_packets(&vec![], 60.0, 6, RateControl::Cbr, 1.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// how a decoder's frames compared to a software decode of the same file; a fast decoder is no use if it's wrong
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DecodeCheck {
+    pub frames: usize,
+    pub mismatching_frames: usize,
+    pub first_mismatch: Option<usize>,
+}
+
+impl DecodeCheck {
+    // takes the md5 of each frame from both decodes; a frame only one of them produced counts as a mismatch
+    pub fn compare(reference: &Vec<String>, decoded: &Vec<String>) -> Self {
+        let frames = reference.len().max(decoded.len());
+        let mismatches: Vec<usize> = (0..frames)
+            .filter(|frame| reference.get(*frame) != decoded.get(*frame))
+            .collect();
+
+        return Self {
+            frames,
+            mismatching_frames: mismatches.len(),
+            first_mismatch: mismatches.first().copied(),
+        };
+    }
+
+    pub fn is_correct(&self) -> bool {
+        return self.mismatching_frames == 0;
+    }
+
+    // i.e. 'all 240 frames match a software decode', or '57 of 240 frames differ from a software decode, from frame 12'
+    pub fn to_string(&self) -> String {
+        return match self.first_mismatch {
+            None => format!("all {} frames match a software decode", self.frames),
+            Some(frame) => format!(
+                "{} of {} frames differ from a software decode, from frame {}",
+                self.mismatching_frames, self.frames, frame
+            ),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode_check::DecodeCheck;
+
+    fn get_hashes(hashes: &[&str]) -> Vec<String> {
+        return hashes.iter().map(|hash| hash.to_string()).collect();
+    }
+
+    #[test]
+    fn matching_test() {
+        let hashes = get_hashes(&["a", "b", "c"]);
+        let check = DecodeCheck::compare(&hashes, &hashes);
+
+        assert!(check.is_correct());
+        assert_eq!(check.to_string(), "all 3 frames match a software decode");
+    }
+
+    #[test]
+    fn mismatching_test() {
+        let check = DecodeCheck::compare(
+            &get_hashes(&["a", "b", "c", "d"]),
+            &get_hashes(&["a", "x", "c", "y"]),
+        );
+
+        assert!(!check.is_correct());
+        assert_eq!(check.mismatching_frames, 2);
+        assert_eq!(
+            check.to_string(),
+            "2 of 4 frames differ from a software decode, from frame 1"
+        );
+    }
+
+    #[test]
+    fn dropped_frames_test() {
+        // the decoder gave up two frames early
+        let check =
+            DecodeCheck::compare(&get_hashes(&["a", "b", "c", "d"]), &get_hashes(&["a", "b"]));
+
+        assert_eq!(check.frames, 4);
+        assert_eq!(check.mismatching_frames, 2);
+        assert_eq!(check.first_mismatch, Some(2));
+    }
+}
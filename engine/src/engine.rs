@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::ffi::c_float;
 use std::fs;
-use std::process::{Child, Command, Stdio};
+use std::path::Path;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
@@ -8,38 +9,65 @@ use compound_duration::format_dhms;
 use crossbeam_channel::Receiver;
 use ctrlc::Error;
 
-use cli::cli_util::error_with_ack;
+use codecs::get_vendor_for_codec;
+use environment::disk::{check_read_throughput, ReadThroughput};
+use environment::load::SystemLoad;
 use ffmpeg::args::FfmpegArgs;
+use ffmpeg::decoder::Decoder;
+use ffmpeg::hwaccel::{get_hwaccel_candidates, resolve_decoder};
 use ffmpeg::metadata::MetaData;
+use ffmpeg::report_files::extract_video_size;
+use ffmpeg::source::get_encoded_output_path;
+use gpus::telemetry::GpuTelemetry;
 use permutation::permutation::Permutation;
 
+use crate::conformance::{BitrateConformance, MAX_OVERSHOOT};
+use crate::decode_check::DecodeCheck;
+use crate::error::EngineError;
+use crate::ffmpeg_logs::FfmpegLogs;
 use crate::progressbar;
 use crate::progressbar::TrialResult;
 use crate::result::PermutationResult;
-use crate::threads::was_ctrl_c_received;
+use crate::runner::ProcessRunner;
+use crate::settings_check::{RequestedSettings, SettingsCheck};
+use crate::telemetry::Monitoring;
+use crate::trials::{TrialStats, Trials, MAX_COEFFICIENT_OF_VARIATION};
 
 pub fn run_encode(
     mut p: Permutation,
+    index: usize,
     ctrl_channel: &Result<Receiver<()>, Error>,
-) -> PermutationResult {
+    runner: &dyn ProcessRunner,
+    logs: &FfmpegLogs,
+    monitoring: &Monitoring,
+) -> Result<PermutationResult, EngineError> {
+    let metadata = p.get_metadata()?;
     let mut result = PermutationResult::new(
-        &p.get_metadata(),
+        &metadata,
         p.bitrate,
         &p.encoder_settings,
         &p.encoder,
         p.decode_run,
     );
 
-    let metadata = p.get_metadata();
-
     let mut ffmpeg_args = FfmpegArgs::build_ffmpeg_args(
-        p.video_file,
-        p.encoder,
+        p.video_file.clone(),
+        p.encoder.clone(),
         &p.encoder_settings,
         p.bitrate,
         p.decode_run,
         p.ten_bit,
     );
+    ffmpeg_args.rate_control = p.rate_control;
+    ffmpeg_args.decoder = p.decoder.clone();
+    if p.decode_run && ffmpeg_args.decoder.is_none() {
+        ffmpeg_args.decoder = Some(resolve_hwaccel(&ffmpeg_args, runner));
+    }
+    ffmpeg_args.gop_frames = p.gop.map(|gop| gop.to_frames(metadata.fps.as_f64()));
+    result.gop = p.gop;
+
+    // whatever else is keeping the cpu busy before the encode starts would skew its numbers
+    let background_load = monitoring.measure_background_load();
 
     let encode_start_time = SystemTime::now();
 
@@ -52,19 +80,101 @@ pub fn run_encode(
         }
     }
 
+    // the output has to be written somewhere to be read back, decoding already writes it
+    let inspect_output = (p.check_bitrate || p.verify_settings) && !p.decode_run;
+    let keep_output = inspect_output && !p.is_decoding;
+    if keep_output {
+        ffmpeg_args.output_args = get_encoded_output_path(&ffmpeg_args.first_input);
+    }
+
+    result.read_throughput = p.read_throughput;
+    result.source_file = p.video_file.clone();
+    result.decoder = ffmpeg_args.decoder.clone();
+    if p.decode_only {
+        // the name is enough to tell them apart by, and stays the same when the file is staged in memory
+        result.decoded_file = Path::new(&p.video_file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+    }
+
+    let stage = if p.decode_run { "decode" } else { "encode" };
+    let log_path = logs.get_log_path(index, &p.encoder_settings, stage);
+
+    // the gpu and the system are sampled at the same period ffmpeg reports its progress at
+    let sample_period = Duration::from_secs_f32(ffmpeg_args.stats_period);
+    let gpu_recording = monitoring.record_gpu(sample_period);
+    let load_recording = monitoring.record_load(sample_period);
+
     // not sure what to do about these results here
-    let mut trial_result = run_overload_benchmark(
+    let trial_result = run_overload_benchmark(
         &metadata,
         &ffmpeg_args,
         p.verbose,
         p.detect_overload,
+        p.rate_control.is_quality_targeted() && !p.decode_run,
         &ctrl_channel,
+        runner,
+        monitoring,
+        log_path.as_deref(),
     );
+    result.gpu_telemetry =
+        gpu_recording.map(|recording| GpuTelemetry::from_samples(&recording.stop()));
+    result.system_load = load_recording.map(|recording| {
+        let mut load = SystemLoad::from_samples(&recording.stop());
+        monitoring.watch_process(None);
+        if let Some(background_load) = background_load {
+            load.check_background(background_load, monitoring.max_background_load);
+        }
+
+        return load;
+    });
+
+    // a cancelled or overloaded encode stopped part way, so what it wrote isn't representative
+    if inspect_output {
+        if let Ok(finished) = &trial_result {
+            if !finished.cancelled && !finished.was_overloaded {
+                if p.check_bitrate {
+                    result.bitrate_conformance =
+                        check_bitrate_conformance(&ffmpeg_args, &metadata, p.vbv_buffer, runner);
+                }
+                if p.verify_settings {
+                    result.settings_check =
+                        check_settings(&ffmpeg_args, &p.encoder_settings, p.no_b_frame, runner);
+                }
+            }
+        }
+    }
+    if keep_output {
+        remove_encoded_output(&ffmpeg_args.output_args);
+    }
 
-    // this should be a hard-stop for the program here
-    // perhaps abstract this method out
-    if trial_result.ffmpeg_error {
-        error_with_ack(true);
+    // the frames are hashed in a decode of their own, so only a cancelled run goes without
+    if p.decode_run && p.verify_decode {
+        if let Ok(finished) = &trial_result {
+            if !finished.cancelled {
+                result.decode_check = check_decode(&ffmpeg_args, runner);
+            }
+        }
+    }
+
+    let mut trial_result = match trial_result {
+        Ok(trial_result) => trial_result,
+        // one rejected combination of settings shouldn't cost the rest of the run
+        Err(EngineError::EncodeFailed(error_output)) => {
+            println!("Ffmpeg was not able to run this permutation, moving on to the next one...");
+            println!("{}\n", error_output);
+            result.encode_time = encode_start_time.elapsed().unwrap().as_secs();
+            result.error = Some(error_output);
+            logs.finish(&log_path, true);
+            return Ok(result);
+        }
+        Err(e) => return Err(e),
+    };
+
+    logs.finish(&log_path, false);
+
+    if trial_result.cancelled {
+        return Err(EngineError::Cancelled);
     }
 
     result.was_overloaded = trial_result.was_overloaded;
@@ -72,22 +182,243 @@ pub fn run_encode(
 
     // calculate the fps statistics and store this in the result
     calculate_fps_statistics(&mut result, &mut trial_result);
+    result.achieved_bitrate = trial_result
+        .video_bytes
+        .and_then(|bytes| calculate_bitrate(bytes, &metadata));
+    if p.decode_only {
+        // includes the container's overhead and any audio, but close enough to tell files apart by
+        result.achieved_bitrate = fs::metadata(&ffmpeg_args.first_input)
+            .ok()
+            .and_then(|file| calculate_bitrate(file.len(), &metadata));
+    }
 
     // log the calculated fps statistics; two spaces match the progress bar
     println!("  Average FPS:\t{:.0}", result.fps_stats.avg);
     println!("  1%'ile:\t{}", result.fps_stats.one_perc_low);
-    println!("  90%'ile:\t{}\n", result.fps_stats.ninety_perc);
+    println!("  90%'ile:\t{}", result.fps_stats.ninety_perc);
+    if let Some(bitrate) = result.achieved_bitrate {
+        println!("  Bitrate:\t{:.2}Mb/s ({})", bitrate, p.rate_control);
+    }
+    if let Some(check) = &result.settings_check {
+        println!("  Output:\t{}", check.to_string());
+        for mismatch in &check.mismatches {
+            println!(
+                "  Warning: the encoder didn't apply its settings, {}",
+                mismatch
+            );
+        }
+    }
+    if let Some(check) = &result.decode_check {
+        println!("  Frames:\t{}", check.to_string());
+        if !check.is_correct() {
+            println!(
+                "  Warning: {} decoded frames differently than software, its fps won't be ranked",
+                ffmpeg_args.get_decoder()
+            );
+        }
+    }
+    if let Some(conformance) = &result.bitrate_conformance {
+        println!("  Conformance:\t{}", conformance.to_string());
+        if !conformance.is_conformant() {
+            println!("  Warning: the output missed its bitrate by more than {}% or overflowed the decoder's buffer, it isn't safe to stream", MAX_OVERSHOOT);
+        }
+    }
+    if let Some(telemetry) = &result.gpu_telemetry {
+        println!("  GPU (min/avg/max):\t{}", telemetry.to_string());
+    }
+    if let Some(load) = &result.system_load {
+        println!("  System (min/avg/max):\t{}", load.to_string());
+        if load.contaminated {
+            println!(
+                "  Warning: more than {}% of the cpu was busy with something else before or during this encode, its results may be skewed",
+                monitoring.max_background_load
+            );
+        }
+    }
+    println!();
 
     // delete the file we created to save on storage space
-    if p.decode_run {
+    if p.decodes_previous_encode() {
         // gives time for ffmpeg to release it's hold on the file
-        println!("Giving ffmpeg a change to let go of the decode file, hang tight...");
-        sleep(Duration::from_secs(5));
-        fs::remove_file(ffmpeg_args.first_input)
-            .expect("Not able to delete the file produced by the previous encode");
+        let release_delay = runner.release_delay();
+        if !release_delay.is_zero() {
+            println!("Giving ffmpeg a change to let go of the decode file, hang tight...");
+            sleep(release_delay);
+        }
+        if let Err(e) = fs::remove_file(&ffmpeg_args.first_input) {
+            println!(
+                "Warning: not able to delete the file produced by the previous encode [{}]: {}",
+                ffmpeg_args.first_input, e
+            );
+        }
+    }
+
+    return Ok(result);
+}
+
+// same as run_encode, but runs the permutation a second time when background load skewed the first one
+pub fn run_monitored_encode(
+    p: Permutation,
+    index: usize,
+    ctrl_channel: &Result<Receiver<()>, Error>,
+    runner: &dyn ProcessRunner,
+    logs: &FfmpegLogs,
+    monitoring: &Monitoring,
+) -> Result<PermutationResult, EngineError> {
+    let result = run_encode(p.clone(), index, ctrl_channel, runner, logs, monitoring)?;
+
+    // a decode run consumes the previous encode's output, so there's nothing left to decode a second time
+    if !monitoring.rerun_contaminated || p.decodes_previous_encode() || !result.is_contaminated() {
+        return Ok(result);
+    }
+
+    println!("Running the permutation again, since background load was present during it...");
+    return run_encode(p, index, ctrl_channel, runner, logs, monitoring);
+}
+
+// runs the permutation once per trial, after an optional warm-up that isn't counted, and reports the mean of them
+pub fn run_trials(
+    p: Permutation,
+    index: usize,
+    ctrl_channel: &Result<Receiver<()>, Error>,
+    runner: &dyn ProcessRunner,
+    logs: &FfmpegLogs,
+    monitoring: &Monitoring,
+    trials: &Trials,
+) -> Result<PermutationResult, EngineError> {
+    // a decode run consumes the previous encode's output, so it can only ever run once
+    if p.decodes_previous_encode() || (trials.count <= 1 && !trials.warmup) {
+        return run_monitored_encode(p, index, ctrl_channel, runner, logs, monitoring);
+    }
+
+    if trials.warmup {
+        println!("Warming up, this run won't be counted...");
+        let warmup =
+            run_monitored_encode(p.clone(), index, ctrl_channel, runner, logs, monitoring)?;
+        if warmup.has_failed() {
+            return Ok(warmup);
+        }
+    }
+
+    let count = trials.count.max(1);
+    let mut avg_fps: Vec<f64> = Vec::new();
+    let mut one_perc_lows: Vec<f64> = Vec::new();
+    let mut result = None;
+    for trial in 0..count {
+        if count > 1 {
+            println!("Trial {}/{}:", trial + 1, count);
+        }
+
+        let trial_result =
+            run_monitored_encode(p.clone(), index, ctrl_channel, runner, logs, monitoring)?;
+        // nothing to compare between trials that didn't produce fps numbers
+        if trial_result.has_failed() || trial_result.was_overloaded {
+            return Ok(trial_result);
+        }
+
+        avg_fps.push(trial_result.fps_stats.avg as f64);
+        one_perc_lows.push(trial_result.fps_stats.one_perc_low as f64);
+        result = Some(trial_result);
+    }
+
+    // the last trial stands in for the rest, with its fps swapped for the mean of all of them
+    let mut result = result.unwrap();
+    let trial_stats = TrialStats::from_trials(&avg_fps, &one_perc_lows);
+    result.fps_stats.avg = trial_stats.avg_fps.mean.round() as u16;
+    result.fps_stats.one_perc_low = trial_stats.one_perc_low.mean.round() as u16;
+
+    println!("  Trials:\t{}", trial_stats.to_string());
+    if trial_stats.is_too_variable() {
+        println!(
+            "  Warning: average FPS varied by more than {}% between trials, its results may not be trustworthy",
+            MAX_COEFFICIENT_OF_VARIATION
+        );
+    }
+    println!();
+
+    result.trial_stats = Some(trial_stats);
+    return Ok(result);
+}
+
+// fills in the metadata of every permutation up front, probing each source only once
+pub fn probe_sources(
+    permutations: &mut Vec<Permutation>,
+    runner: &dyn ProcessRunner,
+) -> Result<(), EngineError> {
+    let mut probed: HashMap<String, MetaData> = HashMap::new();
+
+    for permutation in permutations.iter_mut() {
+        if !permutation.metadata.is_empty() {
+            continue;
+        }
+
+        if !probed.contains_key(&permutation.video_file) {
+            probed.insert(
+                permutation.video_file.clone(),
+                runner.probe(&permutation.video_file)?,
+            );
+        }
+
+        permutation.metadata = probed[&permutation.video_file];
+    }
+
+    return Ok(());
+}
+
+// measures the read speed of each source once, warning (or stopping) if storage would be the bottleneck
+pub fn check_source_storage(permutations: &mut Vec<Permutation>) -> Result<(), EngineError> {
+    let mut measured: HashMap<String, Option<ReadThroughput>> = HashMap::new();
+
+    for permutation in permutations.iter_mut() {
+        // decode runs read the encoder's output, and staged sources are read from memory
+        if permutation.decodes_previous_encode() || permutation.stage_in_memory {
+            continue;
+        }
+
+        if !measured.contains_key(&permutation.video_file) {
+            let metadata = permutation.get_metadata()?;
+            println!("Checking read speed of [{}]...", permutation.video_file);
+            let throughput = check_read_throughput(
+                &permutation.video_file,
+                metadata.frames,
+                metadata.fps.as_f64(),
+            );
+
+            match throughput {
+                Some(throughput) => {
+                    println!("  Read speed: {}", throughput.to_string());
+                    if throughput.is_bottleneck() {
+                        println!("  Warning: storage is too slow to read this source in realtime, fps results will be limited by i/o");
+                        if permutation.fail_on_slow_storage {
+                            return Err(EngineError::SlowStorage {
+                                source: permutation.video_file.clone(),
+                                throughput,
+                            });
+                        }
+                    }
+                }
+                None => println!("  Warning: unable to measure the read speed of the source file"),
+            }
+
+            measured.insert(permutation.video_file.clone(), throughput);
+        }
+
+        permutation.read_throughput = measured[&permutation.video_file];
     }
 
-    return result;
+    println!();
+    return Ok(());
+}
+
+// lets the user know about failures without them having to dig through the results file
+pub fn log_failed_permutations(results: &Vec<PermutationResult>) {
+    let failed = results.iter().filter(|r| r.has_failed()).count();
+    if failed > 0 {
+        println!(
+            "{} permutation(s) failed to run, see the end of the results file for ffmpeg's errors",
+            failed
+        );
+    }
 }
 
 pub fn log_permutation_header(
@@ -114,8 +445,9 @@ fn log_header(
     log_eta: bool,
     ignore_factor: c_float,
 ) {
-    let mut permutation = permutations[index].clone();
-    let metadata = permutation.get_metadata();
+    // sources are all probed before any permutation runs
+    let permutation = &permutations[index];
+    let metadata = permutation.metadata;
     if log_eta {
         if calc_time.is_some() {
             println!(
@@ -132,6 +464,17 @@ fn log_header(
         }
     }
     println!("[Permutation:\t{}/{}]", index + 1, permutations.len());
+    if permutation.decode_only {
+        println!("[Decode Benchmark]");
+        println!("[File:\t\t{}]", permutation.video_file);
+        println!("[Resolution:\t{}x{}]", metadata.width, metadata.height);
+        println!("[FPS:\t\t{}]", metadata.fps);
+        if let Some(decoder) = &permutation.decoder {
+            println!("[Decoder:\t{}]", decoder);
+        }
+        return;
+    }
+
     if permutation.is_decoding {
         if permutation.decode_run {
             println!("[Decode Benchmark]");
@@ -143,41 +486,19 @@ fn log_header(
     println!("[Resolution:\t{}x{}]", metadata.width, metadata.height);
     println!("[Encoder:\t{}]", permutation.encoder);
     println!("[FPS:\t\t{}]", metadata.fps);
-    println!("[Bitrate:\t{}Mb/s]", permutation.bitrate);
-    println!("[{}]", permutation.encoder_settings);
-}
-
-pub fn spawn_ffmpeg_child(
-    ffmpeg_args: &FfmpegArgs,
-    verbose: bool,
-    log_error_output: Option<bool>,
-) -> Child {
-    // log the full ffmpeg command to be spawned
-    if verbose {
-        println!("V: ffmpeg args: [{}]", ffmpeg_args.to_string());
-        let mut cloned = ffmpeg_args.clone();
-        cloned.set_no_output_for_error();
+    if permutation.rate_control.is_quality_targeted() {
+        println!("[Rate control:\t{}]", permutation.rate_control);
+    } else {
+        println!("[Bitrate:\t{}Mb/s]", permutation.bitrate);
+    }
+    if let Some(gop) = permutation.gop {
         println!(
-            "V: ffmpeg args no network calls (copy this and run locally, minus the quotes): [{}]",
-            cloned.to_string()
+            "[GOP:\t\t{} ({} frames)]",
+            gop,
+            gop.to_frames(metadata.fps.as_f64())
         );
     }
-
-    let mut effective_ffmpeg_args = ffmpeg_args.clone();
-    if log_error_output.is_some() && log_error_output.unwrap() {
-        effective_ffmpeg_args.set_no_output_for_error();
-    }
-
-    let mut command = Command::new("ffmpeg");
-    let child = command.args(effective_ffmpeg_args.to_vec());
-
-    if log_error_output.is_some() && log_error_output.unwrap() {
-        child.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    } else {
-        child.stdout(Stdio::null()).stderr(Stdio::null());
-    }
-
-    return child.spawn().expect("Failed to start instance of ffmpeg");
+    println!("[{}]", permutation.encoder_settings);
 }
 
 fn run_overload_benchmark(
@@ -185,47 +506,59 @@ fn run_overload_benchmark(
     ffmpeg_args: &FfmpegArgs,
     verbose: bool,
     detect_overload: bool,
+    measure_output: bool,
     ctrl_channel: &Result<Receiver<()>, Error>,
-) -> TrialResult {
-    let mut child = spawn_ffmpeg_child(ffmpeg_args, verbose, None);
-    if verbose {
-        println!("V: Successfully spawned encoding child");
-    }
-
-    let trial_result = progressbar::watch_encode_progress(
+    runner: &dyn ProcessRunner,
+    monitoring: &Monitoring,
+    log_file: Option<&Path>,
+) -> Result<TrialResult, EngineError> {
+    let (mut child, progress) = runner.spawn_encode(ffmpeg_args, verbose, log_file)?;
+    monitoring.watch_process(child.id());
+
+    let mut trial_result = progressbar::watch_encode_progress(
+        progress,
         metadata.frames,
         detect_overload,
         metadata.fps,
         verbose,
         ffmpeg_args.stats_period,
+        &runner.progress_limits(),
         ctrl_channel,
     );
 
-    if trial_result.ffmpeg_error && !was_ctrl_c_received(&ctrl_channel) {
-        let _ = child.kill();
-        eprintln!("Ffmpeg encountered an error when attempting to run, double-check that your environment is setup correctly. If so, open an issue in github!");
-        eprintln!("See below re-creation of the ffmpeg error...");
-        // spawn the ffmpeg command, with output logged so we can troubleshoot better
-        // modifying the command just a little bit so that it fails immediately
-        let mut child = spawn_ffmpeg_child(&ffmpeg_args, verbose, Option::from(true));
-        sleep(Duration::from_secs(20));
-        child
-            .kill()
-            .expect("Not able to kill the error ffmpeg thread");
-    } else if trial_result.was_overloaded && !was_ctrl_c_received(&ctrl_channel) {
-        let _ = child.kill();
+    if trial_result.cancelled {
+        child.kill();
+    } else if trial_result.ffmpeg_error {
+        let error_output = child.error_output();
+        if error_output.is_empty() {
+            return Err(EngineError::EncodeFailed(String::from(
+                "ffmpeg stopped reporting progress without writing any error output",
+            )));
+        }
+
+        return Err(EngineError::EncodeFailed(error_output));
+    } else if trial_result.was_overloaded {
+        child.kill();
         println!(
             "Encoder was overloaded and could not encode the video file in realtime, stopping..."
         );
+    } else if measure_output {
+        // ffmpeg only writes how much video it produced once it's done
+        trial_result.video_bytes = extract_video_size(&child.finish());
     }
 
-    return trial_result;
+    return Ok(trial_result);
 }
 
 fn calculate_fps_statistics(
     permutation_result: &mut PermutationResult,
     trial_result: &mut TrialResult,
 ) {
+    // nothing was recorded (encode never got going), leave the stats empty
+    if trial_result.all_fps.is_empty() {
+        return;
+    }
+
     // must use a much larger data type for calculating the average
     let mut sum: u64 = 0;
     for fps in &trial_result.all_fps {
@@ -239,12 +572,139 @@ fn calculate_fps_statistics(
 
     // find the index & calculate 1%ile
     let mut index = (0.01 as c_float * trial_result.all_fps.len() as c_float).ceil();
-    permutation_result.fps_stats.one_perc_low =
-        *(trial_result.all_fps.get(index as usize).unwrap());
+    // very short encodes may not have enough measurements to go past the last one
+    let last_index = trial_result.all_fps.len() - 1;
+    permutation_result.fps_stats.one_perc_low = *(trial_result
+        .all_fps
+        .get((index as usize).min(last_index))
+        .unwrap());
 
     // find the index & calculate 90%ile
     index = (0.90 as c_float * trial_result.all_fps.len() as c_float).ceil();
-    permutation_result.fps_stats.ninety_perc = *(trial_result.all_fps.get(index as usize).unwrap());
+    permutation_result.fps_stats.ninety_perc = *(trial_result
+        .all_fps
+        .get((index as usize).min(last_index))
+        .unwrap());
+}
+
+// reads back the packets an encode wrote, to see how well it stuck to the bitrate it was given
+fn check_bitrate_conformance(
+    ffmpeg_args: &FfmpegArgs,
+    metadata: &MetaData,
+    vbv_buffer: f64,
+    runner: &dyn ProcessRunner,
+) -> Option<BitrateConformance> {
+    let packets = match runner.probe_packets(&ffmpeg_args.output_args) {
+        Ok(packets) => packets,
+        Err(e) => {
+            println!(
+                "  Warning: unable to read the packets of the encoded output: {}",
+                e
+            );
+            return None;
+        }
+    };
+
+    return BitrateConformance::from_packets(
+        &packets,
+        metadata.fps.as_f64(),
+        ffmpeg_args.bitrate,
+        ffmpeg_args.rate_control,
+        vbv_buffer,
+    );
+}
+
+// hashes every frame the decoder produced and holds them against a software decode of the same file
+fn check_decode(ffmpeg_args: &FfmpegArgs, runner: &dyn ProcessRunner) -> Option<DecodeCheck> {
+    let decoder = ffmpeg_args.get_decoder();
+    // software is what the others are checked against
+    if decoder == Decoder::Software {
+        return None;
+    }
+
+    let hashes = runner
+        .hash_frames(&ffmpeg_args.first_input, &Decoder::Software)
+        .and_then(|reference| {
+            let decoded = runner.hash_frames(&ffmpeg_args.first_input, &decoder)?;
+            return Ok((reference, decoded));
+        });
+
+    return match hashes {
+        Ok((reference, decoded)) => Some(DecodeCheck::compare(&reference, &decoded)),
+        Err(e) => {
+            println!("  Warning: unable to hash the decoded frames: {}", e);
+            None
+        }
+    };
+}
+
+// decodes on the encoder vendor's hwaccel for this os, as long as ffmpeg was built with it
+fn resolve_hwaccel(ffmpeg_args: &FfmpegArgs, runner: &dyn ProcessRunner) -> Decoder {
+    let vendor = get_vendor_for_codec(&ffmpeg_args.encoder);
+    let decoder = resolve_decoder(vendor, ffmpeg_args.target_os, runner.hwaccels().as_ref());
+
+    let candidates = get_hwaccel_candidates(vendor, ffmpeg_args.target_os);
+    if decoder == Decoder::Software && !candidates.is_empty() {
+        println!(
+            "  Warning: ffmpeg has none of [{}] to decode with on {}, decoding in software instead",
+            candidates.join(", "),
+            ffmpeg_args.target_os
+        );
+    }
+
+    return decoder;
+}
+
+// reads back what the encoder wrote, to see whether it applied the settings it was given
+fn check_settings(
+    ffmpeg_args: &FfmpegArgs,
+    encoder_settings: &String,
+    no_b_frame: bool,
+    runner: &dyn ProcessRunner,
+) -> Option<SettingsCheck> {
+    let stream = match runner.probe_encoded_stream(&ffmpeg_args.output_args) {
+        Ok(stream) => stream,
+        Err(e) => {
+            println!(
+                "  Warning: unable to read the stream of the encoded output: {}",
+                e
+            );
+            return None;
+        }
+    };
+
+    let requested = RequestedSettings {
+        encoder_settings,
+        ten_bit: ffmpeg_args.ten_bit,
+        no_b_frame,
+        gop_frames: ffmpeg_args.gop_frames,
+    };
+    return Some(SettingsCheck::run(stream, &requested));
+}
+
+fn remove_encoded_output(output_file: &String) {
+    // the fake runner never writes one
+    if !Path::new(output_file).exists() {
+        return;
+    }
+
+    if let Err(e) = fs::remove_file(output_file) {
+        println!(
+            "Warning: not able to delete the encoded output [{}]: {}",
+            output_file, e
+        );
+    }
+}
+
+// the average bitrate (in Mb/s) of a video of the given size, as long as the source
+fn calculate_bitrate(video_bytes: u64, metadata: &MetaData) -> Option<f64> {
+    let fps = metadata.fps.as_f64();
+    if metadata.frames == 0 || fps == 0.0 {
+        return None;
+    }
+
+    let duration = metadata.frames as f64 / fps;
+    return Some(video_bytes as f64 * 8.0 / duration / 1_000_000.0);
 }
 
 fn calculate_eta(
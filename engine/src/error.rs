@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use environment::disk::ReadThroughput;
+use environment::error::StagingError;
+use ffmpeg::error::{ProbeError, VmafError};
+
+// everything that can stop an engine run, handed back to whoever called run()
+#[derive(Debug)]
+pub enum EngineError {
+    Probe(ProbeError),
+    // ffmpeg couldn't be started
+    Spawn(io::Error),
+    // couldn't open the tcp port ffmpeg reports its progress to
+    ProgressListener(io::Error),
+    // ffmpeg never connected to report its progress
+    ProgressTimeout(u64),
+    // ffmpeg rejected or gave up on a single encode, along with what it had to say about it
+    EncodeFailed(String),
+    Vmaf(VmafError),
+    VmafRetriesExhausted {
+        attempts: i32,
+        last_error: VmafError,
+    },
+    Staging(StagingError),
+    SlowStorage {
+        source: String,
+        throughput: ReadThroughput,
+    },
+    Results(io::Error),
+    // every permutation was dropped for not being taken by any of these platforms
+    NoCompliantPermutations(String),
+    // the user hit ctrl-c
+    Cancelled,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            EngineError::Probe(e) => write!(f, "{}", e),
+            EngineError::Spawn(e) => write!(f, "failed to start instance of ffmpeg: {}", e),
+            EngineError::ProgressListener(e) => write!(f, "unable to listen for ffmpeg's progress output: {}", e),
+            EngineError::ProgressTimeout(seconds) => write!(f, "unable to connect to ffmpeg output for {} seconds, either ffmpeg didn't start correctly or the tcp connection could not be created", seconds),
+            EngineError::EncodeFailed(error_output) => write!(f, "ffmpeg was not able to run the encode:\n{}", error_output),
+            EngineError::Vmaf(e) => write!(f, "{}", e),
+            EngineError::VmafRetriesExhausted { attempts, last_error } => write!(f, "failed to calc encode quality after {} attempts: {}", attempts, last_error),
+            EngineError::Staging(e) => write!(f, "{}", e),
+            EngineError::SlowStorage { source, throughput } => write!(f, "refusing to run on storage that would bottleneck the encoder ([{}] reads at {}); move the source to faster storage", source, throughput.to_string()),
+            EngineError::Results(e) => write!(f, "unable to write the results file: {}", e),
+            EngineError::NoCompliantPermutations(platforms) => write!(f, "none of the permutations can be streamed to {}; see the results file of a run without restricting to them for why", platforms),
+            EngineError::Cancelled => write!(f, "cancelled by ctrl-c"),
+        };
+    }
+}
+
+impl Error for EngineError {}
+
+impl From<ProbeError> for EngineError {
+    fn from(e: ProbeError) -> Self {
+        return EngineError::Probe(e);
+    }
+}
+
+impl From<VmafError> for EngineError {
+    fn from(e: VmafError) -> Self {
+        return EngineError::Vmaf(e);
+    }
+}
+
+impl From<StagingError> for EngineError {
+    fn from(e: StagingError) -> Self {
+        return EngineError::Staging(e);
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::VecDeque;
+use std::ffi::c_float;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+use stoppable_thread::StoppableHandle;
+
+use ffmpeg::args::FfmpegArgs;
+use ffmpeg::decoder::Decoder;
+use ffmpeg::encoded_stream::EncodedStream;
+use ffmpeg::error::{FrameHashError, ProbeError, VmafError};
+use ffmpeg::metadata::MetaData;
+use ffmpeg::packet::Packet;
+
+use crate::error::EngineError;
+use crate::runner::{FfmpegProcess, ProcessRunner, ProgressFeed, ProgressLimits, VmafProcess};
+
+// what an erroring fake encode wrote to stderr
+pub static FAKE_ERROR_OUTPUT: &str =
+    "Unrecognized option 'fake'.\nError splitting the argument list: Option not found";
+
+// the size of the video every successful fake encode produced
+pub const FAKE_VIDEO_KIBIBYTES: u64 = 2048;
+
+// fake encodes play out this much faster than real time, so tests don't sit through them;
+// the fps they report is worked out from the stats period, so it comes out the same
+pub const FAKE_TIME_SCALE: f32 = 0.05;
+
+// what a failing fake vmaf calculation wrote to stderr
+pub static FAKE_VMAF_ERROR_OUTPUT: &str = "Error while filtering: Invalid argument";
+
+// how a fake encode reports its progress
+#[derive(Copy, Clone)]
+pub enum FakeProgress {
+    // reports frames at the given fps until the source is done
+    Steady(u32),
+    // reports frames at the given fps, meant to be below the source's fps
+    Overloaded(u32),
+    // reports frames at the given fps, then stops reporting after the given frame
+    Stalled(u32, u64),
+    // never reports any progress, like an ffmpeg that rejected its arguments
+    Erroring,
+}
+
+struct FakeState {
+    metadata: MetaData,
+    default_progress: FakeProgress,
+    queued_progress: VecDeque<FakeProgress>,
+    // None is a failed vmaf calculation
+    queued_vmaf_scores: VecDeque<Option<c_float>>,
+    // read back in place of what the encode asked for
+    queued_streams: VecDeque<EncodedStream>,
+    // hashed in place of what was decoded
+    queued_frame_hashes: VecDeque<Vec<String>>,
+    // None is an ffmpeg that couldn't be asked for its hwaccels
+    hwaccels: Option<Vec<String>>,
+    encode_args: Vec<FfmpegArgs>,
+    vmaf_count: usize,
+}
+
+// stands in for ffmpeg/ffprobe so the engines can run without either (or a GPU)
+// clones share their state, so tests can keep a handle on what the engine did
+#[derive(Clone)]
+pub struct FakeRunner {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeRunner {
+    pub fn new(metadata: MetaData, default_progress: FakeProgress) -> Self {
+        return Self {
+            state: Arc::new(Mutex::new(FakeState {
+                metadata,
+                default_progress,
+                queued_progress: VecDeque::new(),
+                queued_vmaf_scores: VecDeque::new(),
+                queued_streams: VecDeque::new(),
+                queued_frame_hashes: VecDeque::new(),
+                hwaccels: None,
+                encode_args: vec![],
+                vmaf_count: 0,
+            })),
+        };
+    }
+
+    // the next encode will report progress this way, instead of the default
+    pub fn queue_progress(&self, progress: FakeProgress) {
+        self.state
+            .lock()
+            .unwrap()
+            .queued_progress
+            .push_back(progress);
+    }
+
+    // the next vmaf calculation produces this score; when none are queued, 0 is produced
+    pub fn queue_vmaf_score(&self, score: Option<c_float>) {
+        self.state
+            .lock()
+            .unwrap()
+            .queued_vmaf_scores
+            .push_back(score);
+    }
+
+    // the next encoded output is read back as this stream; when none are queued, it's what the encode asked for
+    pub fn queue_encoded_stream(&self, stream: EncodedStream) {
+        self.state.lock().unwrap().queued_streams.push_back(stream);
+    }
+
+    // the next decode to be hashed produces these frames; when none are queued, every decoder produces the same ones
+    pub fn queue_frame_hashes(&self, hashes: Vec<String>) {
+        self.state
+            .lock()
+            .unwrap()
+            .queued_frame_hashes
+            .push_back(hashes);
+    }
+
+    // the hwaccels ffmpeg claims to have; when never set, it couldn't be asked
+    pub fn set_hwaccels(&self, hwaccels: Vec<String>) {
+        self.state.lock().unwrap().hwaccels = Some(hwaccels);
+    }
+
+    pub fn get_encode_args(&self) -> Vec<FfmpegArgs> {
+        return self.state.lock().unwrap().encode_args.clone();
+    }
+
+    pub fn get_encode_count(&self) -> usize {
+        return self.state.lock().unwrap().encode_args.len();
+    }
+
+    pub fn get_vmaf_count(&self) -> usize {
+        return self.state.lock().unwrap().vmaf_count;
+    }
+}
+
+impl ProcessRunner for FakeRunner {
+    fn spawn_encode(
+        &self,
+        args: &FfmpegArgs,
+        _verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<(Box<dyn FfmpegProcess>, Box<dyn ProgressFeed>), EngineError> {
+        let mut state = self.state.lock().unwrap();
+        state.encode_args.push(args.clone());
+        let progress = state
+            .queued_progress
+            .pop_front()
+            .unwrap_or(state.default_progress);
+
+        let feed = FakeProgressFeed::start(progress, state.metadata.frames, args.stats_period);
+        let success = !matches!(progress, FakeProgress::Erroring);
+        if success {
+            write_log(
+                log_file,
+                &format!("fake encode of {} frames", state.metadata.frames),
+            );
+        } else {
+            write_log(log_file, FAKE_ERROR_OUTPUT);
+        }
+
+        return Ok((Box::new(FakeProcess { success }), Box::new(feed)));
+    }
+
+    fn spawn_vmaf(
+        &self,
+        _args: &FfmpegArgs,
+        _verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<Box<dyn VmafProcess>, EngineError> {
+        let mut state = self.state.lock().unwrap();
+        state.vmaf_count += 1;
+        let score = state.queued_vmaf_scores.pop_front().unwrap_or(Some(0.0));
+        match score {
+            Some(score) => write_log(log_file, &format!("VMAF score: {}", score)),
+            None => write_log(log_file, FAKE_VMAF_ERROR_OUTPUT),
+        }
+
+        return Ok(Box::new(FakeVmafProcess { score }));
+    }
+
+    fn probe(&self, _video_file: &String) -> Result<MetaData, ProbeError> {
+        return Ok(self.state.lock().unwrap().metadata);
+    }
+
+    // the fake video's size spread evenly over every frame of the source
+    fn probe_packets(&self, _video_file: &String) -> Result<Vec<Packet>, ProbeError> {
+        let metadata = self.state.lock().unwrap().metadata;
+        let bytes = FAKE_VIDEO_KIBIBYTES * 1024 / metadata.frames.max(1);
+        let packets = (0..metadata.frames)
+            .map(|frame| Packet::new(frame as f64 / metadata.fps.as_f64(), bytes, frame == 0))
+            .collect();
+
+        return Ok(packets);
+    }
+
+    fn probe_encoded_stream(&self, _video_file: &String) -> Result<EncodedStream, ProbeError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(stream) = state.queued_streams.pop_front() {
+            return Ok(stream);
+        }
+
+        let args = state.encode_args.last().unwrap();
+        return Ok(get_honored_stream(args, state.metadata.frames));
+    }
+
+    fn hash_frames(
+        &self,
+        _video_file: &String,
+        _decoder: &Decoder,
+    ) -> Result<Vec<String>, FrameHashError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(hashes) = state.queued_frame_hashes.pop_front() {
+            return Ok(hashes);
+        }
+
+        return Ok((0..state.metadata.frames)
+            .map(|frame| format!("{:032x}", frame))
+            .collect());
+    }
+
+    fn hwaccels(&self) -> Option<Vec<String>> {
+        return self.state.lock().unwrap().hwaccels.clone();
+    }
+
+    // the same number of stats periods as the real limits, on the fake encodes' clock
+    fn progress_limits(&self) -> ProgressLimits {
+        let limits = ProgressLimits::default();
+        return ProgressLimits {
+            overload_time: limits.overload_time.mul_f32(FAKE_TIME_SCALE),
+            allowed_ffmpeg_downtime: limits.allowed_ffmpeg_downtime.mul_f32(FAKE_TIME_SCALE),
+        };
+    }
+
+    // there's no ffmpeg holding on to anything
+    fn release_delay(&self) -> Duration {
+        return Duration::ZERO;
+    }
+}
+
+struct FakeProcess {
+    success: bool,
+}
+
+impl FfmpegProcess for FakeProcess {
+    fn id(&self) -> Option<u32> {
+        return None;
+    }
+
+    fn kill(&mut self) {}
+
+    fn wait(&mut self) -> bool {
+        return self.success;
+    }
+
+    fn error_output(&mut self) -> String {
+        if self.success {
+            return String::new();
+        }
+
+        return String::from(FAKE_ERROR_OUTPUT);
+    }
+
+    fn finish(&mut self) -> String {
+        if !self.success {
+            return String::from(FAKE_ERROR_OUTPUT);
+        }
+
+        return format!(
+            "[out#0/null @ 0x0] video:{}KiB audio:0KiB subtitle:0KiB other streams:0KiB global headers:0KiB muxing overhead: unknown",
+            FAKE_VIDEO_KIBIBYTES
+        );
+    }
+}
+
+struct FakeVmafProcess {
+    score: Option<c_float>,
+}
+
+impl VmafProcess for FakeVmafProcess {
+    fn wait_for_score(&mut self) -> Result<c_float, VmafError> {
+        return self
+            .score
+            .ok_or(VmafError::Failed(String::from(FAKE_VMAF_ERROR_OUTPUT)));
+    }
+}
+
+// an encoder that did everything it was asked, with no b frames and keyframes only where asked for
+fn get_honored_stream(args: &FfmpegArgs, frames: u64) -> EncodedStream {
+    let mut encoder_args = args.encoder_args.split_whitespace();
+    let profile = encoder_args
+        .find(|arg| *arg == "-profile:v")
+        .and(encoder_args.next())
+        .unwrap_or("");
+    let keyframes = match args.gop_frames {
+        Some(gop_frames) => frames.div_ceil(gop_frames as u64),
+        None => 1,
+    };
+
+    return EncodedStream {
+        codec: args.encoder.split('_').next().unwrap().to_string(),
+        profile: if args.ten_bit {
+            format!("{} 10", profile)
+        } else {
+            profile.to_string()
+        },
+        level: None,
+        pix_fmt: String::from(if args.ten_bit {
+            "yuv420p10le"
+        } else {
+            "yuv420p"
+        }),
+        i_frames: keyframes,
+        p_frames: frames - keyframes,
+        b_frames: 0,
+        keyframe_interval: args.gop_frames.filter(|_| keyframes > 1),
+    };
+}
+
+// stands in for the stderr a real ffmpeg would have written to its log
+fn write_log(log_file: Option<&Path>, output: &str) {
+    if let Some(path) = log_file {
+        let _ = fs::write(path, output);
+    }
+}
+
+pub struct FakeProgressFeed {
+    reporter: StoppableHandle<()>,
+    frame: Arc<AtomicUsize>,
+    previous_frame: Arc<AtomicUsize>,
+}
+
+impl FakeProgressFeed {
+    // reports progress every stats period, just like ffmpeg's -progress output, only on a faster clock
+    pub fn start(progress: FakeProgress, total_frames: u64, stats_period: c_float) -> Self {
+        let frame = Arc::new(AtomicUsize::new(0));
+        let previous_frame = Arc::new(AtomicUsize::new(0));
+
+        let reporter_frame = frame.clone();
+        let reporter_previous_frame = previous_frame.clone();
+        let reporter = stoppable_thread::spawn(move |stopped| {
+            let (fps, stall_at) = match progress {
+                FakeProgress::Steady(fps) | FakeProgress::Overloaded(fps) => (fps, total_frames),
+                FakeProgress::Stalled(fps, stall_at) => (fps, stall_at.min(total_frames)),
+                FakeProgress::Erroring => (0, 0),
+            };
+            let frames_per_period = (fps as c_float * stats_period) as usize;
+
+            while !stopped.get() {
+                sleep(Duration::from_secs_f32(stats_period * FAKE_TIME_SCALE));
+
+                let current = reporter_frame.load(Ordering::Relaxed);
+                if current as u64 >= stall_at {
+                    continue;
+                }
+
+                let next = (current + frames_per_period).min(stall_at as usize);
+                reporter_previous_frame.store(current, Ordering::Relaxed);
+                reporter_frame.store(next, Ordering::Relaxed);
+            }
+        });
+
+        return Self {
+            reporter,
+            frame,
+            previous_frame,
+        };
+    }
+}
+
+impl ProgressFeed for FakeProgressFeed {
+    fn frames(&self) -> (usize, usize) {
+        return (
+            self.frame.load(Ordering::Relaxed),
+            self.previous_frame.load(Ordering::Relaxed),
+        );
+    }
+
+    fn stop(self: Box<Self>) {
+        let _ = self.reporter.stop().join();
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// which per-permutation ffmpeg logs are kept once their permutation is done
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LogRetention {
+    // a log of an encode that went fine is rarely worth reading
+    #[default]
+    Failures,
+    All,
+    None,
+}
+
+impl FromStr for LogRetention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "failures" => Ok(LogRetention::Failures),
+            "all" => Ok(LogRetention::All),
+            "none" => Ok(LogRetention::None),
+            _ => Err(format!(
+                "[{}] is not a log retention, expected one of: failures, all, none",
+                s
+            )),
+        };
+    }
+}
+
+impl fmt::Display for LogRetention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            LogRetention::Failures => write!(f, "failures"),
+            LogRetention::All => write!(f, "all"),
+            LogRetention::None => write!(f, "none"),
+        };
+    }
+}
+
+// decides where each ffmpeg child's stderr is written to, and cleans up the logs not worth keeping
+#[derive(Clone)]
+pub struct FfmpegLogs {
+    directory: String,
+    retention: LogRetention,
+}
+
+impl FfmpegLogs {
+    pub fn new(directory: &String, retention: LogRetention) -> Self {
+        return Self {
+            directory: directory.clone(),
+            retention,
+        };
+    }
+
+    // i.e. ffmpeg-perm-3-8c5d2f1a-encode.log; None when no logs are being kept at all
+    pub fn get_log_path(
+        &self,
+        index: usize,
+        encoder_settings: &str,
+        stage: &str,
+    ) -> Option<PathBuf> {
+        if self.retention == LogRetention::None {
+            return None;
+        }
+
+        let file_name = format!(
+            "ffmpeg-perm-{}-{}-{}.log",
+            index + 1,
+            hash_settings(encoder_settings),
+            stage
+        );
+
+        // same as the results file, no directory means the current one
+        if self.directory.is_empty() {
+            return Some(PathBuf::from(file_name));
+        }
+
+        return Some(PathBuf::from(&self.directory).join(file_name));
+    }
+
+    // call once the ffmpeg child writing to the log is done
+    pub fn finish(&self, log_path: &Option<PathBuf>, failed: bool) {
+        if let Some(path) = log_path {
+            if self.retention == LogRetention::Failures && !failed {
+                // best effort, a leftover log is only clutter
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+// fnv-1a; unlike std's hasher, the same settings get the same name across builds
+fn hash_settings(encoder_settings: &str) -> String {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in encoder_settings.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    return format!("{:08x}", hash);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::ffmpeg_logs::{hash_settings, FfmpegLogs, LogRetention};
+
+    static SETTINGS: &str = "-preset p1 -tune hq -profile:v high";
+
+    fn get_log_directory(test_name: &str) -> String {
+        let dir: PathBuf = std::env::temp_dir().join(format!("ffmpeg-logs-{}", test_name));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        fs::create_dir_all(&dir).unwrap();
+        return dir.to_str().unwrap().to_string();
+    }
+
+    #[test]
+    fn hash_settings_test() {
+        assert_eq!(hash_settings(""), "811c9dc5");
+        assert_eq!(hash_settings(SETTINGS), hash_settings(SETTINGS));
+        assert_ne!(
+            hash_settings(SETTINGS),
+            hash_settings("-preset p2 -tune hq -profile:v high")
+        );
+    }
+
+    #[test]
+    fn get_log_path_test() {
+        let logs = FfmpegLogs::new(&String::from("logs"), LogRetention::Failures);
+        let expected = format!("ffmpeg-perm-3-{}-encode.log", hash_settings(SETTINGS));
+
+        assert_eq!(
+            logs.get_log_path(2, SETTINGS, "encode"),
+            Some(PathBuf::from("logs").join(expected))
+        );
+    }
+
+    #[test]
+    fn get_log_path_current_directory_test() {
+        let logs = FfmpegLogs::new(&String::new(), LogRetention::All);
+        let expected = format!("ffmpeg-perm-1-{}-vmaf-1.log", hash_settings(SETTINGS));
+
+        assert_eq!(
+            logs.get_log_path(0, SETTINGS, "vmaf-1"),
+            Some(PathBuf::from(expected))
+        );
+    }
+
+    #[test]
+    fn no_log_path_without_retention_test() {
+        let logs = FfmpegLogs::new(&String::from("logs"), LogRetention::None);
+        assert_eq!(logs.get_log_path(0, SETTINGS, "encode"), None);
+    }
+
+    #[test]
+    fn finish_keeps_only_failures_test() {
+        let logs = FfmpegLogs::new(&get_log_directory("failures"), LogRetention::Failures);
+        let passed = logs.get_log_path(0, SETTINGS, "encode");
+        let failed = logs.get_log_path(1, SETTINGS, "encode");
+        fs::write(passed.as_ref().unwrap(), "").unwrap();
+        fs::write(failed.as_ref().unwrap(), "").unwrap();
+
+        logs.finish(&passed, false);
+        logs.finish(&failed, true);
+
+        assert!(!passed.unwrap().exists());
+        assert!(failed.unwrap().exists());
+    }
+
+    #[test]
+    fn finish_keeps_all_test() {
+        let logs = FfmpegLogs::new(&get_log_directory("all"), LogRetention::All);
+        let passed = logs.get_log_path(0, SETTINGS, "encode");
+        fs::write(passed.as_ref().unwrap(), "").unwrap();
+
+        logs.finish(&passed, false);
+
+        assert!(passed.unwrap().exists());
+    }
+
+    #[test]
+    fn parse_retention_test() {
+        assert_eq!(
+            "failures".parse::<LogRetention>(),
+            Ok(LogRetention::Failures)
+        );
+        assert_eq!("all".parse::<LogRetention>(), Ok(LogRetention::All));
+        assert_eq!("none".parse::<LogRetention>(), Ok(LogRetention::None));
+        assert!("some".parse::<LogRetention>().is_err());
+    }
+}
@@ -1,8 +1,19 @@
 pub mod benchmark_engine;
+pub mod conformance;
+pub mod decode_check;
 mod engine;
+pub mod error;
+pub mod fake_runner;
+pub mod ffmpeg_logs;
 pub mod fps_stats;
 pub mod permutation_engine;
+mod platforms;
 pub mod progressbar;
 pub mod result;
+pub mod runner;
+pub mod settings_check;
+mod staging;
 pub mod stat_tcp_listener;
+pub mod telemetry;
 pub mod threads;
+pub mod trials;
@@ -1,21 +1,33 @@
 use std::collections::HashSet;
 use std::ffi::c_float;
-use std::fs::{remove_file, rename};
 use std::time::{Duration, SystemTime};
 
 use compound_duration::format_dhms;
 use crossbeam_channel::Receiver;
 use ctrlc::Error;
 
+use environment::fingerprint::HostFingerprint;
+use environment::load::LoadSampler;
 use ffmpeg::args::FfmpegArgs;
-use ffmpeg::report_files::{extract_vmaf_score, get_latest_ffmpeg_report_file, read_last_line_at};
+use gpus::telemetry::TelemetrySampler;
 use permutation::permutation::Permutation;
-
-use crate::engine::{log_permutation_header, run_encode, spawn_ffmpeg_child};
+use permutation::platform::PlatformProfile;
+
+use crate::engine::{
+    check_source_storage, log_failed_permutations, log_permutation_header, probe_sources,
+    run_trials,
+};
+use crate::error::EngineError;
+use crate::ffmpeg_logs::{FfmpegLogs, LogRetention};
+use crate::platforms::Platforms;
 use crate::progressbar;
 use crate::progressbar::draw_yellow_bar;
 use crate::result::{log_results_to_file, PermutationResult};
+use crate::runner::{FfmpegRunner, ProcessRunner};
+use crate::staging::SourceStager;
+use crate::telemetry::Monitoring;
 use crate::threads::setup_ctrl_channel;
+use crate::trials::Trials;
 
 pub static TCP_OUTPUT: &str = "-f {} tcp://localhost:2000";
 
@@ -31,31 +43,88 @@ pub struct PermutationEngine {
     dup_results: Vec<PermutationResult>,
     vmaf_scores: HashSet<String>,
     log_files_directory: String,
+    ffmpeg_log_retention: LogRetention,
+    host: Option<HostFingerprint>,
+    monitoring: Monitoring,
+    trials: Trials,
+    platforms: Platforms,
+    runner: Box<dyn ProcessRunner>,
 }
 
 // note: we can make 2 engines; benchmark engine, and the permutation engine
 // this way we can make the run() method a lot less complex
 impl PermutationEngine {
     pub fn new(log_files: String) -> Self {
+        return Self::with_runner(log_files, Box::new(FfmpegRunner));
+    }
+
+    pub fn with_runner(log_files: String, runner: Box<dyn ProcessRunner>) -> Self {
         return Self {
             permutations: vec![],
             results: vec![],
             dup_results: vec![],
             vmaf_scores: HashSet::new(),
             log_files_directory: log_files,
+            ffmpeg_log_retention: LogRetention::default(),
+            host: None,
+            monitoring: Monitoring::default(),
+            trials: Trials::default(),
+            platforms: Platforms::default(),
+            runner,
         };
     }
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), EngineError> {
         let runtime = SystemTime::now();
         let ctrl_channel = setup_ctrl_channel();
+        probe_sources(&mut self.permutations, self.runner.as_ref())?;
+        self.platforms.restrict(&mut self.permutations)?;
+        check_source_storage(&mut self.permutations)?;
+
+        let mut stager = SourceStager::new();
+        let outcome = self.run_permutations(&ctrl_channel, &mut stager);
+        // staged sources are released whether or not the run made it to the end
+        stager.cleanup();
+        outcome?;
+
+        // produce output files and other logging here
+        let runtime_str = format_dhms(runtime.elapsed().unwrap().as_secs());
+
+        log_results_to_file(
+            self.results.clone(),
+            &runtime_str,
+            self.dup_results.clone(),
+            self.permutations[0].bitrate,
+            false,
+            &self.log_files_directory,
+            &self.host,
+        )
+        .map_err(EngineError::Results)?;
+        println!("Benchmark runtime: {}", runtime_str);
+        log_failed_permutations(&self.results);
+
+        return Ok(());
+    }
+
+    fn run_permutations(
+        &mut self,
+        ctrl_channel: &Result<Receiver<()>, Error>,
+        stager: &mut SourceStager,
+    ) -> Result<(), EngineError> {
+        let logs = FfmpegLogs::new(&self.log_files_directory, self.ffmpeg_log_retention);
         let mut target_quality_found = false;
+        // the rate control (and gop) whose remaining bitrates are skipped, once one of them reached the target quality
+        let mut finished_sweep = None;
 
         let mut ignore_factor = 1 as c_float;
         let mut calc_time: Option<Duration> = None;
         for i in 0..self.permutations.clone().len() {
             let permutation_start_time = SystemTime::now();
             let mut permutation = self.permutations[i].clone();
+            if finished_sweep == Some((permutation.rate_control, permutation.gop)) {
+                continue;
+            }
+
             log_permutation_header(i, &self.permutations, calc_time, ignore_factor);
 
             // if this permutation was added to the list of duplicates, skip to save calculation time
@@ -63,23 +132,43 @@ impl PermutationEngine {
                 && permutation.check_quality
                 && will_be_duplicate(&self.dup_results, &permutation)
             {
-                draw_yellow_bar(permutation.get_metadata().frames);
+                draw_yellow_bar(permutation.metadata.frames);
                 println!("\n!!! Above encoder settings will produce identical vmaf score as other permutations, skipping... \n");
                 continue;
             }
 
-            let mut result = run_encode(permutation.clone(), &ctrl_channel);
+            stager.prepare(&mut permutation)?;
+            let mut result = run_trials(
+                permutation.clone(),
+                i,
+                ctrl_channel,
+                self.runner.as_ref(),
+                &logs,
+                &self.monitoring,
+                &self.trials,
+            )?;
             calc_time = Option::from(permutation_start_time.elapsed().unwrap());
 
-            if !result.was_overloaded && permutation.check_quality.clone() {
+            if !result.was_overloaded && !result.has_failed() && permutation.check_quality.clone() {
                 let vmaf_start_time = SystemTime::now();
-                result.vmaf_score = check_encode_quality(
+                let vmaf_score = check_encode_quality(
                     &mut permutation.clone(),
-                    &ctrl_channel,
+                    ctrl_channel,
                     permutation.verbose,
                     i,
-                )
-                .expect("Failed to check encode quality");
+                    self.runner.as_ref(),
+                    &logs,
+                );
+
+                match vmaf_score {
+                    Ok(vmaf_score) => result.vmaf_score = vmaf_score,
+                    // the encode streaming into vmaf can be rejected just like the original
+                    Err(EngineError::EncodeFailed(error_output)) => {
+                        println!("{}\n", error_output);
+                        result.error = Some(error_output);
+                    }
+                    Err(e) => return Err(e),
+                }
 
                 result.vmaf_calculation_time = vmaf_start_time.elapsed().unwrap().as_secs();
 
@@ -92,8 +181,12 @@ impl PermutationEngine {
                 calc_time = Option::from(permutation_start_time.elapsed().unwrap());
             }
 
+            self.platforms.check(&mut result, &permutation);
+
             let is_initial_bitrate_permutation_over = i == self.permutations.len() - 1
-                || self.permutations[i + 1].clone().bitrate != permutation.bitrate;
+                || self.permutations[i + 1].clone().bitrate != permutation.bitrate
+                || self.permutations[i + 1].rate_control != permutation.rate_control
+                || self.permutations[i + 1].gop != permutation.gop;
             self.add_result(
                 result,
                 is_initial_bitrate_permutation_over,
@@ -110,32 +203,78 @@ impl PermutationEngine {
 
             // stop if we've found the target quality, and we're done permuting over the current bitrate
             if target_quality_found && is_initial_bitrate_permutation_over {
+                let has_other_sweeps = self.permutations[i + 1..].iter().any(|p| {
+                    p.rate_control != permutation.rate_control || p.gop != permutation.gop
+                });
+                if !has_other_sweeps {
+                    println!(
+                        "Found VMAF score >= {}, stopping permutations...",
+                        TARGET_QUALITY
+                    );
+                    break;
+                }
+
+                // higher bitrates of this rate control won't be needed, but the other rate controls/gops still are
                 println!(
-                    "Found VMAF score >= {}, stopping permutations...",
+                    "Found VMAF score >= {}, moving on to the next rate control or GOP...",
                     TARGET_QUALITY
                 );
-                break;
+                finished_sweep = Some((permutation.rate_control, permutation.gop));
+                target_quality_found = false;
             }
         }
 
-        // produce output files and other logging here
-        let runtime_str = format_dhms(runtime.elapsed().unwrap().as_secs());
+        return Ok(());
+    }
 
-        log_results_to_file(
-            self.results.clone(),
-            &runtime_str,
-            self.dup_results.clone(),
-            self.permutations[0].bitrate,
-            false,
-            &self.log_files_directory,
-        );
-        println!("Benchmark runtime: {}", runtime_str);
+    // which of the ffmpeg logs written next to the results file are kept
+    pub fn set_ffmpeg_log_retention(&mut self, retention: LogRetention) {
+        self.ffmpeg_log_retention = retention;
+    }
+
+    // written at the top of the results file so they can be traced back to the machine they came from
+    pub fn set_host_fingerprint(&mut self, host: HostFingerprint) {
+        self.host = Some(host);
+    }
+
+    // samples the gpu while every encode runs; without one no telemetry is recorded
+    pub fn set_telemetry_sampler(&mut self, sampler: Box<dyn TelemetrySampler>) {
+        self.monitoring.set_gpu_sampler(sampler);
+    }
+
+    // samples the cpu and system load while every encode runs, and checks for background load before each
+    pub fn set_load_sampler(&mut self, sampler: Box<dyn LoadSampler>) {
+        self.monitoring.set_load_sampler(sampler);
+    }
+
+    // % of cpu busy before an encode above which its results are flagged, and optionally run again
+    pub fn set_background_load_check(&mut self, max_background_load: f64, rerun: bool) {
+        self.monitoring.max_background_load = max_background_load;
+        self.monitoring.rerun_contaminated = rerun;
+    }
+
+    // runs every encode this many times and reports the mean, after an uncounted warm-up run if asked for
+    pub fn set_trials(&mut self, count: u32, warmup: bool) {
+        self.trials = Trials { count, warmup };
+    }
+
+    // checks every result against the streaming platforms, and only runs what at least one of them takes if restricted
+    pub fn set_platforms(&mut self, profiles: Vec<PlatformProfile>, restrict: bool) {
+        self.platforms = Platforms { profiles, restrict };
     }
 
     pub fn add(&mut self, permutation: Permutation) {
         self.permutations.push(permutation);
     }
 
+    pub fn get_results(&self) -> &Vec<PermutationResult> {
+        return &self.results;
+    }
+
+    pub fn get_duplicate_results(&self) -> &Vec<PermutationResult> {
+        return &self.dup_results;
+    }
+
     fn add_result(
         &mut self,
         result: PermutationResult,
@@ -148,6 +287,7 @@ impl PermutationEngine {
         if !allow_duplicates
             && is_checking_quality
             && !result.was_overloaded
+            && !result.has_failed()
             && !is_bitrate_permutation_over
         {
             let score_str = result.vmaf_score.to_string();
@@ -170,8 +310,10 @@ fn calc_vmaf_score(
     verbose: bool,
     attempt: i32,
     perm_num: usize,
-) -> Option<c_float> {
-    let ffmpeg_args = FfmpegArgs::build_ffmpeg_args(
+    runner: &dyn ProcessRunner,
+    logs: &FfmpegLogs,
+) -> Result<c_float, EngineError> {
+    let mut ffmpeg_args = FfmpegArgs::build_ffmpeg_args(
         p.video_file.clone(),
         p.encoder.clone(),
         &p.encoder_settings,
@@ -179,12 +321,14 @@ fn calc_vmaf_score(
         p.decode_run,
         p.ten_bit,
     );
+    ffmpeg_args.rate_control = p.rate_control;
 
     println!(
         "Calculating vmaf score; might take longer than original encode depending on your CPU..."
     );
 
-    let metadata = p.get_metadata();
+    let metadata = p.get_metadata()?;
+    ffmpeg_args.gop_frames = p.gop.map(|gop| gop.to_frames(metadata.fps.as_f64()));
     // first spawn the ffmpeg instance to listen for incoming encode
     let vmaf_args = ffmpeg_args.map_to_vmaf(metadata.fps);
     if verbose {
@@ -194,7 +338,12 @@ fn calc_vmaf_score(
         );
     }
 
-    let mut vmaf_child = spawn_ffmpeg_child(&vmaf_args, verbose, None);
+    let vmaf_log = logs.get_log_path(
+        perm_num,
+        &p.encoder_settings,
+        &format!("vmaf-{}", attempt + 1),
+    );
+    let mut vmaf_child = runner.spawn_vmaf(&vmaf_args, verbose, vmaf_log.as_deref())?;
 
     // then spawn the ffmpeg instance to perform the encoding
     let mut encoder_args = ffmpeg_args.clone();
@@ -208,66 +357,51 @@ fn calc_vmaf_score(
         );
     }
 
-    let mut encoder_child = spawn_ffmpeg_child(&encoder_args, verbose, None);
+    let encode_log = logs.get_log_path(
+        perm_num,
+        &p.encoder_settings,
+        &format!("vmaf-encode-{}", attempt + 1),
+    );
+    let (mut encoder_child, progress) =
+        match runner.spawn_encode(&encoder_args, verbose, encode_log.as_deref()) {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                logs.finish(&vmaf_log, true);
+                logs.finish(&encode_log, true);
+                return Err(e);
+            }
+        };
 
     // not the cleanest way to do this but oh well
-    progressbar::watch_encode_progress(
+    let trial_result = progressbar::watch_encode_progress(
+        progress,
         metadata.frames,
         false,
         metadata.fps,
         false,
         ffmpeg_args.stats_period,
+        &runner.progress_limits(),
         ctrl_channel,
     );
 
+    if trial_result.cancelled {
+        encoder_child.kill();
+        return Err(EngineError::Cancelled);
+    }
+
     // need to wait for the vmaf calculating thread to finish
     println!("VMAF calculation finishing up...");
-    let vmaf_child_status = vmaf_child.wait().expect("Vmaf child could not wait");
-    let vmaf_log_file = get_latest_ffmpeg_report_file();
-    // TODO: this does fix the issue for apple however, this may not scale very well across other vendors
-    // or the output line number may have changed recently where we'll need to make this not dependent on line numbers at all
-    let line_number = if encoder_args.encoder.contains("videotoolbox") {
-        15
-    } else {
-        3
-    };
-    let vmaf_score_line = read_last_line_at(line_number);
+    let vmaf_score = vmaf_child.wait_for_score();
     //Cleanup process
-    encoder_child
-        .kill()
-        .expect("Could not kill encoder process");
-
-    if vmaf_child_status.success() {
-        let vmaf_score_extract = extract_vmaf_score(vmaf_score_line.as_str());
-        let vmaf_score = vmaf_score_extract.expect(&format!(
-            "Could not parse score from line: {}",
-            vmaf_score_line
-        ));
-        println!("VMAF score: {}\n", vmaf_score);
-        // Cleanup log file
-        remove_file(vmaf_log_file.as_path()).unwrap();
-        return Some(vmaf_score);
-    }
+    encoder_child.kill();
 
-    let org_filename = vmaf_log_file.file_name().unwrap().to_str().unwrap();
-    let mut ffmpeg_error_log = vmaf_log_file.clone();
-    ffmpeg_error_log.set_extension("");
-    let ffmpeg_error_log_filename = ffmpeg_error_log.file_name().unwrap().to_str().unwrap();
-    let new_filename = format!(
-        "{}-perm-{}-attempt-{}.log",
-        ffmpeg_error_log_filename,
-        perm_num + 1,
-        attempt + 1
-    );
-    rename(org_filename, &new_filename).expect("Could not rename file");
+    logs.finish(&vmaf_log, vmaf_score.is_err());
+    logs.finish(&encode_log, vmaf_score.is_err());
 
-    if verbose {
-        let ffmpeg_error_line = read_last_line_at(1);
-        println!("{}", ffmpeg_error_line.as_str());
-        println!("See {} for more details.", new_filename);
-    }
+    let vmaf_score = vmaf_score?;
+    println!("VMAF score: {}\n", vmaf_score);
 
-    return None;
+    return Ok(vmaf_score);
 }
 
 fn check_encode_quality(
@@ -275,36 +409,39 @@ fn check_encode_quality(
     ctrl_channel: &Result<Receiver<()>, Error>,
     verbose: bool,
     perm_num: usize,
-) -> Option<c_float> {
-    let mut quality_val = None;
-    for attempt in 0..MAX_ATTEMPTS_CALC_QUALITY {
+    runner: &dyn ProcessRunner,
+    logs: &FfmpegLogs,
+) -> Result<c_float, EngineError> {
+    let mut attempt = 0;
+    loop {
         if verbose {
             print!("[ ATTEMPT {}/{} ] ", attempt + 1, MAX_ATTEMPTS_CALC_QUALITY);
         }
-        quality_val = calc_vmaf_score(p, &ctrl_channel, verbose, attempt, perm_num);
-        match quality_val {
-            Some(val) => {
-                quality_val = Some(val);
-                break;
-            }
 
-            None => println!("Check encode quality failed. Retrying..."),
-        }
-    }
+        match calc_vmaf_score(p, &ctrl_channel, verbose, attempt, perm_num, runner, logs) {
+            Ok(score) => return Ok(score),
+            // only a failed calculation is worth retrying; anything else would fail the same way again
+            Err(EngineError::Vmaf(e)) => {
+                attempt += 1;
+                if attempt >= MAX_ATTEMPTS_CALC_QUALITY {
+                    return Err(EngineError::VmafRetriesExhausted {
+                        attempts: MAX_ATTEMPTS_CALC_QUALITY,
+                        last_error: e,
+                    });
+                }
 
-    if quality_val.is_none() {
-        panic!(
-            "Error, Failed to calc encode quality after {} attempts",
-            MAX_ATTEMPTS_CALC_QUALITY
-        );
+                println!("Check encode quality failed: {}. Retrying...", e);
+            }
+            Err(e) => return Err(e),
+        }
     }
-
-    return quality_val;
 }
 
 fn will_be_duplicate(duplicates: &Vec<PermutationResult>, next_permutation: &Permutation) -> bool {
     for dup in duplicates {
-        if dup.encoder_settings == next_permutation.encoder_settings {
+        if dup.encoder_settings == next_permutation.encoder_settings
+            && dup.gop == next_permutation.gop
+        {
             return true;
         }
     }
@@ -317,9 +454,31 @@ fn insert_format_from(input: &str, encoder: &String) -> String {
         "h264"
     } else if encoder.contains("hevc") {
         "hevc"
-    } else {
+    } else if encoder.contains("av1") {
+        // a raw av1 stream (obu) can't be reliably probed by the listening side, ivf gives it a header to be found by
         "ivf"
+    } else {
+        // anything else (i.e. prores) can't go in ivf, nut carries any codec without needing to seek
+        "nut"
     };
     // this should be cleaner when we support more than 1 type
     return input.replace("{}", format);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::permutation_engine::{insert_format_from, TCP_OUTPUT};
+
+    #[test]
+    fn insert_format_from_test() {
+        let format_for = |encoder: &str| insert_format_from(TCP_OUTPUT, &String::from(encoder));
+        assert_eq!(format_for("h264_nvenc"), "-f h264 tcp://localhost:2000");
+        assert_eq!(format_for("hevc_amf"), "-f hevc tcp://localhost:2000");
+        assert_eq!(format_for("av1_nvenc"), "-f ivf tcp://localhost:2000");
+        assert_eq!(format_for("av1_amf"), "-f ivf tcp://localhost:2000");
+        assert_eq!(
+            format_for("prores_videotoolbox"),
+            "-f nut tcp://localhost:2000"
+        );
+    }
+}
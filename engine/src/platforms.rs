@@ -0,0 +1,70 @@
+use permutation::permutation::Permutation;
+use permutation::platform::PlatformProfile;
+
+use crate::error::EngineError;
+use crate::result::PermutationResult;
+
+// the streaming platforms each result is checked against, and whether permutations they won't take are run at all
+#[derive(Default)]
+pub struct Platforms {
+    pub profiles: Vec<PlatformProfile>,
+    pub restrict: bool,
+}
+
+impl Platforms {
+    // drops the permutations none of the platforms take, once their sources have been probed
+    pub fn restrict(&self, permutations: &mut Vec<Permutation>) -> Result<(), EngineError> {
+        if !self.restrict || self.profiles.is_empty() {
+            return Ok(());
+        }
+
+        let total = permutations.len();
+        permutations.retain(|p| {
+            self.profiles
+                .iter()
+                .any(|profile| profile.check(p, &p.metadata, None).is_ok())
+        });
+
+        if permutations.is_empty() {
+            return Err(EngineError::NoCompliantPermutations(
+                self.get_platform_names(),
+            ));
+        }
+
+        if permutations.len() != total {
+            println!(
+                "Skipping {} of {} permutations that {} wouldn't take",
+                total - permutations.len(),
+                total,
+                self.get_platform_names()
+            );
+        }
+
+        return Ok(());
+    }
+
+    // records which platforms the result could be streamed to, now that its bitrate is known
+    pub fn check(&self, result: &mut PermutationResult, p: &Permutation) {
+        if result.decode_run || result.has_failed() {
+            return;
+        }
+
+        result.platform_checks = self
+            .profiles
+            .iter()
+            .map(|profile| profile.check_permutation(p, &p.metadata, result.achieved_bitrate))
+            .collect();
+        if !result.platform_checks.is_empty() {
+            println!("  Platforms:\t{}", result.get_satisfied_platforms());
+        }
+    }
+
+    fn get_platform_names(&self) -> String {
+        return self
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+    }
+}
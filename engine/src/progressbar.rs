@@ -1,6 +1,5 @@
 use std::ffi::c_float;
 use std::fmt::Write;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time;
 use std::time::SystemTime;
 
@@ -8,13 +7,19 @@ use crossbeam_channel::Receiver;
 use ctrlc::Error;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 
-use crate::stat_tcp_listener::start_listening_to_ffmpeg_stats;
-use crate::threads::exit_on_ctrl_c;
+use ffmpeg::frame_rate::FrameRate;
+
+use crate::runner::{ProgressFeed, ProgressLimits};
+use crate::threads::was_ctrl_c_received;
 
 pub struct TrialResult {
     pub all_fps: Vec<u16>,
     pub was_overloaded: bool,
     pub ffmpeg_error: bool,
+    // the user hit ctrl-c part way through
+    pub cancelled: bool,
+    // size of the encoded video, when it was measured
+    pub video_bytes: Option<u64>,
 }
 
 impl Default for TrialResult {
@@ -23,16 +28,20 @@ impl Default for TrialResult {
             all_fps: vec![],
             was_overloaded: false,
             ffmpeg_error: false,
+            cancelled: false,
+            video_bytes: None,
         }
     }
 }
 
 pub fn watch_encode_progress(
+    progress: Box<dyn ProgressFeed>,
     total_frames: u64,
     detect_overload: bool,
-    target_fps: u32,
+    target_fps: FrameRate,
     verbose: bool,
     stats_period: c_float,
+    limits: &ProgressLimits,
     ctrl_channel: &Result<Receiver<()>, Error>,
 ) -> TrialResult {
     // set this flag every second to see real-time fps statistics and other information
@@ -40,9 +49,6 @@ pub fn watch_encode_progress(
     let verbose_log_interval = time::Duration::from_secs(1);
     let mut log_verbose_timer = SystemTime::now();
 
-    static FRAME: AtomicUsize = AtomicUsize::new(0);
-    static PREVIOUS_FRAME: AtomicUsize = AtomicUsize::new(0);
-
     // keep track of all fps metrics to calculate on later on
     let mut trial_result = TrialResult::default();
     let bar = ProgressBar::new(total_frames);
@@ -50,8 +56,8 @@ pub fn watch_encode_progress(
     bar.tick();
 
     // time it takes for the encoder to need to process the target # of frames
-    let overload_time = time::Duration::from_secs(5);
-    let allowed_ffmpeg_downtime = time::Duration::from_secs(10);
+    let overload_time = limits.overload_time;
+    let allowed_ffmpeg_downtime = limits.allowed_ffmpeg_downtime;
 
     let mut checking_overload = false;
     let mut first_overload_detected = SystemTime::now();
@@ -62,8 +68,6 @@ pub fn watch_encode_progress(
     // how many milliseconds has passed since the last frame stat
     let interval_adjustment = (1.0 / stats_period) as usize;
 
-    let stat_listener = start_listening_to_ffmpeg_stats(verbose, &FRAME, &PREVIOUS_FRAME);
-
     let mut last_frame = 0;
 
     loop {
@@ -73,24 +77,26 @@ pub fn watch_encode_progress(
         }
 
         // important to not get stuck in this thread
-        exit_on_ctrl_c(&ctrl_channel);
+        if was_ctrl_c_received(&ctrl_channel) {
+            trial_result.cancelled = true;
+            break;
+        }
 
         // takes into account the stat update period to properly adjust the calculated FPS
-        let calculated_fps = ((FRAME.load(Ordering::Relaxed)
-            - PREVIOUS_FRAME.load(Ordering::Relaxed))
-            * interval_adjustment) as u16;
+        let (frame, previous_frame) = progress.frames();
+        let calculated_fps = (frame.saturating_sub(previous_frame) * interval_adjustment) as u16;
 
         if verbose && can_log_verbose {
             println!("V: Calculated fps: {}", calculated_fps);
         }
 
         // only record fps counts that are close to 1/4 of the target; any lower is noise
-        if calculated_fps >= (target_fps / 4) as u16 {
+        if calculated_fps as f64 >= target_fps.as_f64() / 4.0 {
             trial_result.all_fps.push(calculated_fps);
         }
 
         // calculate the number of frames processed since the last second (more accurate than using fps from ffmpeg)
-        if detect_overload && calculated_fps < target_fps as u16 {
+        if detect_overload && (calculated_fps as f64) < target_fps.as_f64() {
             if !checking_overload {
                 first_overload_detected = SystemTime::now();
                 checking_overload = true;
@@ -104,12 +110,12 @@ pub fn watch_encode_progress(
             checking_overload = false;
         }
 
-        if FRAME.load(Ordering::Relaxed) >= total_frames as usize {
+        if frame >= total_frames as usize {
             bar.set_position(total_frames);
             break;
         }
 
-        let new_frame = FRAME.load(Ordering::Relaxed) as u64;
+        let new_frame = frame as u64;
         bar.set_position(new_frame);
 
         if new_frame != last_frame {
@@ -135,8 +141,10 @@ pub fn watch_encode_progress(
         }
     }
 
+    let (final_frame, _) = progress.frames();
+
     // change bar style as read
-    if (FRAME.load(Ordering::Relaxed) as u64) < total_frames {
+    if (final_frame as u64) < total_frames {
         set_bar_style(&bar, "red");
         bar.abandon()
     } else {
@@ -145,16 +153,9 @@ pub fn watch_encode_progress(
 
     println!();
 
-    // kill the tcp reading thread
-    stat_listener
-        .stop()
-        .join()
-        .expect("Child thread reading TCP did not finish");
+    progress.stop();
 
-    trial_result.was_overloaded = (FRAME.load(Ordering::Relaxed) as u64) != total_frames;
-    // reset the static values
-    FRAME.store(0, Ordering::Relaxed);
-    PREVIOUS_FRAME.store(0, Ordering::Relaxed);
+    trial_result.was_overloaded = (final_frame as u64) < total_frames;
 
     return trial_result;
 }
@@ -1,18 +1,30 @@
 use std::ffi::c_float;
 use std::fs::File;
+use std::io;
 use std::io::Write;
 
 use compound_duration::format_dhms;
 
+use codecs::gop::Gop;
+use environment::disk::ReadThroughput;
+use environment::fingerprint::HostFingerprint;
+use environment::load::SystemLoad;
+use ffmpeg::decoder::Decoder;
 use ffmpeg::metadata::MetaData;
+use gpus::telemetry::GpuTelemetry;
+use permutation::platform::PlatformCheck;
 
+use crate::conformance::BitrateConformance;
+use crate::decode_check::DecodeCheck;
 use crate::fps_stats::FpsStats;
+use crate::settings_check::SettingsCheck;
+use crate::trials::TrialStats;
 
 #[derive(Clone)]
 pub struct PermutationResult {
     pub encoder: String,
     pub was_overloaded: bool,
-    bitrate: u32,
+    pub bitrate: u32,
     metadata: MetaData,
     pub encoder_settings: String,
     // only if the encodes were successful
@@ -20,7 +32,33 @@ pub struct PermutationResult {
     pub vmaf_calculation_time: u64,
     pub vmaf_score: c_float,
     pub fps_stats: FpsStats,
+    // the bitrate (in Mb/s) a quality-targeted rate control ended up at
+    pub achieved_bitrate: Option<f64>,
+    // the bitrate the output actually came out at, when it was checked
+    pub bitrate_conformance: Option<BitrateConformance>,
+    // what the encoder actually wrote, and which of its settings it didn't apply, when it was checked
+    pub settings_check: Option<SettingsCheck>,
+    // the fixed keyframe interval the encode was made with, if any
+    pub gop: Option<Gop>,
     pub decode_run: bool,
+    // what an existing file was decoded with, and which file, when it wasn't the output of an encode
+    pub decoder: Option<Decoder>,
+    pub decoded_file: Option<String>,
+    // how the decoder's frames compared to a software decode, when they were checked
+    pub decode_check: Option<DecodeCheck>,
+    pub read_throughput: Option<ReadThroughput>,
+    // the file the throughput was measured on
+    pub source_file: String,
+    // min/avg/max of what the gpu was doing during the encode, when it was sampled
+    pub gpu_telemetry: Option<GpuTelemetry>,
+    // cpu and load of the whole system during the encode, when it was sampled
+    pub system_load: Option<SystemLoad>,
+    // how the fps of each trial compared, when the permutation was run more than once
+    pub trial_stats: Option<TrialStats>,
+    // whether each of the streaming platforms asked about would take this permutation
+    pub platform_checks: Vec<PlatformCheck>,
+    // what ffmpeg had to say when it wasn't able to run this permutation
+    pub error: Option<String>,
 }
 
 impl PermutationResult {
@@ -41,22 +79,149 @@ impl PermutationResult {
             vmaf_calculation_time: 0,
             vmaf_score: 0.0,
             fps_stats: FpsStats::default(),
+            achieved_bitrate: None,
+            bitrate_conformance: None,
+            settings_check: None,
+            gop: None,
             decode_run: decode,
+            decoder: None,
+            decoded_file: None,
+            decode_check: None,
+            read_throughput: None,
+            source_file: String::new(),
+            gpu_telemetry: None,
+            system_load: None,
+            trial_stats: None,
+            platform_checks: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn has_failed(&self) -> bool {
+        return self.error.is_some();
+    }
+
+    // the decoder produced different frames than software did, so how fast it did it doesn't count
+    pub fn decodes_wrong(&self) -> bool {
+        return self
+            .decode_check
+            .map(|check| !check.is_correct())
+            .unwrap_or(false);
+    }
+
+    // something else was keeping the cpu busy when the encode started
+    pub fn is_contaminated(&self) -> bool {
+        return self
+            .system_load
+            .map(|load| load.contaminated)
+            .unwrap_or(false);
+    }
+
+    // the encoder quietly ignored some of the settings, so its numbers don't belong to them
+    pub fn has_unapplied_settings(&self) -> bool {
+        return self
+            .settings_check
+            .as_ref()
+            .map(|check| !check.is_applied())
+            .unwrap_or(false);
+    }
+
+    // missed its bitrate or overflowed the decoder's buffer, so it can't be streamed as is
+    pub fn is_nonconformant(&self) -> bool {
+        return self
+            .bitrate_conformance
+            .map(|conformance| !conformance.is_conformant())
+            .unwrap_or(false);
+    }
+
+    // the average fps moved around too much between trials to be compared with other permutations
+    pub fn is_too_variable(&self) -> bool {
+        return self
+            .trial_stats
+            .as_ref()
+            .map(|stats| stats.is_too_variable())
+            .unwrap_or(false);
+    }
+
+    // i.e. 'Twitch, Kick', or 'none'
+    pub fn get_satisfied_platforms(&self) -> String {
+        let satisfied: Vec<String> = self
+            .platform_checks
+            .iter()
+            .filter(|check| check.is_satisfied())
+            .map(|check| check.platform.clone())
+            .collect();
+        if satisfied.is_empty() {
+            return String::from("none");
+        }
+
+        return satisfied.join(", ");
+    }
+
+    // decodes don't have settings of their own, so they're told apart by what they decoded and with what
+    fn get_effective_settings(&self) -> String {
+        if !self.decode_run {
+            return self.encoder_settings.clone();
         }
+
+        return match (&self.decoder, &self.decoded_file) {
+            (Some(decoder), Some(file)) => format!("(Decode: {}) {}", decoder, file),
+            (Some(decoder), None) => format!("(Decode: {})", decoder),
+            _ => String::from("(Decode)"),
+        };
+    }
+
+    // what a result is listed under in the results file's sections; existing files weren't encoded at a bitrate,
+    // and the gop is passed outside the encoder settings so it has to be named for the label to tell them apart
+    fn get_label(&self) -> String {
+        if self.decoded_file.is_some() {
+            return format!("[{}]", self.get_effective_settings());
+        }
+
+        return match self.gop {
+            Some(gop) => format!(
+                "[{}Mb/s] [GOP {}] [{}]",
+                self.bitrate,
+                gop,
+                self.get_effective_settings()
+            ),
+            None => format!("[{}Mb/s] [{}]", self.bitrate, self.get_effective_settings()),
+        };
     }
 
     fn to_string(&self) -> String {
         let mut default = String::new();
 
-        let overloaded_indicator = if self.was_overloaded { "[O]" } else { "   " };
+        let overloaded_indicator = if self.has_failed() {
+            "[F]"
+        } else if self.decodes_wrong() {
+            "[W]"
+        } else if self.was_overloaded {
+            "[O]"
+        } else if self.has_unapplied_settings() {
+            "[S]"
+        } else if self.is_nonconformant() {
+            "[C]"
+        } else if self.is_contaminated() {
+            "[B]"
+        } else if self.is_too_variable() {
+            "[V]"
+        } else {
+            "   "
+        };
+        // quality-targeted rate controls don't have a bitrate until they've encoded
+        let bitrate = match self.achieved_bitrate {
+            Some(achieved_bitrate) => format!("{:.1}Mb/s", achieved_bitrate),
+            None => format!("{}Mb/s", self.bitrate),
+        };
         default.push_str(
             format!(
-                "{}{}x{}\t{}\t{}Mb/s",
+                "{}{}x{}\t{}\t{}",
                 overloaded_indicator,
                 self.metadata.width,
                 self.metadata.height,
                 self.metadata.fps,
-                self.bitrate
+                bitrate
             )
             .as_str(),
         );
@@ -70,12 +235,7 @@ impl PermutationResult {
             format!("0.00000\t\t")
         };
 
-        let effective_settings;
-        if self.decode_run {
-            effective_settings = String::from("(Decode)");
-        } else {
-            effective_settings = self.encoder_settings.clone()
-        }
+        let effective_settings = self.get_effective_settings();
 
         default.push_str(
             format!(
@@ -102,10 +262,17 @@ pub fn log_results_to_file(
     bitrate: u32,
     is_benchmark: bool,
     log_directory: &String,
-) {
+    host: &Option<HostFingerprint>,
+) -> io::Result<()> {
+    // nothing ran, so there's nothing to log
+    let first = match results.first() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+
     // might make this naming here more robust eventually
-    let first_metadata = results.get(0).unwrap().metadata;
-    let encoder = results.get(0).unwrap().encoder.as_str();
+    let first_metadata = first.metadata;
+    let encoder = first.encoder.as_str();
     let permute_file_name = format!(
         "{}-{}-{}.log",
         encoder,
@@ -125,30 +292,52 @@ pub fn log_results_to_file(
         file_name = format!("{}/{}", log_directory, file_name);
     }
 
-    let mut w = File::create(file_name).unwrap();
+    let mut w = File::create(file_name)?;
+    log_host(&mut w, host)?;
 
-    writeln!(&mut w, "Results from entire permutation:").unwrap();
-    writeln!(&mut w, "==================================================================================================================================================================").unwrap();
+    writeln!(&mut w, "Results from entire permutation:")?;
+    writeln!(&mut w, "==================================================================================================================================================================")?;
     let mut time = "[Encode Time]";
     if results.len() > 1 && results[1].decode_run {
         time = "[Encode/Decode Time]"
     }
 
-    writeln!(&mut w, "   [Resolution]\t[FPS]\t[Bitrate]\t{}\t[VMAF Time]\t[VMAF Score]\t[Average FPS]\t[1%'ile]\t[90%'ile]\t[Encoder Settings]", time).unwrap();
+    writeln!(&mut w, "   [Resolution]\t[FPS]\t[Bitrate]\t{}\t[VMAF Time]\t[VMAF Score]\t[Average FPS]\t[1%'ile]\t[90%'ile]\t[Encoder Settings]", time)?;
     let mut current_bitrate = 0;
+    let mut current_gop = None;
 
     for result in &results {
-        // print a line split between bitrate permutations for improved readability
-        if !is_benchmark && current_bitrate != result.bitrate {
-            writeln!(&mut w, "##################################################################################################################################################################").unwrap();
+        // print a line split between bitrate (and gop) permutations for improved readability
+        if !is_benchmark && (current_bitrate != result.bitrate || current_gop != result.gop) {
+            writeln!(&mut w, "##################################################################################################################################################################")?;
             current_bitrate = result.bitrate;
+            current_gop = result.gop;
+
+            if let Some(gop) = result.gop {
+                writeln!(
+                    &mut w,
+                    "   GOP: {} ({} frames)",
+                    gop,
+                    gop.to_frames(result.metadata.fps.as_f64())
+                )?;
+            }
         }
 
-        writeln!(&mut w, "{}", result.to_string()).unwrap();
+        writeln!(&mut w, "{}", result.to_string())?;
     }
-    writeln!(&mut w, "==================================================================================================================================================================").unwrap();
-    writeln!(&mut w, "Benchmark runtime: {}\n", runtime_str).unwrap();
+    writeln!(&mut w, "==================================================================================================================================================================")?;
+    writeln!(&mut w, "Benchmark runtime: {}\n", runtime_str)?;
+    log_read_throughput(&mut w, &results)?;
+    log_gpu_telemetry(&mut w, &results)?;
+    log_system_load(&mut w, &results)?;
+    log_trial_stats(&mut w, &results)?;
+    log_decode_checks(&mut w, &results)?;
+    log_settings_checks(&mut w, &results)?;
+    log_bitrate_conformance(&mut w, &results)?;
+    log_platform_checks(&mut w, &results)?;
 
+    let failures: Vec<PermutationResult> =
+        results.iter().filter(|r| r.has_failed()).cloned().collect();
     let mut has_logged_dup_header = false;
 
     // log out the duplicated results so we can keep track of them
@@ -171,20 +360,257 @@ pub fn log_results_to_file(
         }
 
         if !has_logged_dup_header {
-            writeln!(&mut w, "Encoder settings that produced identical scores:").unwrap();
-            writeln!(&mut w, "==================================================================================================================================================================").unwrap();
+            writeln!(&mut w, "Encoder settings that produced identical scores:")?;
+            writeln!(&mut w, "==================================================================================================================================================================")?;
             has_logged_dup_header = true;
         }
 
-        writeln!(&mut w, "Identical score: {}", perm.vmaf_score).unwrap();
-        writeln!(&mut w, "\tEncoded: [{}]", perm.encoder_settings).unwrap();
+        writeln!(&mut w, "Identical score: {}", perm.vmaf_score)?;
+        writeln!(&mut w, "\tEncoded: [{}]", perm.encoder_settings)?;
 
         for dup in dups {
-            writeln!(&mut w, "\tIgnored: [{}]", dup.encoder_settings).unwrap();
+            writeln!(&mut w, "\tIgnored: [{}]", dup.encoder_settings)?;
         }
 
-        writeln!(&mut w, "\n").unwrap();
+        writeln!(&mut w, "\n")?;
     }
 
-    writeln!(&mut w, "==================================================================================================================================================================").unwrap();
+    writeln!(&mut w, "==================================================================================================================================================================")?;
+    log_failures(&mut w, &failures)?;
+    return Ok(());
+}
+
+fn log_failures(w: &mut File, failures: &Vec<PermutationResult>) -> io::Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(w, "Permutations that ffmpeg was not able to run:")?;
+    writeln!(w, "==================================================================================================================================================================")?;
+
+    for failure in failures {
+        writeln!(w, "{}", failure.get_label())?;
+
+        for line in failure.error.as_deref().unwrap_or("").lines() {
+            writeln!(w, "\t{}", line)?;
+        }
+
+        writeln!(w)?;
+    }
+
+    writeln!(w, "==================================================================================================================================================================")?;
+    return Ok(());
+}
+
+fn log_host(w: &mut File, host: &Option<HostFingerprint>) -> io::Result<()> {
+    let host = match host {
+        Some(host) => host,
+        None => return Ok(()),
+    };
+
+    writeln!(w, "Host:")?;
+    writeln!(w, "==================================================================================================================================================================")?;
+    for line in host.to_lines() {
+        writeln!(w, "{}", line)?;
+    }
+
+    writeln!(w)?;
+    return Ok(());
+}
+
+// writes the section when any of the results has something to say in it, one line per result under its label
+fn log_section(
+    w: &mut File,
+    results: &Vec<PermutationResult>,
+    title: &str,
+    describe: impl Fn(&PermutationResult) -> Option<String>,
+) -> io::Result<()> {
+    let lines: Vec<String> = results
+        .iter()
+        .filter_map(|result| {
+            describe(result).map(|description| format!("{}: {}", result.get_label(), description))
+        })
+        .collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(w, "{}", title)?;
+    for line in lines {
+        writeln!(w, "{}", line)?;
+    }
+
+    writeln!(w)?;
+    return Ok(());
+}
+
+// appended to a line when the result was flagged for what the line is about
+fn indicator(flagged: bool, indicator: &str) -> &str {
+    return if flagged { indicator } else { "" };
+}
+
+fn log_gpu_telemetry(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "GPU telemetry during each encode (min/avg/max):",
+        |result| result.gpu_telemetry.map(|telemetry| telemetry.to_string()),
+    );
+}
+
+fn log_system_load(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "System load during each encode (min/avg/max):",
+        |result| {
+            result.system_load.map(|load| {
+                format!(
+                    "{}{}",
+                    load.to_string(),
+                    indicator(result.is_contaminated(), " (background load)")
+                )
+            })
+        },
+    );
+}
+
+fn log_trial_stats(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "FPS across trials (mean ± 95% confidence interval):",
+        |result| {
+            result.trial_stats.as_ref().map(|stats| {
+                format!(
+                    "{}{}",
+                    stats.to_string(),
+                    indicator(result.is_too_variable(), " (too variable)")
+                )
+            })
+        },
+    );
+}
+
+fn log_read_throughput(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    let mut logged_sources: Vec<String> = Vec::new();
+
+    for result in results {
+        let throughput = match result.read_throughput {
+            Some(throughput) => throughput,
+            None => continue,
+        };
+
+        // sources of the same resolution and fps can still sit on different storage
+        if logged_sources.contains(&result.source_file) {
+            continue;
+        }
+
+        let bottleneck_indicator = if throughput.is_bottleneck() {
+            " (storage bottleneck)"
+        } else {
+            ""
+        };
+
+        writeln!(
+            w,
+            "Source read speed [{}] ({}@{}): {}{}",
+            result.source_file,
+            result.metadata.get_res(),
+            result.metadata.fps,
+            throughput.to_string(),
+            bottleneck_indicator
+        )?;
+        logged_sources.push(result.source_file.clone());
+    }
+
+    if !logged_sources.is_empty() {
+        writeln!(w)?;
+    }
+
+    return Ok(());
+}
+
+fn log_decode_checks(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "Frames each decoder got right, against a software decode:",
+        |result| {
+            result.decode_check.map(|check| {
+                format!(
+                    "{}{}",
+                    check.to_string(),
+                    indicator(result.decodes_wrong(), " (wrong frames, not ranked)")
+                )
+            })
+        },
+    );
+}
+
+fn log_settings_checks(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "What the encoder actually wrote for each permutation:",
+        |result| {
+            result.settings_check.as_ref().map(|check| {
+                let mut description = check.to_string();
+                for mismatch in &check.mismatches {
+                    description.push_str(format!("\n\tNot applied: {}", mismatch).as_str());
+                }
+
+                return description;
+            })
+        },
+    );
+}
+
+fn log_bitrate_conformance(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "Bitrate each permutation's output actually came out at:",
+        |result| {
+            result.bitrate_conformance.map(|conformance| {
+                format!(
+                    "{}{}",
+                    conformance.to_string(),
+                    indicator(result.is_nonconformant(), " (not conformant)")
+                )
+            })
+        },
+    );
+}
+
+fn log_platform_checks(w: &mut File, results: &Vec<PermutationResult>) -> io::Result<()> {
+    return log_section(
+        w,
+        results,
+        "Streaming platforms each permutation can be used on:",
+        |result| {
+            if result.platform_checks.is_empty() {
+                return None;
+            }
+
+            let violations: String = result
+                .platform_checks
+                .iter()
+                .filter(|check| !check.is_satisfied())
+                .map(|check| {
+                    format!(
+                        " | {} {}",
+                        check.platform,
+                        check.violation.as_deref().unwrap()
+                    )
+                })
+                .collect();
+
+            return Some(format!(
+                "{}{}",
+                result.get_satisfied_platforms(),
+                violations
+            ));
+        },
+    );
 }
@@ -0,0 +1,398 @@
+use std::collections::VecDeque;
+use std::ffi::c_float;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStderr, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use stoppable_thread::StoppableHandle;
+
+use environment::encoders::get_ffmpeg_hwaccels;
+use ffmpeg::args::FfmpegArgs;
+use ffmpeg::decoder::Decoder;
+use ffmpeg::encoded_stream::EncodedStream;
+use ffmpeg::error::{FrameHashError, ProbeError, VmafError};
+use ffmpeg::ffprobe::{probe_encoded_stream, probe_for_video_metadata, probe_packets};
+use ffmpeg::frame_hashes::hash_frames;
+use ffmpeg::metadata::MetaData;
+use ffmpeg::packet::Packet;
+use ffmpeg::report_files::extract_vmaf_score;
+
+use crate::error::EngineError;
+use crate::stat_tcp_listener::start_listening_to_ffmpeg_stats;
+
+// ffmpeg explains what went wrong at the end of its output, so only the tail is worth keeping
+const ERROR_OUTPUT_LINES: usize = 20;
+
+// how long progress can misbehave before we act on it
+#[derive(Copy, Clone)]
+pub struct ProgressLimits {
+    // time the encoder can stay below the target fps before it's considered overloaded
+    pub overload_time: Duration,
+    // time ffmpeg can go without reporting progress before it's considered to have errored
+    pub allowed_ffmpeg_downtime: Duration,
+}
+
+impl Default for ProgressLimits {
+    fn default() -> Self {
+        ProgressLimits {
+            overload_time: Duration::from_secs(5),
+            allowed_ffmpeg_downtime: Duration::from_secs(10),
+        }
+    }
+}
+
+// a running ffmpeg process, or a stand-in for one
+pub trait FfmpegProcess {
+    // the os' id for the process, None for a stand-in
+    fn id(&self) -> Option<u32>;
+
+    fn kill(&mut self);
+
+    // waits for the process to exit, returning whether it exited successfully
+    fn wait(&mut self) -> bool;
+
+    // stops the process, returning the last of what it wrote to stderr
+    fn error_output(&mut self) -> String;
+
+    // waits for the process to finish on its own, returning the last of what it wrote to stderr
+    fn finish(&mut self) -> String;
+}
+
+// a running vmaf calculation, which produces a score once the encode streaming into it is done
+pub trait VmafProcess {
+    fn wait_for_score(&mut self) -> Result<c_float, VmafError>;
+}
+
+// frame counts reported through an encode's -progress output
+pub trait ProgressFeed {
+    // the latest frame reported, and the frame reported right before it
+    fn frames(&self) -> (usize, usize);
+
+    fn stop(self: Box<Self>);
+}
+
+// everything the engines need from the outside world to run an encode
+// every ffmpeg spawned writes its stderr to the given log file, when there is one
+pub trait ProcessRunner {
+    // spawns an encode (or decode) along with the feed its progress is reported through
+    fn spawn_encode(
+        &self,
+        args: &FfmpegArgs,
+        verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<(Box<dyn FfmpegProcess>, Box<dyn ProgressFeed>), EngineError>;
+
+    // spawns the vmaf calculation that an encode will stream its output into
+    fn spawn_vmaf(
+        &self,
+        args: &FfmpegArgs,
+        verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<Box<dyn VmafProcess>, EngineError>;
+
+    fn probe(&self, video_file: &String) -> Result<MetaData, ProbeError>;
+
+    // sizes and timestamps of every packet an encode wrote to the given file
+    fn probe_packets(&self, video_file: &String) -> Result<Vec<Packet>, ProbeError>;
+
+    // what the encoder actually wrote to the given file: its profile, pixel format and frame types
+    fn probe_encoded_stream(&self, video_file: &String) -> Result<EncodedStream, ProbeError>;
+
+    // an md5 of every frame the given decoder produces from the file, in order
+    fn hash_frames(
+        &self,
+        video_file: &String,
+        decoder: &Decoder,
+    ) -> Result<Vec<String>, FrameHashError>;
+
+    // the hwaccels ffmpeg was built with, None when it couldn't be asked
+    fn hwaccels(&self) -> Option<Vec<String>>;
+
+    fn progress_limits(&self) -> ProgressLimits {
+        return ProgressLimits::default();
+    }
+
+    // how long ffmpeg is given to let go of a file it decoded before it's deleted
+    fn release_delay(&self) -> Duration {
+        return Duration::from_secs(5);
+    }
+}
+
+// the real thing; spawns ffmpeg/ffprobe from the user's path
+pub struct FfmpegRunner;
+
+impl ProcessRunner for FfmpegRunner {
+    fn spawn_encode(
+        &self,
+        args: &FfmpegArgs,
+        verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<(Box<dyn FfmpegProcess>, Box<dyn ProgressFeed>), EngineError> {
+        let child = spawn_ffmpeg_child(args, verbose).map_err(EngineError::Spawn)?;
+        if verbose {
+            println!("V: Successfully spawned encoding child");
+        }
+        let mut ffmpeg_child = FfmpegChild::new(child, log_file);
+
+        let frame = Arc::new(AtomicUsize::new(0));
+        let previous_frame = Arc::new(AtomicUsize::new(0));
+        let listener =
+            match start_listening_to_ffmpeg_stats(verbose, frame.clone(), previous_frame.clone()) {
+                Ok(listener) => listener,
+                // ffmpeg rejecting its arguments means it never connects, so this permutation failed
+                Err(EngineError::ProgressTimeout(seconds)) => {
+                    let mut error_output = ffmpeg_child.error_output();
+                    if error_output.is_empty() {
+                        error_output = EngineError::ProgressTimeout(seconds).to_string();
+                    }
+
+                    return Err(EngineError::EncodeFailed(error_output));
+                }
+                Err(e) => {
+                    // nothing will ever read this encode's progress, so don't leave it running
+                    ffmpeg_child.kill();
+                    return Err(e);
+                }
+            };
+
+        return Ok((
+            Box::new(ffmpeg_child),
+            Box::new(TcpProgressFeed {
+                listener,
+                frame,
+                previous_frame,
+            }),
+        ));
+    }
+
+    fn spawn_vmaf(
+        &self,
+        args: &FfmpegArgs,
+        verbose: bool,
+        log_file: Option<&Path>,
+    ) -> Result<Box<dyn VmafProcess>, EngineError> {
+        let child = spawn_ffmpeg_child(args, verbose).map_err(EngineError::Spawn)?;
+        return Ok(Box::new(FfmpegVmafChild {
+            child: FfmpegChild::new(child, log_file),
+        }));
+    }
+
+    fn probe(&self, video_file: &String) -> Result<MetaData, ProbeError> {
+        return probe_for_video_metadata(video_file);
+    }
+
+    fn probe_packets(&self, video_file: &String) -> Result<Vec<Packet>, ProbeError> {
+        return probe_packets(video_file);
+    }
+
+    fn probe_encoded_stream(&self, video_file: &String) -> Result<EncodedStream, ProbeError> {
+        return probe_encoded_stream(video_file);
+    }
+
+    fn hash_frames(
+        &self,
+        video_file: &String,
+        decoder: &Decoder,
+    ) -> Result<Vec<String>, FrameHashError> {
+        return hash_frames(video_file, decoder);
+    }
+
+    fn hwaccels(&self) -> Option<Vec<String>> {
+        return get_ffmpeg_hwaccels();
+    }
+}
+
+pub fn spawn_ffmpeg_child(ffmpeg_args: &FfmpegArgs, verbose: bool) -> io::Result<Child> {
+    // log the full ffmpeg command to be spawned
+    if verbose {
+        println!("V: ffmpeg args: [{}]", ffmpeg_args.to_string());
+        let mut cloned = ffmpeg_args.clone();
+        cloned.set_no_output_for_error();
+        println!(
+            "V: ffmpeg args no network calls (copy this and run locally, minus the quotes): [{}]",
+            cloned.to_string()
+        );
+    }
+
+    return Command::new("ffmpeg")
+        .args(ffmpeg_args.to_vec())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+}
+
+struct FfmpegChild {
+    child: Child,
+    error_reader: Option<JoinHandle<VecDeque<String>>>,
+}
+
+impl FfmpegChild {
+    fn new(mut child: Child, log_file: Option<&Path>) -> Self {
+        let log = log_file.and_then(open_log);
+        let error_reader = child
+            .stderr
+            .take()
+            .map(|stderr| read_error_output(stderr, log));
+        return Self {
+            child,
+            error_reader,
+        };
+    }
+}
+
+impl FfmpegProcess for FfmpegChild {
+    fn id(&self) -> Option<u32> {
+        return Some(self.child.id());
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+
+    fn wait(&mut self) -> bool {
+        return self
+            .child
+            .wait()
+            .map(|status| status.success())
+            .unwrap_or(false);
+    }
+
+    fn error_output(&mut self) -> String {
+        self.kill();
+        return self.finish();
+    }
+
+    fn finish(&mut self) -> String {
+        let _ = self.child.wait();
+
+        // stderr closes once ffmpeg is gone, so the reader is done by now
+        return match self.error_reader.take().map(|reader| reader.join()) {
+            Some(Ok(lines)) => Vec::from(lines).join("\n"),
+            _ => String::new(),
+        };
+    }
+}
+
+fn open_log(path: &Path) -> Option<File> {
+    return match File::create(path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            println!(
+                "Warning: unable to create ffmpeg log file [{}]: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    };
+}
+
+// keeps draining stderr so ffmpeg never blocks on a full pipe, writing all of it to the log
+// while holding on to just the last few lines
+fn read_error_output(stderr: ChildStderr, mut log: Option<File>) -> JoinHandle<VecDeque<String>> {
+    return thread::spawn(move || {
+        let mut lines: VecDeque<String> = VecDeque::new();
+
+        // ffmpeg redraws its stats line with carriage returns rather than new lines
+        for chunk in BufReader::new(stderr).split(b'\r').map_while(Result::ok) {
+            for line in String::from_utf8_lossy(&chunk).lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                // a log that can't be written to shouldn't stop the encode
+                if let Some(file) = log.as_mut() {
+                    if writeln!(file, "{}", line.trim_end()).is_err() {
+                        log = None;
+                    }
+                }
+
+                if lines.len() == ERROR_OUTPUT_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line.trim_end().to_string());
+            }
+        }
+
+        return lines;
+    });
+}
+
+struct FfmpegVmafChild {
+    child: FfmpegChild,
+}
+
+impl VmafProcess for FfmpegVmafChild {
+    fn wait_for_score(&mut self) -> Result<c_float, VmafError> {
+        let success = self.child.wait();
+        let error_output = self.child.error_output();
+
+        if !success {
+            return Err(VmafError::Failed(error_output));
+        }
+
+        return find_vmaf_score(&error_output);
+    }
+}
+
+// libvmaf logs the score as it finishes, so it's near the end of ffmpeg's output
+fn find_vmaf_score(output: &str) -> Result<c_float, VmafError> {
+    let score_line = output
+        .lines()
+        .rev()
+        .find(|line| line.contains("VMAF score"))
+        .ok_or(VmafError::ParseFailed(String::from(
+            output.lines().last().unwrap_or(""),
+        )))?;
+
+    return extract_vmaf_score(score_line)
+        .map_err(|_| VmafError::ParseFailed(score_line.to_string()));
+}
+
+struct TcpProgressFeed {
+    listener: StoppableHandle<()>,
+    frame: Arc<AtomicUsize>,
+    previous_frame: Arc<AtomicUsize>,
+}
+
+impl ProgressFeed for TcpProgressFeed {
+    fn frames(&self) -> (usize, usize) {
+        return (
+            self.frame.load(Ordering::Relaxed),
+            self.previous_frame.load(Ordering::Relaxed),
+        );
+    }
+
+    fn stop(self: Box<Self>) {
+        // kill the tcp reading thread
+        let _ = self.listener.stop().join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg::error::VmafError;
+
+    use crate::runner::find_vmaf_score;
+
+    #[test]
+    fn find_vmaf_score_test() {
+        let output = "frame=  240 fps=120 q=-0.0 Lsize=N/A time=00:00:04.00 bitrate=N/A speed=   2x\n[Parsed_libvmaf_0 @ 00000169cf14fc00] VMAF score: 98.644730\n[out#0/null @ 0x55d5] video:112kB audio:0kB";
+        assert_eq!(find_vmaf_score(output).unwrap(), 98.64473);
+    }
+
+    #[test]
+    fn find_missing_vmaf_score_test() {
+        assert!(matches!(
+            find_vmaf_score("Conversion failed!"),
+            Err(VmafError::ParseFailed(line)) if line == "Conversion failed!"
+        ));
+    }
+}
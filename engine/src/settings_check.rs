@@ -0,0 +1,301 @@
+use ffmpeg::encoded_stream::EncodedStream;
+
+// what the permutation asked the encoder for, to hold its output against
+pub struct RequestedSettings<'a> {
+    pub encoder_settings: &'a str,
+    pub ten_bit: bool,
+    pub no_b_frame: bool,
+    pub gop_frames: Option<u32>,
+}
+
+// whether the encoder actually applied the settings it was given; drivers are known to quietly ignore some
+#[derive(Clone, Debug, PartialEq)]
+pub struct SettingsCheck {
+    pub stream: EncodedStream,
+    // i.e. 'asked for the high10 profile, got High'
+    pub mismatches: Vec<String>,
+}
+
+impl SettingsCheck {
+    pub fn run(stream: EncodedStream, requested: &RequestedSettings) -> Self {
+        let mut mismatches = Vec::new();
+
+        if let Some(profile) = get_expected_profile(requested, &stream.codec) {
+            if normalize_profile(&profile) != normalize_profile(&stream.profile) {
+                mismatches.push(format!(
+                    "asked for the {} profile, got {}",
+                    profile,
+                    describe(&stream.profile)
+                ));
+            }
+        }
+
+        if let Some(level) = get_option(requested.encoder_settings, "-level") {
+            let expected = get_probed_level(level, &stream.codec);
+            if expected.is_some() && expected != stream.level {
+                mismatches.push(format!(
+                    "asked for level {}, got {}",
+                    level,
+                    stream
+                        .level
+                        .map(|level| level.to_string())
+                        .unwrap_or(String::from("none"))
+                ));
+            }
+        }
+
+        // prores is never less than 10-bit
+        if stream.codec != "prores" && requested.ten_bit != stream.is_ten_bit() {
+            let depth = if requested.ten_bit { "10-bit" } else { "8-bit" };
+            mismatches.push(format!(
+                "asked for {}, got {}",
+                depth,
+                describe(&stream.pix_fmt)
+            ));
+        }
+
+        let no_b_frames = requested.no_b_frame || requested.encoder_settings.contains("-bf 0");
+        if no_b_frames && stream.b_frames > 0 {
+            mismatches.push(format!("asked for no b frames, got {}", stream.b_frames));
+        }
+
+        if let Some(gop_frames) = requested.gop_frames {
+            match stream.keyframe_interval {
+                Some(interval) if interval != gop_frames => mismatches.push(format!(
+                    "asked for a keyframe every {} frames, got up to {} apart",
+                    gop_frames, interval
+                )),
+                // the video ends before a second keyframe was due
+                None if stream.get_frames() > gop_frames as u64 => mismatches.push(format!(
+                    "asked for a keyframe every {} frames, got only the first",
+                    gop_frames
+                )),
+                _ => {}
+            }
+        }
+
+        return Self { stream, mismatches };
+    }
+
+    pub fn is_applied(&self) -> bool {
+        return self.mismatches.is_empty();
+    }
+
+    // i.e. 'h264 High level 42 yuv420p, 2/238/0 I/P/B frames, keyframe every 120 frames'
+    pub fn to_string(&self) -> String {
+        let mut output = self.stream.codec.clone();
+        if !self.stream.profile.is_empty() {
+            output.push_str(format!(" {}", self.stream.profile).as_str());
+        }
+        if let Some(level) = self.stream.level {
+            output.push_str(format!(" level {}", level).as_str());
+        }
+
+        output.push_str(
+            format!(
+                " {}, {}/{}/{} I/P/B frames",
+                self.stream.pix_fmt,
+                self.stream.i_frames,
+                self.stream.p_frames,
+                self.stream.b_frames
+            )
+            .as_str(),
+        );
+
+        match self.stream.keyframe_interval {
+            Some(interval) => {
+                output.push_str(format!(", keyframe every {} frames", interval).as_str())
+            }
+            None => output.push_str(", a single keyframe"),
+        }
+
+        return output;
+    }
+}
+
+// 10-bit h264/hevc come out in their 10-bit profile, even when the settings only name the 8-bit one
+fn get_expected_profile(requested: &RequestedSettings, codec: &str) -> Option<String> {
+    let profile = get_option(requested.encoder_settings, "-profile:v")?;
+    // the encoder picks one itself
+    if profile == "auto" {
+        return None;
+    }
+
+    let has_ten_bit_profile = codec == "h264" || codec == "hevc";
+    if requested.ten_bit && has_ten_bit_profile && !profile.ends_with("10") {
+        return Some(format!("{}10", profile));
+    }
+
+    return Some(profile.to_string());
+}
+
+// 'Constrained Baseline', 'constrained_baseline' and 'baseline' are all the same profile as far as we're concerned;
+// encoders set the constraint flags on their own
+// nvenc's 'high444p' is what ffprobe calls 'High 4:4:4 Predictive'
+fn normalize_profile(profile: &str) -> String {
+    let normalized = profile
+        .to_lowercase()
+        .replace([' ', '_', ':'], "")
+        .replace("predictive", "p");
+    return normalized
+        .strip_prefix("constrained")
+        .unwrap_or(normalized.as_str())
+        .to_string();
+}
+
+// h264 levels are probed as ten times the level, hevc's as thirty times it, i.e. 4.1 is 41 and 123
+fn get_probed_level(level: &str, codec: &str) -> Option<i64> {
+    let level = level.parse::<f64>().ok()?;
+    return match codec {
+        "h264" => Some((level * 10.0).round() as i64),
+        "hevc" => Some((level * 30.0).round() as i64),
+        _ => None,
+    };
+}
+
+fn get_option<'a>(encoder_settings: &'a str, option: &str) -> Option<&'a str> {
+    let mut args = encoder_settings.split_whitespace();
+    args.find(|arg| *arg == option)?;
+    return args.next();
+}
+
+fn describe(value: &str) -> &str {
+    if value.is_empty() {
+        return "none";
+    }
+
+    return value;
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg::encoded_stream::EncodedStream;
+
+    use crate::settings_check::{RequestedSettings, SettingsCheck};
+
+    fn get_stream() -> EncodedStream {
+        return EncodedStream {
+            codec: String::from("h264"),
+            profile: String::from("High"),
+            level: Some(42),
+            pix_fmt: String::from("yuv420p"),
+            i_frames: 2,
+            p_frames: 238,
+            b_frames: 0,
+            keyframe_interval: Some(120),
+        };
+    }
+
+    fn get_requested(encoder_settings: &str) -> RequestedSettings<'_> {
+        return RequestedSettings {
+            encoder_settings,
+            ten_bit: false,
+            no_b_frame: false,
+            gop_frames: Some(120),
+        };
+    }
+
+    #[test]
+    fn applied_test() {
+        let check = SettingsCheck::run(
+            get_stream(),
+            &get_requested("-preset p1 -profile:v high -level 4.2 -bf 0"),
+        );
+
+        assert!(check.is_applied());
+        assert_eq!(
+            check.to_string(),
+            "h264 High level 42 yuv420p, 2/238/0 I/P/B frames, keyframe every 120 frames"
+        );
+    }
+
+    #[test]
+    fn profile_test() {
+        let mut stream = get_stream();
+        stream.profile = String::from("Constrained Baseline");
+        assert!(
+            SettingsCheck::run(stream.clone(), &get_requested("-profile:v baseline")).is_applied()
+        );
+        assert!(SettingsCheck::run(
+            stream.clone(),
+            &get_requested("-profile:v constrained_baseline")
+        )
+        .is_applied());
+
+        let check = SettingsCheck::run(stream.clone(), &get_requested("-profile:v high"));
+        assert_eq!(
+            check.mismatches,
+            vec!["asked for the high profile, got Constrained Baseline"]
+        );
+
+        stream.profile = String::from("High 4:4:4 Predictive");
+        assert!(SettingsCheck::run(stream, &get_requested("-profile:v high444p")).is_applied());
+    }
+
+    #[test]
+    fn ten_bit_test() {
+        let mut requested = get_requested("-profile:v main");
+        requested.ten_bit = true;
+        let mut stream = get_stream();
+        stream.codec = String::from("hevc");
+        stream.level = None;
+        stream.profile = String::from("Main 10");
+        stream.pix_fmt = String::from("yuv420p10le");
+        assert!(SettingsCheck::run(stream.clone(), &requested).is_applied());
+
+        // the driver ignoring -pix_fmt leaves an 8-bit stream behind
+        stream.profile = String::from("Main");
+        stream.pix_fmt = String::from("yuv420p");
+        let check = SettingsCheck::run(stream, &requested);
+        assert_eq!(
+            check.mismatches,
+            vec![
+                "asked for the main10 profile, got Main",
+                "asked for 10-bit, got yuv420p"
+            ]
+        );
+    }
+
+    #[test]
+    fn b_frames_test() {
+        let mut stream = get_stream();
+        stream.b_frames = 57;
+        stream.p_frames = 181;
+
+        let mut requested = get_requested("-preset p1 -b_ref_mode 0");
+        assert!(SettingsCheck::run(stream.clone(), &requested).is_applied());
+
+        requested.no_b_frame = true;
+        let check = SettingsCheck::run(stream, &requested);
+        assert_eq!(check.mismatches, vec!["asked for no b frames, got 57"]);
+    }
+
+    #[test]
+    fn gop_test() {
+        let mut stream = get_stream();
+        stream.keyframe_interval = Some(250);
+        let check = SettingsCheck::run(stream.clone(), &get_requested(""));
+        assert_eq!(
+            check.mismatches,
+            vec!["asked for a keyframe every 120 frames, got up to 250 apart"]
+        );
+
+        stream.keyframe_interval = None;
+        let check = SettingsCheck::run(stream.clone(), &get_requested(""));
+        assert_eq!(
+            check.mismatches,
+            vec!["asked for a keyframe every 120 frames, got only the first"]
+        );
+
+        // nothing was asked for
+        let mut requested = get_requested("");
+        requested.gop_frames = None;
+        assert!(SettingsCheck::run(stream, &requested).is_applied());
+    }
+
+    #[test]
+    fn level_test() {
+        let check = SettingsCheck::run(get_stream(), &get_requested("-level 4.1"));
+        assert_eq!(check.mismatches, vec!["asked for level 4.1, got 42"]);
+    }
+}
@@ -0,0 +1,56 @@
+use environment::memory::{remove_staged, stage_in_memory};
+use permutation::permutation::Permutation;
+
+use crate::error::EngineError;
+
+// keeps at most one source staged in memory at a time, swapping it out when the permutations move on to the next source
+pub struct SourceStager {
+    // (original source, staged copy)
+    staged: Option<(String, String)>,
+}
+
+impl SourceStager {
+    pub fn new() -> Self {
+        return Self { staged: None };
+    }
+
+    // points the permutation at the in-memory copy of its source, staging it first if needed
+    pub fn prepare(&mut self, permutation: &mut Permutation) -> Result<(), EngineError> {
+        if !permutation.stage_in_memory {
+            return Ok(());
+        }
+
+        let is_already_staged = self
+            .staged
+            .as_ref()
+            .map(|(original, _)| *original == permutation.video_file)
+            .unwrap_or(false);
+
+        if !is_already_staged {
+            self.cleanup();
+
+            println!(
+                "Staging [{}] in memory, this may take a moment...",
+                permutation.video_file
+            );
+            let staged = stage_in_memory(&permutation.video_file)?;
+            if permutation.verbose {
+                println!("V: Staged source at: {}", staged);
+            }
+
+            self.staged = Some((permutation.video_file.clone(), staged));
+        }
+
+        if let Some((_, staged)) = &self.staged {
+            permutation.video_file = staged.clone();
+        }
+
+        return Ok(());
+    }
+
+    pub fn cleanup(&mut self) {
+        if let Some((_, staged)) = self.staged.take() {
+            remove_staged(&staged);
+        }
+    }
+}
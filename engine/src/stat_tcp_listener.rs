@@ -2,27 +2,30 @@ use std::io::{BufRead, BufReader};
 use std::net::{TcpListener, TcpStream};
 use std::num::ParseIntError;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
 use stoppable_thread::StoppableHandle;
 
-use cli::cli_util::error_with_ack;
 use ffmpeg::report_files::capture_group;
 
+use crate::error::EngineError;
+
 static LOCALHOST: &str = "localhost";
 static PORT: &str = "1234";
 
 pub fn start_listening_to_ffmpeg_stats(
     verbose: bool,
-    frame: &'static AtomicUsize,
-    previous_frame: &'static AtomicUsize,
-) -> StoppableHandle<()> {
-    let stat_listener = TcpListener::bind(format!("{}:{}", LOCALHOST, PORT)).unwrap();
+    frame: Arc<AtomicUsize>,
+    previous_frame: Arc<AtomicUsize>,
+) -> Result<StoppableHandle<()>, EngineError> {
+    let stat_listener = TcpListener::bind(format!("{}:{}", LOCALHOST, PORT))
+        .map_err(EngineError::ProgressListener)?;
     // important so that this thread doesn't just hang here
     stat_listener
         .set_nonblocking(true)
-        .expect("Unable to set non-blocking for tcp listener, listener might block...");
+        .map_err(EngineError::ProgressListener)?;
 
     let tcp_reading_thread;
 
@@ -31,8 +34,7 @@ pub fn start_listening_to_ffmpeg_stats(
 
     loop {
         if listen_start_time.elapsed().unwrap() > allowed_elapsed_time {
-            println!("Unable to connect to ffmpeg output for {} seconds, either ffmpeg didn't start correctly or the tcp connection: {}:{} could not be created...", allowed_elapsed_time.as_secs(), LOCALHOST, PORT);
-            error_with_ack(true);
+            return Err(EngineError::ProgressTimeout(allowed_elapsed_time.as_secs()));
         }
 
         // will try to connect for 10 seconds
@@ -43,8 +45,12 @@ pub fn start_listening_to_ffmpeg_stats(
                 }
 
                 // making received client non-blocking, otherwise it dies pretty quick
-                client.0.set_nonblocking(false).unwrap();
-                tcp_reading_thread = spawn_tcp_reading_thread(client.0, frame, previous_frame);
+                client
+                    .0
+                    .set_nonblocking(false)
+                    .map_err(EngineError::ProgressListener)?;
+                tcp_reading_thread =
+                    spawn_tcp_reading_thread(client.0, frame.clone(), previous_frame.clone());
                 break;
             }
             // probably log this error eventually
@@ -57,16 +63,20 @@ pub fn start_listening_to_ffmpeg_stats(
         }
     }
 
-    return tcp_reading_thread;
+    return Ok(tcp_reading_thread);
 }
 
 fn spawn_tcp_reading_thread(
     stream: TcpStream,
-    frame: &'static AtomicUsize,
-    previous_frame: &'static AtomicUsize,
+    frame: Arc<AtomicUsize>,
+    previous_frame: Arc<AtomicUsize>,
 ) -> StoppableHandle<()> {
     return stoppable_thread::spawn(move |stopped| {
-        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut reader = match stream.try_clone() {
+            Ok(cloned) => BufReader::new(cloned),
+            // without a reader no progress gets reported, which is caught as an ffmpeg error
+            Err(_) => return,
+        };
 
         let mut peek = [0u8];
         while stream.peek(&mut peek).is_ok() {
@@ -75,14 +85,15 @@ fn spawn_tcp_reading_thread(
             }
 
             let mut line = String::new();
-            reader.read_line(&mut line).unwrap();
+            if reader.read_line(&mut line).is_err() {
+                break;
+            }
 
             if is_frame_line(line.as_str()) {
-                previous_frame.store(frame.load(Ordering::Relaxed), Ordering::Relaxed);
-                frame.store(
-                    extract_frame(line.as_str()).unwrap() as usize,
-                    Ordering::Relaxed,
-                );
+                if let Ok(extracted) = extract_frame(line.as_str()) {
+                    previous_frame.store(frame.load(Ordering::Relaxed), Ordering::Relaxed);
+                    frame.store(extracted as usize, Ordering::Relaxed);
+                }
             }
         }
     });
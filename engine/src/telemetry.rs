@@ -0,0 +1,125 @@
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use stoppable_thread::StoppableHandle;
+
+use environment::load::{LoadSample, LoadSampler};
+use gpus::telemetry::{TelemetrySample, TelemetrySampler};
+
+// how often the sampling thread checks whether it should stop, so stopping it doesn't cost a whole period
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+// how long the cpu is watched before an encode starts, to see what else is running
+const BACKGROUND_WINDOW: Duration = Duration::from_millis(500);
+
+// the default % of cpu that can be busy before an encode for its measurement to still count as clean
+pub const DEFAULT_MAX_BACKGROUND_LOAD: f64 = 20.0;
+
+// shared between every encode of a run, each sampler is only ever used by one recording at a time
+pub type SharedSampler = Arc<Mutex<Box<dyn TelemetrySampler>>>;
+pub type SharedLoadSampler = Arc<Mutex<Box<dyn LoadSampler>>>;
+
+// what gets watched while each encode runs; nothing is sampled by default
+#[derive(Clone)]
+pub struct Monitoring {
+    pub gpu: Option<SharedSampler>,
+    pub load: Option<SharedLoadSampler>,
+    pub max_background_load: f64,
+    // run a permutation again when background load was present during it, once
+    pub rerun_contaminated: bool,
+}
+
+impl Default for Monitoring {
+    fn default() -> Self {
+        return Self {
+            gpu: None,
+            load: None,
+            max_background_load: DEFAULT_MAX_BACKGROUND_LOAD,
+            rerun_contaminated: false,
+        };
+    }
+}
+
+impl Monitoring {
+    pub fn set_gpu_sampler(&mut self, sampler: Box<dyn TelemetrySampler>) {
+        self.gpu = Some(Arc::new(Mutex::new(sampler)));
+    }
+
+    pub fn set_load_sampler(&mut self, sampler: Box<dyn LoadSampler>) {
+        self.load = Some(Arc::new(Mutex::new(sampler)));
+    }
+
+    pub fn record_gpu(&self, period: Duration) -> Option<Recording<TelemetrySample>> {
+        let sampler = self.gpu.clone()?;
+        return Some(start_recording(
+            Arc::new(move || sampler.lock().ok().map(|mut s| s.sample())),
+            period,
+        ));
+    }
+
+    pub fn record_load(&self, period: Duration) -> Option<Recording<LoadSample>> {
+        let sampler = self.load.clone()?;
+        return Some(start_recording(
+            Arc::new(move || sampler.lock().ok().map(|mut s| s.sample())),
+            period,
+        ));
+    }
+
+    // has the load samples tell the given encode's own cpu use apart from whatever else is running
+    pub fn watch_process(&self, pid: Option<u32>) {
+        if let Some(sampler) = &self.load {
+            if let Ok(mut sampler) = sampler.lock() {
+                sampler.watch_process(pid);
+            }
+        }
+    }
+
+    // cpu utilization over a short window with nothing of ours running yet
+    pub fn measure_background_load(&self) -> Option<LoadSample> {
+        let sampler = self.load.as_ref()?;
+        let mut sampler = sampler.lock().ok()?;
+        sampler.sample();
+        sleep(BACKGROUND_WINDOW);
+        return Some(sampler.sample());
+    }
+}
+
+// takes a sample; None when the sampler isn't usable anymore
+type SampleFn<T> = Arc<dyn Fn() -> Option<T> + Send + Sync>;
+
+pub struct Recording<T> {
+    sample: SampleFn<T>,
+    handle: StoppableHandle<Vec<T>>,
+}
+
+// samples right away, then every period until stopped
+fn start_recording<T: Send + 'static>(sample: SampleFn<T>, period: Duration) -> Recording<T> {
+    let thread_sample = sample.clone();
+    let handle = stoppable_thread::spawn(move |stopped| {
+        let mut samples = Vec::new();
+        let mut last_sample_time = SystemTime::now();
+        samples.extend(thread_sample());
+
+        while !stopped.get() {
+            sleep(STOP_CHECK_INTERVAL);
+            if last_sample_time.elapsed().unwrap_or(period) >= period {
+                last_sample_time = SystemTime::now();
+                samples.extend(thread_sample());
+            }
+        }
+
+        return samples;
+    });
+
+    return Recording { sample, handle };
+}
+
+impl<T> Recording<T> {
+    // takes one last sample, so even a very short encode has a reading from its start and its end
+    pub fn stop(self) -> Vec<T> {
+        let mut samples = self.handle.stop().join().unwrap_or_default();
+        samples.extend((self.sample)());
+        return samples;
+    }
+}
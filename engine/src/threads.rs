@@ -1,9 +1,17 @@
+use std::sync::Mutex;
+
 use crossbeam_channel::{bounded, select, Receiver};
 use ctrlc::Error;
 
 pub fn was_ctrl_c_received(ctrl_c_events: &Result<Receiver<()>, Error>) -> bool {
+    // without a handler there's no way to have received one
+    let receiver = match ctrl_c_events {
+        Ok(receiver) => receiver,
+        Err(_) => return false,
+    };
+
     select! {
-        recv(ctrl_c_events.as_ref().unwrap()) -> _ => {
+        recv(receiver) -> _ => {
             return true;
         },
         default() => {
@@ -12,19 +20,21 @@ pub fn was_ctrl_c_received(ctrl_c_events: &Result<Receiver<()>, Error>) -> bool
     }
 }
 
-pub fn exit_on_ctrl_c(ctrl_channel: &Result<Receiver<()>, Error>) {
-    if was_ctrl_c_received(&ctrl_channel) {
-        println!("Ctrl-C acknowledged, program exiting...");
-        std::process::exit(0);
-    }
-}
+// ctrl-c handlers can only be registered once per process, so every engine run shares the same channel
+static CTRL_CHANNEL: Mutex<Option<Receiver<()>>> = Mutex::new(None);
 
 pub fn setup_ctrl_channel() -> Result<Receiver<()>, Error> {
+    let mut ctrl_channel = CTRL_CHANNEL.lock().unwrap();
+    if ctrl_channel.is_some() {
+        return Ok(ctrl_channel.as_ref().unwrap().clone());
+    }
+
     let (sender, receiver) = bounded(100);
     ctrlc::set_handler(move || {
         println!("Received ctrl-c, exiting gracefully...");
         let _ = sender.send(());
     })?;
 
+    *ctrl_channel = Some(receiver.clone());
     Ok(receiver)
 }
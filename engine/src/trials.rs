@@ -0,0 +1,150 @@
+// a permutation whose average fps varies more than this % between trials can't be ranked against its neighbours
+pub const MAX_COEFFICIENT_OF_VARIATION: f64 = 3.0;
+
+// two-sided 95% critical values of the t-distribution, by degrees of freedom; 1.96 past the end of the table
+const T_CRITICAL_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+// how many times every encode is repeated
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Trials {
+    pub count: u32,
+    // run once more up front and throw the result away, so clocks and caches are warm for the measured trials
+    pub warmup: bool,
+}
+
+impl Default for Trials {
+    fn default() -> Self {
+        return Self {
+            count: 1,
+            warmup: false,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SampleStats {
+    pub mean: f64,
+    // sample standard deviation, 0 for a single value
+    pub std_dev: f64,
+    // half the width of the 95% confidence interval of the mean
+    pub ci95: f64,
+}
+
+impl SampleStats {
+    pub fn from_values(values: &Vec<f64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        if values.len() == 1 {
+            return Self {
+                mean,
+                std_dev: 0.0,
+                ci95: 0.0,
+            };
+        }
+
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let std_dev = variance.sqrt();
+        let t = T_CRITICAL_95.get(values.len() - 2).cloned().unwrap_or(1.96);
+
+        return Self {
+            mean,
+            std_dev,
+            ci95: t * std_dev / n.sqrt(),
+        };
+    }
+
+    // standard deviation as a % of the mean
+    pub fn coefficient_of_variation(&self) -> f64 {
+        if self.mean == 0.0 {
+            return 0.0;
+        }
+
+        return self.std_dev / self.mean * 100.0;
+    }
+
+    // i.e. '412.3 ± 4.1 (sd 3.3)'
+    pub fn to_string(&self) -> String {
+        return format!(
+            "{:.1} ± {:.1} (sd {:.1})",
+            self.mean, self.ci95, self.std_dev
+        );
+    }
+}
+
+// how the measured trials of one permutation compare to each other
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrialStats {
+    pub trials: usize,
+    pub avg_fps: SampleStats,
+    pub one_perc_low: SampleStats,
+}
+
+impl TrialStats {
+    pub fn from_trials(avg_fps: &Vec<f64>, one_perc_lows: &Vec<f64>) -> Self {
+        return Self {
+            trials: avg_fps.len(),
+            avg_fps: SampleStats::from_values(avg_fps),
+            one_perc_low: SampleStats::from_values(one_perc_lows),
+        };
+    }
+
+    pub fn is_too_variable(&self) -> bool {
+        return self.avg_fps.coefficient_of_variation() > MAX_COEFFICIENT_OF_VARIATION;
+    }
+
+    pub fn to_string(&self) -> String {
+        return format!(
+            "{} trials, Average FPS {} | 1%'ile {}",
+            self.trials,
+            self.avg_fps.to_string(),
+            self.one_perc_low.to_string()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trials::{SampleStats, TrialStats};
+
+    #[test]
+    fn single_value_test() {
+        let stats = SampleStats::from_values(&vec![400.0]);
+        assert_eq!(stats.mean, 400.0);
+        assert_eq!(stats.std_dev, 0.0);
+        assert_eq!(stats.ci95, 0.0);
+    }
+
+    #[test]
+    fn sample_stats_test() {
+        let stats = SampleStats::from_values(&vec![400.0, 410.0, 420.0]);
+        assert_eq!(stats.mean, 410.0);
+        assert_eq!(stats.std_dev, 10.0);
+        // t for 2 degrees of freedom
+        assert!((stats.ci95 - 4.303 * 10.0 / 3.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(stats.to_string(), "410.0 ± 24.8 (sd 10.0)");
+    }
+
+    #[test]
+    fn large_sample_uses_normal_distribution_test() {
+        let values: Vec<f64> = (0..40).map(|i| (i % 2) as f64 * 2.0).collect();
+        let stats = SampleStats::from_values(&values);
+        assert!((stats.ci95 - 1.96 * stats.std_dev / 40.0_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn too_variable_test() {
+        let steady = TrialStats::from_trials(&vec![400.0, 402.0, 398.0], &vec![350.0; 3]);
+        assert!(!steady.is_too_variable());
+
+        let noisy = TrialStats::from_trials(&vec![400.0, 440.0, 380.0], &vec![350.0; 3]);
+        assert!(noisy.is_too_variable());
+    }
+}
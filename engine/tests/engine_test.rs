@@ -0,0 +1,1094 @@
+use std::fs;
+use std::path::PathBuf;
+
+use codecs::gop::Gop;
+use codecs::rate_control::RateControl;
+use engine::benchmark_engine::BenchmarkEngine;
+use engine::error::EngineError;
+use engine::fake_runner::{
+    FakeProgress, FakeProgressFeed, FakeRunner, FAKE_ERROR_OUTPUT, FAKE_VIDEO_KIBIBYTES,
+};
+use engine::ffmpeg_logs::LogRetention;
+use engine::permutation_engine::PermutationEngine;
+use engine::progressbar::watch_encode_progress;
+use engine::result::{log_results_to_file, PermutationResult};
+use engine::runner::ProcessRunner;
+use engine::threads::setup_ctrl_channel;
+use environment::disk::ReadThroughput;
+use environment::fingerprint::HostFingerprint;
+use environment::load::{FakeLoadSampler, LoadSample};
+use ffmpeg::decoder::Decoder;
+use ffmpeg::encoded_stream::EncodedStream;
+use ffmpeg::frame_rate::FrameRate;
+use ffmpeg::metadata::MetaData;
+use gpus::telemetry::{FakeSampler, TelemetrySample};
+use permutation::permutation::Permutation;
+use permutation::platform::{load_platform_profiles, PlatformProfile};
+
+static ENCODER: &str = "h264_nvenc";
+static SOURCE: &str = "fake-1080-60.y4m";
+static STATS_PERIOD: f32 = 0.5;
+
+fn get_metadata() -> MetaData {
+    return MetaData {
+        fps: FrameRate::from(60),
+        frames: 240,
+        width: 1920,
+        height: 1080,
+    };
+}
+
+fn get_log_directory(test_name: &str) -> String {
+    let dir: PathBuf = std::env::temp_dir().join(format!("encoder-benchmark-{}", test_name));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fs::create_dir_all(&dir).unwrap();
+    return dir.to_str().unwrap().to_string();
+}
+
+fn get_ffmpeg_logs(log_directory: &String) -> Vec<String> {
+    let mut logs: Vec<String> = fs::read_dir(log_directory)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("ffmpeg-perm-"))
+        .collect();
+    logs.sort();
+    return logs;
+}
+
+fn get_permutation(settings: &str, bitrate: u32, check_quality: bool) -> Permutation {
+    let mut permutation = Permutation::new(String::from(SOURCE), String::from(ENCODER));
+    permutation.encoder_settings = String::from(settings);
+    permutation.bitrate = bitrate;
+    permutation.check_quality = check_quality;
+    return permutation;
+}
+
+#[test]
+fn benchmark_engine_run_test() {
+    let log_directory = get_log_directory("benchmark-run");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1", 20, false));
+    engine.add(get_permutation("-preset p2", 20, false));
+
+    engine.run().unwrap();
+
+    let results = engine.get_results();
+    assert_eq!(results.len(), 2);
+    assert_eq!(runner.get_encode_count(), 2);
+    for result in results {
+        assert!(!result.was_overloaded);
+        assert!(result.fps_stats.avg > 0);
+    }
+
+    assert!(PathBuf::from(&log_directory)
+        .join(format!("{}-benchmark.log", ENCODER))
+        .exists());
+}
+
+#[test]
+fn decode_only_test() {
+    let log_directory = get_log_directory("decode-only");
+    let file = PathBuf::from(&log_directory).join("gameplay-hevc.mp4");
+    // 1MB over the 4 seconds of the fake source is 2Mb/s
+    fs::write(&file, vec![0u8; 1_000_000]).unwrap();
+    let file = file.to_str().unwrap().to_string();
+
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_trials(2, false);
+    for decoder in [Decoder::Software, Decoder::Hwaccel(String::from("cuda"))] {
+        let mut permutation = Permutation::new(file.clone(), String::from("decode"));
+        permutation.decode_run = true;
+        permutation.decode_only = true;
+        permutation.decoder = Some(decoder);
+        engine.add(permutation);
+    }
+
+    engine.run().unwrap();
+
+    // the files are decoded as they are, with nothing but the decoder in front of them
+    let encode_args = runner.get_encode_args();
+    assert!(encode_args[0]
+        .to_string()
+        .contains(format!("-y -i {} -f null -", file).as_str()));
+    assert!(encode_args[2]
+        .to_string()
+        .contains(format!("-y -hwaccel cuda -i {} -f null -", file).as_str()));
+
+    let results = engine.get_results();
+    assert_eq!(
+        results[1].decoder,
+        Some(Decoder::Hwaccel(String::from("cuda")))
+    );
+    assert!((results[1].achieved_bitrate.unwrap() - 2.0).abs() < 0.001);
+
+    // and left where they were
+    assert!(PathBuf::from(&file).exists());
+    let log =
+        fs::read_to_string(PathBuf::from(&log_directory).join("decode-benchmark.log")).unwrap();
+    assert!(log.contains("(Decode: cuda) gameplay-hevc.mp4"));
+    assert!(log.contains("(Decode: software) gameplay-hevc.mp4"));
+    // and listed by what they decoded rather than a bitrate they weren't encoded at
+    assert!(log.contains("[(Decode: cuda) gameplay-hevc.mp4]: 2 trials"));
+}
+
+#[test]
+fn hwaccel_fallback_test() {
+    let log_directory = get_log_directory("hwaccel-fallback");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    // an ffmpeg built without any of nvidia's hwaccels
+    runner.set_hwaccels(vec![String::from("vaapi")]);
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    let mut permutation = get_permutation("-preset p1", 20, false);
+    permutation.is_decoding = true;
+    engine.add(permutation.clone());
+    permutation.decode_run = true;
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    // the encode's output is decoded on the cpu, rather than with a hwaccel that isn't there
+    let encode_args = runner.get_encode_args();
+    assert!(encode_args[1]
+        .to_string()
+        .contains(format!("-y -i {}", SOURCE.replace(".y4m", ".mp4")).as_str()));
+
+    let results = engine.get_results();
+    assert_eq!(results[0].decoder, None);
+    assert_eq!(results[1].decoder, Some(Decoder::Software));
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("(Decode: software)"));
+}
+
+#[test]
+fn verify_decode_test() {
+    let log_directory = get_log_directory("verify-decode");
+    let file = PathBuf::from(&log_directory).join("gameplay-av1.ivf");
+    fs::write(&file, vec![0u8; 1_000_000]).unwrap();
+    let file = file.to_str().unwrap().to_string();
+
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    for decoder in [
+        Decoder::Software,
+        Decoder::Hwaccel(String::from("cuda")),
+        Decoder::Hwaccel(String::from("vaapi")),
+    ] {
+        let mut permutation = Permutation::new(file.clone(), String::from("decode"));
+        permutation.decode_run = true;
+        permutation.decode_only = true;
+        permutation.decoder = Some(decoder);
+        permutation.verify_decode = true;
+        engine.add(permutation);
+    }
+
+    // cuda is hashed against software first, then vaapi is, which gets the last 40 frames wrong
+    let reference: Vec<String> = (0..240).map(|frame| format!("{:032x}", frame)).collect();
+    let mut wrong = reference.clone();
+    for hash in wrong.iter_mut().skip(200) {
+        *hash = String::from("0");
+    }
+    runner.queue_frame_hashes(reference.clone());
+    runner.queue_frame_hashes(reference.clone());
+    runner.queue_frame_hashes(reference);
+    runner.queue_frame_hashes(wrong);
+
+    engine.run().unwrap();
+
+    let results = engine.get_results();
+    // software is what the others are held against
+    assert_eq!(results[0].decode_check, None);
+    assert!(!results[1].decodes_wrong());
+    assert!(results[2].decodes_wrong());
+    assert_eq!(results[2].decode_check.unwrap().mismatching_frames, 40);
+
+    let log =
+        fs::read_to_string(PathBuf::from(&log_directory).join("decode-benchmark.log")).unwrap();
+    assert!(log.contains("[W]"));
+    assert!(
+        log.contains("[(Decode: cuda) gameplay-av1.ivf]: all 240 frames match a software decode\n")
+    );
+    assert!(log.contains("[(Decode: vaapi) gameplay-av1.ivf]: 40 of 240 frames differ from a software decode, from frame 200 (wrong frames, not ranked)"));
+}
+
+#[test]
+fn permutation_engine_run_test() {
+    let log_directory = get_log_directory("permutation-run");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1", 10, false));
+    engine.add(get_permutation("-preset p2", 10, false));
+    engine.add(get_permutation("-preset p1", 15, false));
+
+    engine.run().unwrap();
+
+    assert_eq!(engine.get_results().len(), 3);
+    assert_eq!(runner.get_encode_count(), 3);
+    assert_eq!(runner.get_vmaf_count(), 0);
+    assert!(PathBuf::from(&log_directory)
+        .join(format!("{}-1920x1080-60.log", ENCODER))
+        .exists());
+}
+
+#[test]
+fn quality_targeted_rate_control_test() {
+    let log_directory = get_log_directory("quality-targeted");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1 -rc cbr -cbr true", 10, false));
+    let mut permutation = get_permutation("-preset p1 -rc constqp -qp 23", 0, false);
+    permutation.rate_control = RateControl::Cqp(23);
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    // cbr keeps its bitrate, cqp doesn't get one at all
+    let encode_args = runner.get_encode_args();
+    assert!(encode_args[0].to_string().contains("-b:v 10M"));
+    assert!(!encode_args[1].to_string().contains("-b:v"));
+
+    // the fake source is 4 seconds long
+    let results = engine.get_results();
+    assert_eq!(results[0].achieved_bitrate, None);
+    let expected = (FAKE_VIDEO_KIBIBYTES * 1024 * 8) as f64 / 4.0 / 1_000_000.0;
+    assert!((results[1].achieved_bitrate.unwrap() - expected).abs() < 1e-9);
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-1920x1080-60.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains(&format!("60\t{:.1}Mb/s", expected)));
+}
+
+#[test]
+fn gop_test() {
+    let log_directory = get_log_directory("gop");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1 -rc cbr", 10, false));
+    let mut permutation = get_permutation("-preset p1 -rc cbr", 10, false);
+    permutation.gop = Some(Gop::new(2.0));
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    // the encoder's own interval is kept when none was given, the 60fps source gets 120 frames otherwise
+    let encode_args = runner.get_encode_args();
+    assert!(!encode_args[0].to_string().contains("-g "));
+    assert!(encode_args[1]
+        .to_string()
+        .contains("-g 120 -keyint_min 120"));
+    assert_eq!(engine.get_results()[1].gop, Some(Gop::new(2.0)));
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-1920x1080-60.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("GOP: 2s (120 frames)"));
+}
+
+#[test]
+fn check_bitrate_test() {
+    let log_directory = get_log_directory("check-bitrate");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    for bitrate in [2, 5] {
+        let mut permutation = get_permutation("-preset p1 -rc cbr", bitrate, false);
+        permutation.check_bitrate = true;
+        engine.add(permutation);
+    }
+
+    engine.run().unwrap();
+
+    // the output is written next to the source so its packets can be read back
+    let encode_args = runner.get_encode_args();
+    assert!(encode_args[0]
+        .to_string()
+        .ends_with("fake-1080-60-encoded.mkv"));
+
+    // the fake output comes out at ~4.2Mb/s, more than double what the first one asked for
+    let results = engine.get_results();
+    let overshot = results[0].bitrate_conformance.unwrap();
+    assert!((overshot.average - 4.194).abs() < 0.001);
+    assert!(overshot.vbv.unwrap().has_overflowed());
+    assert!(results[0].is_nonconformant());
+    assert!(!results[1].is_nonconformant());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-1920x1080-60.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("[C]1920x1080"));
+    assert!(log.contains("Bitrate each permutation's output actually came out at:"));
+    assert!(log.contains("(not conformant)"));
+}
+
+#[test]
+fn verify_settings_test() {
+    let log_directory = get_log_directory("verify-settings");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    for preset in ["p1", "p2"] {
+        let mut permutation = get_permutation(
+            format!("-preset {} -profile:v high -rc cbr -b_ref_mode 0", preset).as_str(),
+            10,
+            false,
+        );
+        permutation.gop = Some(Gop::new(2.0));
+        permutation.no_b_frame = true;
+        permutation.verify_settings = true;
+        engine.add(permutation);
+    }
+
+    // the first encode does what it was asked, the second ignores its profile and keeps its b frames
+    runner.queue_encoded_stream(EncodedStream {
+        codec: String::from("h264"),
+        profile: String::from("high"),
+        level: None,
+        pix_fmt: String::from("yuv420p"),
+        i_frames: 2,
+        p_frames: 238,
+        b_frames: 0,
+        keyframe_interval: Some(120),
+    });
+    runner.queue_encoded_stream(EncodedStream {
+        codec: String::from("h264"),
+        profile: String::from("Main"),
+        level: None,
+        pix_fmt: String::from("yuv420p"),
+        i_frames: 2,
+        p_frames: 160,
+        b_frames: 78,
+        keyframe_interval: Some(120),
+    });
+
+    engine.run().unwrap();
+
+    let results = engine.get_results();
+    assert!(!results[0].has_unapplied_settings());
+    assert_eq!(
+        results[1].settings_check.as_ref().unwrap().mismatches,
+        vec![
+            "asked for the high profile, got Main",
+            "asked for no b frames, got 78"
+        ]
+    );
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-1920x1080-60.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("[S]1920x1080"));
+    assert!(log.contains("Not applied: asked for no b frames, got 78"));
+}
+
+#[test]
+fn verify_settings_honored_test() {
+    let log_directory = get_log_directory("verify-settings-honored");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    let mut permutation = get_permutation("-preset p1 -profile:v high -rc cbr", 10, false);
+    permutation.ten_bit = true;
+    permutation.gop = Some(Gop::new(1.0));
+    permutation.verify_settings = true;
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    let check = engine.get_results()[0].settings_check.clone().unwrap();
+    assert!(check.is_applied());
+    assert_eq!(check.stream.keyframe_interval, Some(60));
+}
+
+fn get_twitch_profile() -> Vec<PlatformProfile> {
+    return load_platform_profiles(&vec![String::from("twitch")], "").unwrap();
+}
+
+fn get_streamable_permutation(bitrate: u32) -> Permutation {
+    let mut permutation = get_permutation("-preset p1 -profile:v high -rc cbr", bitrate, false);
+    permutation.gop = Some(Gop::new(2.0));
+    return permutation;
+}
+
+#[test]
+fn platform_checks_test() {
+    let log_directory = get_log_directory("platform-checks");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_platforms(get_twitch_profile(), false);
+    engine.add(get_streamable_permutation(6));
+    engine.add(get_streamable_permutation(10));
+
+    engine.run().unwrap();
+
+    // both still run, the one twitch won't take is only annotated
+    let results = engine.get_results();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].platform_checks[0].is_satisfied());
+    assert!(!results[1].platform_checks[0].is_satisfied());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-1920x1080-60.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("Streaming platforms each permutation can be used on:"));
+    assert!(log.contains("[6Mb/s] [GOP 2s] [-preset p1 -profile:v high -rc cbr]: Twitch\n"));
+    assert!(log.contains(
+        "[10Mb/s] [GOP 2s] [-preset p1 -profile:v high -rc cbr]: none | Twitch takes up to 6Mb/s at 1080p60"
+    ));
+}
+
+#[test]
+fn restrict_to_platform_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = PermutationEngine::with_runner(
+        get_log_directory("restrict-to-platform"),
+        Box::new(runner.clone()),
+    );
+    engine.set_platforms(get_twitch_profile(), true);
+    engine.add(get_streamable_permutation(6));
+    engine.add(get_streamable_permutation(10));
+
+    engine.run().unwrap();
+
+    assert_eq!(runner.get_encode_count(), 1);
+    assert_eq!(engine.get_results()[0].bitrate, 6);
+}
+
+#[test]
+fn restrict_to_platform_nothing_left_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = PermutationEngine::with_runner(
+        get_log_directory("restrict-to-platform-nothing-left"),
+        Box::new(runner.clone()),
+    );
+    engine.set_platforms(get_twitch_profile(), true);
+    engine.add(get_permutation(
+        "-preset p1 -profile:v high -rc cbr",
+        6,
+        false,
+    ));
+
+    // without a fixed keyframe interval, twitch won't take it
+    assert!(matches!(
+        engine.run(),
+        Err(EngineError::NoCompliantPermutations(_))
+    ));
+    assert_eq!(runner.get_encode_count(), 0);
+}
+
+#[test]
+fn host_fingerprint_logged_test() {
+    let log_directory = get_log_directory("host-fingerprint");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner));
+    engine.set_host_fingerprint(HostFingerprint {
+        cpu_model: String::from("AMD Ryzen 9 5950X 16-Core Processor"),
+        cpu_cores: 32,
+        ffmpeg_version: String::from("6.0"),
+        tool_version: String::from("0.6.1"),
+        ..HostFingerprint::default()
+    });
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.starts_with("Host:"));
+    assert!(log.contains("CPU: AMD Ryzen 9 5950X 16-Core Processor (32 cores)"));
+    assert!(log.contains("ffmpeg version: 6.0"));
+    assert!(log.contains("Tool version: 0.6.1"));
+}
+
+#[test]
+fn gpu_telemetry_recorded_test() {
+    let log_directory = get_log_directory("gpu-telemetry");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner));
+    let sample = |encoder_utilization: f64, power_watts: f64| TelemetrySample {
+        encoder_utilization: Some(encoder_utilization),
+        power_watts: Some(power_watts),
+        ..TelemetrySample::default()
+    };
+    engine.set_telemetry_sampler(Box::new(FakeSampler::new(vec![
+        sample(40.0, 100.0),
+        sample(60.0, 140.0),
+    ])));
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    // sampled at least at the start and the end of the encode
+    let telemetry = engine.get_results()[0].gpu_telemetry.unwrap();
+    assert!(telemetry.samples >= 2);
+    let encoder_utilization = telemetry.encoder_utilization.unwrap();
+    assert_eq!(encoder_utilization.min, 40.0);
+    assert_eq!(encoder_utilization.max, 60.0);
+    assert!(encoder_utilization.avg > 40.0 && encoder_utilization.avg <= 60.0);
+    assert_eq!(telemetry.power_watts.unwrap().max, 140.0);
+    assert_eq!(telemetry.temperature_c, None);
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("GPU telemetry during each encode (min/avg/max):"));
+    assert!(log.contains("[20Mb/s] [-preset p1]: Encoder 40/"));
+}
+
+fn get_load_sample(cpu_utilization: f64) -> LoadSample {
+    return LoadSample {
+        cpu_utilization: Some(cpu_utilization),
+        process_cpu_utilization: None,
+        avg_core_mhz: Some(4000.0),
+        max_core_mhz: Some(4800.0),
+        load_average: Some(1.5),
+    };
+}
+
+#[test]
+fn system_load_recorded_test() {
+    let log_directory = get_log_directory("system-load");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner));
+    // the first two samples are the background check before the encode
+    engine.set_load_sampler(Box::new(FakeLoadSampler::new(vec![
+        get_load_sample(0.0),
+        get_load_sample(5.0),
+        get_load_sample(60.0),
+    ])));
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    let result = &engine.get_results()[0];
+    let load = result.system_load.unwrap();
+    assert_eq!(load.background_cpu_utilization, Some(5.0));
+    assert_eq!(load.cpu_utilization.unwrap().max, 60.0);
+    assert_eq!(load.peak_core_mhz, Some(4800.0));
+    assert!(!result.is_contaminated());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("System load during each encode (min/avg/max):"));
+    assert!(!log.contains("(background load)"));
+}
+
+#[test]
+fn background_load_flagged_test() {
+    let log_directory = get_log_directory("background-load");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_load_sampler(Box::new(FakeLoadSampler::new(vec![
+        get_load_sample(0.0),
+        get_load_sample(45.0),
+    ])));
+    engine.set_background_load_check(20.0, false);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    assert!(engine.get_results()[0].is_contaminated());
+    assert_eq!(runner.get_encode_count(), 1);
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("[B]1920x1080"));
+    assert!(log.contains("Background 45% (background load)"));
+}
+
+#[test]
+fn background_load_during_encode_test() {
+    let log_directory = get_log_directory("background-load-during");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    // quiet before the encode, then something else takes 35% of the cpu next to the encode's 40%
+    let mut during = get_load_sample(75.0);
+    during.process_cpu_utilization = Some(40.0);
+    engine.set_load_sampler(Box::new(FakeLoadSampler::new(vec![
+        get_load_sample(0.0),
+        get_load_sample(5.0),
+        during,
+    ])));
+    engine.set_background_load_check(20.0, false);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    assert!(engine.get_results()[0].is_contaminated());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("Background 5%, 35/35/35% during (background load)"));
+}
+
+#[test]
+fn background_load_rerun_test() {
+    let log_directory = get_log_directory("background-load-rerun");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_load_sampler(Box::new(FakeLoadSampler::new(vec![get_load_sample(45.0)])));
+    engine.set_background_load_check(20.0, true);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    // only run again once, and only the second run's result is kept
+    assert_eq!(runner.get_encode_count(), 2);
+    assert_eq!(engine.get_results().len(), 1);
+    assert!(engine.get_results()[0].is_contaminated());
+}
+
+#[test]
+fn read_throughput_per_source_test() {
+    let log_directory = get_log_directory("read-throughput");
+    // two sources of the same resolution and fps, one of them on slow storage
+    let results: Vec<PermutationResult> = [("ssd/1080-60.y4m", 900.0), ("hdd/1080-60.y4m", 90.0)]
+        .iter()
+        .map(|(source, megabytes_per_sec)| {
+            let mut result = PermutationResult::new(
+                &get_metadata(),
+                20,
+                &String::from("-preset p1"),
+                ENCODER,
+                false,
+            );
+            result.source_file = source.to_string();
+            result.read_throughput = Some(ReadThroughput {
+                measured_bytes_per_sec: megabytes_per_sec * 1_000_000.0,
+                required_bytes_per_sec: 186_624_000.0,
+            });
+            return result;
+        })
+        .collect();
+
+    log_results_to_file(
+        results,
+        &String::from("1s"),
+        vec![],
+        20,
+        true,
+        &log_directory,
+        &None,
+    )
+    .unwrap();
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("Source read speed [ssd/1080-60.y4m] (1920x1080@60): "));
+    assert!(log.contains("Source read speed [hdd/1080-60.y4m] (1920x1080@60): "));
+}
+
+#[test]
+fn trials_test() {
+    let log_directory = get_log_directory("trials");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_trials(3, true);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    // the warm-up runs too, but isn't counted
+    assert_eq!(runner.get_encode_count(), 4);
+    let results = engine.get_results();
+    assert_eq!(results.len(), 1);
+
+    let trial_stats = results[0].trial_stats.clone().unwrap();
+    assert_eq!(trial_stats.trials, 3);
+    assert_eq!(trial_stats.avg_fps.std_dev, 0.0);
+    assert_eq!(results[0].fps_stats.avg as f64, trial_stats.avg_fps.mean);
+    assert!(!results[0].is_too_variable());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("FPS across trials (mean ± 95% confidence interval):"));
+    assert!(log.contains("[20Mb/s] [-preset p1]: 3 trials"));
+}
+
+#[test]
+fn trials_too_variable_test() {
+    let log_directory = get_log_directory("trials-too-variable");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Steady(240));
+    runner.queue_progress(FakeProgress::Steady(480));
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_trials(3, false);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    assert_eq!(runner.get_encode_count(), 3);
+    assert!(engine.get_results()[0].is_too_variable());
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("[V]1920x1080"));
+    assert!(log.contains("(too variable)"));
+}
+
+#[test]
+fn overload_detection_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Overloaded(30));
+    let mut engine =
+        BenchmarkEngine::with_runner(get_log_directory("overload"), Box::new(runner.clone()));
+    let mut permutation = get_permutation("-preset p7", 20, false);
+    permutation.detect_overload = true;
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    assert!(engine.get_results()[0].was_overloaded);
+}
+
+#[test]
+fn no_overload_detection_test() {
+    // without -detect_overload, a slow encoder still runs to the end
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Overloaded(240));
+    let mut engine =
+        BenchmarkEngine::with_runner(get_log_directory("no-overload"), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p7", 20, false));
+
+    engine.run().unwrap();
+
+    assert!(!engine.get_results()[0].was_overloaded);
+}
+
+#[test]
+fn stalled_progress_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Stalled(240, 120));
+    let ctrl_channel = setup_ctrl_channel();
+    let feed = FakeProgressFeed::start(FakeProgress::Stalled(240, 120), 240, STATS_PERIOD);
+
+    let trial_result = watch_encode_progress(
+        Box::new(feed),
+        240,
+        false,
+        FrameRate::from(60),
+        false,
+        STATS_PERIOD,
+        &runner.progress_limits(),
+        &ctrl_channel,
+    );
+
+    assert!(trial_result.ffmpeg_error);
+}
+
+#[test]
+fn erroring_progress_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Erroring);
+    let ctrl_channel = setup_ctrl_channel();
+    let feed = FakeProgressFeed::start(FakeProgress::Erroring, 240, STATS_PERIOD);
+
+    let trial_result = watch_encode_progress(
+        Box::new(feed),
+        240,
+        false,
+        FrameRate::from(60),
+        false,
+        STATS_PERIOD,
+        &runner.progress_limits(),
+        &ctrl_channel,
+    );
+
+    assert!(trial_result.ffmpeg_error);
+    assert!(trial_result.all_fps.is_empty());
+}
+
+#[test]
+fn duplicate_skipping_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    // first bitrate: p2 produces the same score as p1, so it's skipped at the next bitrate
+    runner.queue_vmaf_score(Some(90.0));
+    runner.queue_vmaf_score(Some(90.0));
+    runner.queue_vmaf_score(Some(91.0));
+    // second bitrate: only p1 & p3 get encoded
+    runner.queue_vmaf_score(Some(92.0));
+    runner.queue_vmaf_score(Some(93.0));
+
+    let mut engine =
+        PermutationEngine::with_runner(get_log_directory("duplicates"), Box::new(runner.clone()));
+    for bitrate in [10, 15] {
+        engine.add(get_permutation("-preset p1", bitrate, true));
+        engine.add(get_permutation("-preset p2", bitrate, true));
+        engine.add(get_permutation("-preset p3", bitrate, true));
+    }
+
+    engine.run().unwrap();
+
+    let duplicates = engine.get_duplicate_results();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].encoder_settings, "-preset p2");
+
+    let results = engine.get_results();
+    assert_eq!(results.len(), 4);
+    assert!(!results
+        .iter()
+        .any(|r| r.bitrate == 15 && r.encoder_settings == "-preset p2"));
+    assert_eq!(runner.get_vmaf_count(), 5);
+}
+
+#[test]
+fn target_quality_stops_permutations_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_vmaf_score(Some(96.0));
+    runner.queue_vmaf_score(Some(97.0));
+
+    let mut engine = PermutationEngine::with_runner(
+        get_log_directory("target-quality"),
+        Box::new(runner.clone()),
+    );
+    for bitrate in [10, 15] {
+        engine.add(get_permutation("-preset p1", bitrate, true));
+        engine.add(get_permutation("-preset p2", bitrate, true));
+    }
+
+    engine.run().unwrap();
+
+    // the first bitrate hit the target, so the second one never runs
+    assert_eq!(engine.get_results().len(), 2);
+}
+
+#[test]
+fn target_quality_moves_to_next_rate_control_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_vmaf_score(Some(96.0));
+
+    let mut engine = PermutationEngine::with_runner(
+        get_log_directory("target-quality-rate-control"),
+        Box::new(runner.clone()),
+    );
+    for bitrate in [10, 15] {
+        engine.add(get_permutation("-preset p1 -rc cbr", bitrate, true));
+    }
+    let mut permutation = get_permutation("-preset p1 -rc constqp -qp 23", 0, true);
+    permutation.rate_control = RateControl::Cqp(23);
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    // cbr hit the target at its first bitrate, which doesn't stop cqp from running
+    let results = engine.get_results();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].encoder_settings, "-preset p1 -rc constqp -qp 23");
+}
+
+#[test]
+fn check_encode_quality_retry_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_vmaf_score(None);
+    runner.queue_vmaf_score(None);
+    runner.queue_vmaf_score(Some(94.5));
+
+    let mut engine =
+        PermutationEngine::with_runner(get_log_directory("retry"), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1", 10, true));
+
+    engine.run().unwrap();
+
+    assert_eq!(runner.get_vmaf_count(), 3);
+    assert_eq!(engine.get_results()[0].vmaf_score, 94.5);
+}
+
+#[test]
+fn check_encode_quality_retries_exhausted_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_vmaf_score(None);
+    runner.queue_vmaf_score(None);
+    runner.queue_vmaf_score(None);
+
+    let mut engine = PermutationEngine::with_runner(
+        get_log_directory("retry-exhausted"),
+        Box::new(runner.clone()),
+    );
+    engine.add(get_permutation("-preset p1", 10, true));
+
+    let result = engine.run();
+
+    assert!(matches!(
+        result,
+        Err(EngineError::VmafRetriesExhausted { attempts: 3, .. })
+    ));
+    assert_eq!(runner.get_vmaf_count(), 3);
+}
+
+#[test]
+fn failed_permutation_continues_test() {
+    let log_directory = get_log_directory("failed-permutation");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Erroring);
+
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1 -profile:v fake", 20, false));
+    engine.add(get_permutation("-preset p2", 20, false));
+
+    engine.run().unwrap();
+
+    let results = engine.get_results();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].has_failed());
+    assert_eq!(results[0].error.as_deref(), Some(FAKE_ERROR_OUTPUT));
+    assert!(!results[1].has_failed());
+    assert!(results[1].fps_stats.avg > 0);
+
+    let log = fs::read_to_string(
+        PathBuf::from(&log_directory).join(format!("{}-benchmark.log", ENCODER)),
+    )
+    .unwrap();
+    assert!(log.contains("Permutations that ffmpeg was not able to run:"));
+    assert!(log.contains("[20Mb/s] [-preset p1 -profile:v fake]"));
+    assert!(log.contains("\tUnrecognized option 'fake'."));
+}
+
+#[test]
+fn failed_decode_test() {
+    let log_directory = get_log_directory("failed-decode");
+    let file = PathBuf::from(&log_directory).join("gameplay-hevc.mp4");
+    fs::write(&file, vec![0u8; 1_000_000]).unwrap();
+    let file = file.to_str().unwrap().to_string();
+
+    // i.e. a hwaccel with no device for it on the host
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Erroring);
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    let mut permutation = Permutation::new(file, String::from("decode"));
+    permutation.decode_run = true;
+    permutation.decode_only = true;
+    permutation.decoder = Some(Decoder::Hwaccel(String::from("vaapi")));
+    engine.add(permutation);
+
+    engine.run().unwrap();
+
+    assert!(engine.get_results()[0].has_failed());
+    let log =
+        fs::read_to_string(PathBuf::from(&log_directory).join("decode-benchmark.log")).unwrap();
+    let failures = log
+        .split("Permutations that ffmpeg was not able to run:")
+        .nth(1)
+        .unwrap();
+    assert!(failures.contains("[(Decode: vaapi) gameplay-hevc.mp4]"));
+}
+
+#[test]
+fn failed_permutation_skips_vmaf_test() {
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Erroring);
+    runner.queue_vmaf_score(Some(90.0));
+
+    let mut engine =
+        PermutationEngine::with_runner(get_log_directory("failed-vmaf"), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1", 10, true));
+    engine.add(get_permutation("-preset p2", 10, true));
+
+    engine.run().unwrap();
+
+    let results = engine.get_results();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].has_failed());
+    assert_eq!(results[1].vmaf_score, 90.0);
+    assert_eq!(runner.get_vmaf_count(), 1);
+}
+
+#[test]
+fn ffmpeg_logs_keep_failures_test() {
+    let log_directory = get_log_directory("ffmpeg-logs-failures");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Erroring);
+
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.add(get_permutation("-preset p1", 20, false));
+    engine.add(get_permutation("-preset p2", 20, false));
+
+    engine.run().unwrap();
+
+    let logs = get_ffmpeg_logs(&log_directory);
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].starts_with("ffmpeg-perm-1-"));
+    assert!(logs[0].ends_with("-encode.log"));
+    let log = fs::read_to_string(PathBuf::from(&log_directory).join(&logs[0])).unwrap();
+    assert_eq!(log, FAKE_ERROR_OUTPUT);
+}
+
+#[test]
+fn ffmpeg_logs_keep_all_test() {
+    let log_directory = get_log_directory("ffmpeg-logs-all");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_vmaf_score(None);
+    runner.queue_vmaf_score(Some(94.5));
+
+    let mut engine =
+        PermutationEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_ffmpeg_log_retention(LogRetention::All);
+    engine.add(get_permutation("-preset p1", 10, true));
+
+    engine.run().unwrap();
+
+    let logs = get_ffmpeg_logs(&log_directory);
+    let stages: Vec<&str> = logs
+        .iter()
+        .map(|name| {
+            name.splitn(4, '-')
+                .last()
+                .unwrap()
+                .splitn(2, '-')
+                .last()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        stages,
+        vec![
+            "encode.log",
+            "vmaf-1.log",
+            "vmaf-2.log",
+            "vmaf-encode-1.log",
+            "vmaf-encode-2.log"
+        ]
+    );
+}
+
+#[test]
+fn ffmpeg_logs_none_test() {
+    let log_directory = get_log_directory("ffmpeg-logs-none");
+    let runner = FakeRunner::new(get_metadata(), FakeProgress::Steady(480));
+    runner.queue_progress(FakeProgress::Erroring);
+
+    let mut engine = BenchmarkEngine::with_runner(log_directory.clone(), Box::new(runner.clone()));
+    engine.set_ffmpeg_log_retention(LogRetention::None);
+    engine.add(get_permutation("-preset p1", 20, false));
+
+    engine.run().unwrap();
+
+    assert!(get_ffmpeg_logs(&log_directory).is_empty());
+}
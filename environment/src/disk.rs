@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::SystemTime;
+
+// how much of the file we read to measure throughput; large enough to get past the drive's own cache
+const SAMPLE_BYTES: u64 = 512 * 1024 * 1024;
+const BUFFER_BYTES: usize = 8 * 1024 * 1024;
+// reads that skip the os' cache on windows have to start at, and fill, whole sectors in sector-aligned memory
+const SECTOR_ALIGNMENT: usize = 4096;
+
+const BYTES_IN_MB: f64 = 1_000_000.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ReadThroughput {
+    // what we measured reading the source file sequentially
+    pub measured_bytes_per_sec: f64,
+    // what the encoder will need to read to keep up with the source's frame rate
+    pub required_bytes_per_sec: f64,
+}
+
+impl ReadThroughput {
+    pub fn is_bottleneck(&self) -> bool {
+        return self.measured_bytes_per_sec < self.required_bytes_per_sec;
+    }
+
+    pub fn measured_mb_per_sec(&self) -> u64 {
+        return (self.measured_bytes_per_sec / BYTES_IN_MB) as u64;
+    }
+
+    pub fn required_mb_per_sec(&self) -> u64 {
+        return (self.required_bytes_per_sec / BYTES_IN_MB).ceil() as u64;
+    }
+
+    pub fn to_string(&self) -> String {
+        return format!(
+            "measured {} MB/s, source needs {} MB/s",
+            self.measured_mb_per_sec(),
+            self.required_mb_per_sec()
+        );
+    }
+}
+
+// bytes per second a source needs to be read at to play back in realtime
+pub fn get_required_throughput(file_size: u64, frames: u64, fps: f64) -> f64 {
+    if frames == 0 {
+        return 0.0;
+    }
+
+    return file_size as f64 * fps / frames as f64;
+}
+
+pub fn check_read_throughput(path: &str, frames: u64, fps: f64) -> Option<ReadThroughput> {
+    let file_size = std::fs::metadata(path).ok()?.len();
+    let measured = measure_read_throughput(path)?;
+
+    return Some(ReadThroughput {
+        measured_bytes_per_sec: measured,
+        required_bytes_per_sec: get_required_throughput(file_size, frames, fps),
+    });
+}
+
+// times a sequential read of a chunk of the file, from the drive rather than the os' page cache;
+// a source that was read before, or is smaller than free memory, would otherwise be timed at memory speed
+// note: starts halfway in, as the beginning of the file is the most likely part to have been read before
+pub fn measure_read_throughput(path: &str) -> Option<f64> {
+    let mut file = open_uncached(path)?;
+    let file_size = file.metadata().ok()?.len();
+    let sample = SAMPLE_BYTES.min(file_size);
+    let offset = (file_size - sample) / 2;
+    file.seek(SeekFrom::Start(offset - offset % SECTOR_ALIGNMENT as u64))
+        .ok()?;
+
+    let mut allocation = vec![0u8; BUFFER_BYTES + SECTOR_ALIGNMENT];
+    let aligned = allocation.as_ptr().align_offset(SECTOR_ALIGNMENT);
+    let buffer = &mut allocation[aligned..aligned + BUFFER_BYTES];
+    let mut total_read: u64 = 0;
+    let start = SystemTime::now();
+
+    while total_read < sample {
+        let read = file.read(buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+
+        total_read += read as u64;
+    }
+
+    let elapsed = start.elapsed().ok()?.as_secs_f64();
+    if total_read == 0 || elapsed == 0.0 {
+        return None;
+    }
+
+    return Some(total_read as f64 / elapsed);
+}
+
+// drops whatever of the file the os has cached, and on macos stops it caching what we read
+#[cfg(unix)]
+fn open_uncached(path: &str) -> Option<File> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(path).ok()?;
+    #[cfg(target_os = "macos")]
+    unsafe {
+        libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1);
+    }
+    #[cfg(not(target_os = "macos"))]
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+
+    return Some(file);
+}
+
+// FILE_FLAG_NO_BUFFERING reads straight from the drive
+#[cfg(windows)]
+fn open_uncached(path: &str) -> Option<File> {
+    use std::fs::OpenOptions;
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_FLAG_NO_BUFFERING: u32 = 0x20000000;
+    return OpenOptions::new()
+        .read(true)
+        .custom_flags(FILE_FLAG_NO_BUFFERING)
+        .open(path)
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use crate::disk::{get_required_throughput, measure_read_throughput, ReadThroughput};
+
+    #[test]
+    fn required_throughput_test() {
+        // 1 second worth of frames in a 100MB file
+        assert_eq!(
+            get_required_throughput(100_000_000, 60, 60.0),
+            100_000_000.0
+        );
+        assert_eq!(get_required_throughput(100_000_000, 0, 60.0), 0.0);
+    }
+
+    #[test]
+    fn bottleneck_test() {
+        let throughput = ReadThroughput {
+            measured_bytes_per_sec: 500_000_000.0,
+            required_bytes_per_sec: 1_100_000_000.0,
+        };
+
+        assert!(throughput.is_bottleneck());
+        assert_eq!(throughput.measured_mb_per_sec(), 500);
+        assert_eq!(throughput.required_mb_per_sec(), 1100);
+    }
+
+    #[test]
+    fn measure_read_throughput_test() {
+        let file_name = "./disk-throughput-test.bin";
+        let mut file = fs::File::create(file_name).unwrap();
+        file.write_all(&vec![0u8; 4 * 1024 * 1024]).unwrap();
+        file.sync_all().unwrap();
+
+        let measured = measure_read_throughput(file_name);
+        fs::remove_file(file_name).unwrap();
+
+        assert!(measured.is_some());
+        assert!(measured.unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::process::{Command, Stdio};
+
+// a tiny synthetic source; large enough for every hardware encoder's minimum frame size
+static TEST_SOURCE: &str = "color=black:size=256x256:rate=30";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncoderStatus {
+    Usable,
+    // the local ffmpeg wasn't built with this encoder at all
+    NotBuilt,
+    // built in, but the test encode failed; usually no device or driver that can open it
+    Unusable(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncoderCapability {
+    pub encoder: String,
+    pub status: EncoderStatus,
+}
+
+impl EncoderCapability {
+    pub fn is_usable(&self) -> bool {
+        return self.status == EncoderStatus::Usable;
+    }
+
+    pub fn to_string(&self) -> String {
+        return match &self.status {
+            EncoderStatus::Usable => format!("{}: usable", self.encoder),
+            EncoderStatus::NotBuilt => {
+                format!("{}: not available in this ffmpeg build", self.encoder)
+            }
+            EncoderStatus::Unusable(reason) => {
+                format!("{}: test encode failed ({})", self.encoder, reason)
+            }
+        };
+    }
+}
+
+// None when ffmpeg couldn't be run to ask
+pub fn get_ffmpeg_encoders() -> Option<Vec<String>> {
+    return Some(parse_encoders(&run_ffmpeg_listing("-encoders")?));
+}
+
+pub fn get_ffmpeg_hwaccels() -> Option<Vec<String>> {
+    return Some(parse_hwaccels(&run_ffmpeg_listing("-hwaccels")?));
+}
+
+// checks each encoder against the ffmpeg build first, only encoders that are built in get a test encode
+pub fn probe_encoders(encoders: &[&str]) -> Vec<EncoderCapability> {
+    let built = get_ffmpeg_encoders().unwrap_or_default();
+
+    return encoders
+        .iter()
+        .map(|encoder| probe_encoder_with(encoder, &built))
+        .collect();
+}
+
+pub fn probe_encoder(encoder: &str) -> EncoderCapability {
+    return probe_encoder_with(encoder, &get_ffmpeg_encoders().unwrap_or_default());
+}
+
+fn probe_encoder_with(encoder: &str, built: &Vec<String>) -> EncoderCapability {
+    let status = if !built.iter().any(|e| e == encoder) {
+        EncoderStatus::NotBuilt
+    } else {
+        match run_test_encode(encoder) {
+            Ok(()) => EncoderStatus::Usable,
+            Err(reason) => EncoderStatus::Unusable(reason),
+        }
+    };
+
+    return EncoderCapability {
+        encoder: String::from(encoder),
+        status,
+    };
+}
+
+fn run_ffmpeg_listing(listing: &str) -> Option<String> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", listing])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    return String::from_utf8(output.stdout).ok();
+}
+
+// encodes a single frame to nowhere, which is enough for ffmpeg to open the device behind the encoder
+fn run_test_encode(encoder: &str) -> Result<(), String> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-f",
+            "lavfi",
+            "-i",
+            TEST_SOURCE,
+            "-frames:v",
+            "1",
+            "-c:v",
+            encoder,
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdout(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(get_failure_reason(&stderr));
+}
+
+// ffmpeg's first complaint is the one that names the actual problem, the rest is fallout from it
+fn get_failure_reason(stderr: &str) -> String {
+    return stderr
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("ffmpeg exited without saying why")
+        .to_string();
+}
+
+// i.e. ' V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)', listed after a ' ------' line
+fn parse_encoders(listing: &str) -> Vec<String> {
+    return listing
+        .lines()
+        .skip_while(|line| !line.trim().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(String::from)
+        .collect();
+}
+
+// one hwaccel per line, after a 'Hardware acceleration methods:' header
+fn parse_hwaccels(listing: &str) -> Vec<String> {
+    return listing
+        .lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods:"))
+        .skip(1)
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoders::{
+        get_failure_reason, parse_encoders, parse_hwaccels, probe_encoder_with, EncoderStatus,
+    };
+
+    static ENCODERS: &str = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)\n V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)\n A....D aac                  AAC (Advanced Audio Coding)\n";
+
+    #[test]
+    fn parse_encoders_test() {
+        assert_eq!(
+            parse_encoders(ENCODERS),
+            vec!["libx264", "h264_nvenc", "aac"]
+        );
+        assert!(parse_encoders("").is_empty());
+    }
+
+    #[test]
+    fn parse_hwaccels_test() {
+        assert_eq!(
+            parse_hwaccels("Hardware acceleration methods:\ncuda\nvaapi\nqsv\n\n"),
+            vec!["cuda", "vaapi", "qsv"]
+        );
+        assert!(parse_hwaccels("Hardware acceleration methods:\n").is_empty());
+    }
+
+    #[test]
+    fn not_built_encoder_test() {
+        let built = parse_encoders(ENCODERS);
+        assert_eq!(
+            probe_encoder_with("av1_qsv", &built).status,
+            EncoderStatus::NotBuilt
+        );
+    }
+
+    #[test]
+    fn get_failure_reason_test() {
+        let stderr = "\n[h264_nvenc @ 0x5581] Cannot load libcuda.so.1\nError initializing output stream 0:0 -- Error while opening encoder\n";
+        assert_eq!(
+            get_failure_reason(stderr),
+            "[h264_nvenc @ 0x5581] Cannot load libcuda.so.1"
+        );
+        assert_eq!(get_failure_reason(""), "ffmpeg exited without saying why");
+    }
+}
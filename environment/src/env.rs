@@ -1,15 +1,17 @@
 use std::process::{Command, Stdio};
 
-pub fn fail_if_environment_not_setup() {
+use crate::error::EnvironmentError;
+
+pub fn check_environment_setup() -> Result<(), EnvironmentError> {
     if !is_ffmpeg_installed() {
-        println!("ffmpeg is either not installed or not setup on your path correctly");
-        std::process::exit(1);
+        return Err(EnvironmentError::FfmpegMissing);
     }
 
     if !is_ffprobe_installed() {
-        println!("ffprobe is either not installed or not setup on your path correctly");
-        std::process::exit(1);
+        return Err(EnvironmentError::FfprobeMissing);
     }
+
+    return Ok(());
 }
 
 fn is_ffmpeg_installed() -> bool {
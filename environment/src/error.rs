@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// the tools the benchmark shells out to aren't available
+#[derive(Debug)]
+pub enum EnvironmentError {
+    FfmpegMissing,
+    FfprobeMissing,
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            EnvironmentError::FfmpegMissing => write!(
+                f,
+                "ffmpeg is either not installed or not setup on your path correctly"
+            ),
+            EnvironmentError::FfprobeMissing => write!(
+                f,
+                "ffprobe is either not installed or not setup on your path correctly"
+            ),
+        };
+    }
+}
+
+impl Error for EnvironmentError {}
+
+// failures copying a source into memory before it's encoded
+#[derive(Debug)]
+pub enum StagingError {
+    // no memory-backed filesystem to stage into
+    Unsupported(String),
+    UnknownAvailableMemory,
+    InsufficientMemory {
+        source: String,
+        needed_bytes: u64,
+        headroom_bytes: u64,
+        available_bytes: u64,
+    },
+    Io {
+        source: String,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for StagingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            StagingError::Unsupported(dir) => write!(f, "memory staging needs a memory-backed filesystem at {}, which this system does not have", dir),
+            StagingError::UnknownAvailableMemory => write!(f, "unable to determine how much memory is available"),
+            StagingError::InsufficientMemory { source, needed_bytes, headroom_bytes, available_bytes } => write!(
+                f,
+                "not enough free memory to stage [{}]: needs {} MB plus {} MB of headroom, only {} MB available",
+                source,
+                needed_bytes / 1_000_000,
+                headroom_bytes / 1_000_000,
+                available_bytes / 1_000_000
+            ),
+            StagingError::Io { source, error } => write!(f, "unable to copy [{}] into memory: {}", source, error),
+        };
+    }
+}
+
+impl Error for StagingError {}
@@ -0,0 +1,249 @@
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread::available_parallelism;
+
+use gpus::get_gpu_descriptions;
+
+const BYTES_IN_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+static UNKNOWN: &str = "unknown";
+
+// everything about the machine that can move the numbers, so results from different hosts can be compared
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostFingerprint {
+    pub cpu_model: String,
+    // logical cores, as the encoder threads see them
+    pub cpu_cores: usize,
+    pub memory_bytes: u64,
+    pub os: String,
+    pub kernel: String,
+    pub ffmpeg_version: String,
+    pub ffmpeg_build_config: String,
+    // i.e. NVIDIA GeForce RTX 3080 [nvidia 535.54.03]
+    pub gpus: Vec<String>,
+    pub tool_version: String,
+}
+
+impl HostFingerprint {
+    // anything that can't be read is reported as unknown rather than failing the run
+    pub fn collect(tool_version: &str) -> Self {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let ffmpeg_version = get_ffmpeg_version_output().unwrap_or_default();
+
+        let cpu_cores = match parse_cpu_cores(&cpuinfo) {
+            0 => available_parallelism().map(|n| n.get()).unwrap_or(0),
+            cores => cores,
+        };
+
+        return Self {
+            cpu_model: parse_cpu_model(&cpuinfo).unwrap_or(String::from(UNKNOWN)),
+            cpu_cores,
+            memory_bytes: parse_total_memory(&meminfo).unwrap_or(0),
+            os: parse_os_name(&os_release).unwrap_or(String::from(std::env::consts::OS)),
+            kernel: get_kernel_release().unwrap_or(String::from(UNKNOWN)),
+            ffmpeg_version: parse_ffmpeg_version(&ffmpeg_version).unwrap_or(String::from(UNKNOWN)),
+            ffmpeg_build_config: parse_ffmpeg_build_config(&ffmpeg_version)
+                .unwrap_or(String::from(UNKNOWN)),
+            gpus: get_gpu_descriptions(),
+            tool_version: String::from(tool_version),
+        };
+    }
+
+    // one 'key: value' per line, the same block goes into the console and the results file
+    pub fn to_lines(&self) -> Vec<String> {
+        let gpus = if self.gpus.is_empty() {
+            String::from(UNKNOWN)
+        } else {
+            self.gpus.join(", ")
+        };
+
+        return vec![
+            format!("Tool version: {}", self.tool_version),
+            format!("CPU: {} ({} cores)", self.cpu_model, self.cpu_cores),
+            format!("Memory: {:.1} GB", self.memory_bytes as f64 / BYTES_IN_GB),
+            format!("OS: {}", self.os),
+            format!("Kernel: {}", self.kernel),
+            format!("GPU: {}", gpus),
+            format!("ffmpeg version: {}", self.ffmpeg_version),
+            format!("ffmpeg configuration: {}", self.ffmpeg_build_config),
+        ];
+    }
+}
+
+fn get_ffmpeg_version_output() -> Option<String> {
+    let output = Command::new("ffmpeg")
+        .arg("-version")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    return String::from_utf8(output.stdout).ok();
+}
+
+fn get_kernel_release() -> Option<String> {
+    if let Ok(release) = fs::read_to_string("/proc/sys/kernel/osrelease") {
+        return Some(release.trim().to_string());
+    }
+
+    // no procfs, i.e. macOS
+    let output = Command::new("uname")
+        .arg("-r")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let release = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if release.is_empty() {
+        return None;
+    }
+
+    return Some(release);
+}
+
+// the value of the first 'key : value' line in a /proc file with the given key
+fn get_proc_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    return content
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, value)| value.trim());
+}
+
+fn parse_cpu_model(cpuinfo: &str) -> Option<String> {
+    // arm boards don't have a model name, but some report the board model instead
+    let model = get_proc_value(cpuinfo, "model name").or(get_proc_value(cpuinfo, "Model"))?;
+    return Some(model.to_string());
+}
+
+fn parse_cpu_cores(cpuinfo: &str) -> usize {
+    return cpuinfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim() == "processor")
+        .count();
+}
+
+// MemTotal is reported in kB
+fn parse_total_memory(meminfo: &str) -> Option<u64> {
+    let kb = get_proc_value(meminfo, "MemTotal")?
+        .split_whitespace()
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    return Some(kb * 1024);
+}
+
+fn parse_os_name(os_release: &str) -> Option<String> {
+    let line = os_release
+        .lines()
+        .find(|line| line.starts_with("PRETTY_NAME="))?;
+    return Some(line["PRETTY_NAME=".len()..].trim_matches('"').to_string());
+}
+
+// i.e. 'ffmpeg version 6.0-full_build-www.gyan.dev Copyright (c) 2000-2023 the FFmpeg developers'
+fn parse_ffmpeg_version(version_output: &str) -> Option<String> {
+    let first_line = version_output.lines().next()?;
+    let version = first_line.strip_prefix("ffmpeg version ")?;
+    return Some(version.split_whitespace().next()?.to_string());
+}
+
+// same flags 'ffmpeg -buildconf' lists, already on one line
+fn parse_ffmpeg_build_config(version_output: &str) -> Option<String> {
+    return version_output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|config| config.trim().to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fingerprint::{
+        parse_cpu_cores, parse_cpu_model, parse_ffmpeg_build_config, parse_ffmpeg_version,
+        parse_os_name, parse_total_memory, HostFingerprint,
+    };
+
+    static CPUINFO: &str = "processor\t: 0\nvendor_id\t: AuthenticAMD\nmodel name\t: AMD Ryzen 9 5950X 16-Core Processor\n\nprocessor\t: 1\nvendor_id\t: AuthenticAMD\nmodel name\t: AMD Ryzen 9 5950X 16-Core Processor\n";
+    static FFMPEG_VERSION: &str = "ffmpeg version 6.0-full_build-www.gyan.dev Copyright (c) 2000-2023 the FFmpeg developers\nbuilt with gcc 12.2.0 (Rev10, Built by MSYS2 project)\nconfiguration: --enable-gpl --enable-version3 --enable-nvenc --enable-libvmaf\nlibavutil      58.  2.100 / 58.  2.100\n";
+
+    #[test]
+    fn parse_cpu_model_test() {
+        assert_eq!(
+            parse_cpu_model(CPUINFO),
+            Some(String::from("AMD Ryzen 9 5950X 16-Core Processor"))
+        );
+        assert_eq!(
+            parse_cpu_model("processor\t: 0\nModel\t\t: Raspberry Pi 4 Model B Rev 1.4\n"),
+            Some(String::from("Raspberry Pi 4 Model B Rev 1.4"))
+        );
+        assert_eq!(parse_cpu_model(""), None);
+    }
+
+    #[test]
+    fn parse_cpu_cores_test() {
+        assert_eq!(parse_cpu_cores(CPUINFO), 2);
+        assert_eq!(parse_cpu_cores(""), 0);
+    }
+
+    #[test]
+    fn parse_total_memory_test() {
+        assert_eq!(
+            parse_total_memory("MemTotal:       32768000 kB\nMemFree:         1024000 kB\n"),
+            Some(32768000 * 1024)
+        );
+        assert_eq!(parse_total_memory("MemFree:         1024000 kB\n"), None);
+    }
+
+    #[test]
+    fn parse_os_name_test() {
+        let os_release =
+            "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\n";
+        assert_eq!(
+            parse_os_name(os_release),
+            Some(String::from("Ubuntu 22.04.3 LTS"))
+        );
+        assert_eq!(parse_os_name("NAME=\"Ubuntu\"\n"), None);
+    }
+
+    #[test]
+    fn parse_ffmpeg_version_test() {
+        assert_eq!(
+            parse_ffmpeg_version(FFMPEG_VERSION),
+            Some(String::from("6.0-full_build-www.gyan.dev"))
+        );
+        assert_eq!(parse_ffmpeg_version("not ffmpeg"), None);
+    }
+
+    #[test]
+    fn parse_ffmpeg_build_config_test() {
+        assert_eq!(
+            parse_ffmpeg_build_config(FFMPEG_VERSION),
+            Some(String::from(
+                "--enable-gpl --enable-version3 --enable-nvenc --enable-libvmaf"
+            ))
+        );
+        assert_eq!(parse_ffmpeg_build_config(""), None);
+    }
+
+    #[test]
+    fn to_lines_test() {
+        let fingerprint = HostFingerprint {
+            cpu_model: String::from("AMD Ryzen 9 5950X 16-Core Processor"),
+            cpu_cores: 32,
+            memory_bytes: 32 * 1024 * 1024 * 1024,
+            os: String::from("Ubuntu 22.04.3 LTS"),
+            kernel: String::from("6.2.0-37-generic"),
+            ffmpeg_version: String::from("6.0"),
+            ffmpeg_build_config: String::from("--enable-nvenc"),
+            gpus: vec![],
+            tool_version: String::from("0.6.1"),
+        };
+
+        let lines = fingerprint.to_lines();
+        assert!(lines.contains(&String::from(
+            "CPU: AMD Ryzen 9 5950X 16-Core Processor (32 cores)"
+        )));
+        assert!(lines.contains(&String::from("Memory: 32.0 GB")));
+        assert!(lines.contains(&String::from("GPU: unknown")));
+    }
+}
@@ -1 +1,7 @@
+pub mod disk;
+pub mod encoders;
 pub mod env;
+pub mod error;
+pub mod fingerprint;
+pub mod load;
+pub mod memory;
@@ -0,0 +1,434 @@
+use std::fs;
+
+use gpus::telemetry::MetricStats;
+
+// one reading of the whole system; anything that can't be read is None
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LoadSample {
+    // % of cpu time spent busy since the previous sample, across every core
+    pub cpu_utilization: Option<f64>,
+    // how much of that the watched process (the encode) accounted for, when one is being watched
+    pub process_cpu_utilization: Option<f64>,
+    // mean and fastest of the per-core clocks
+    pub avg_core_mhz: Option<f64>,
+    pub max_core_mhz: Option<f64>,
+    // 1 minute load average
+    pub load_average: Option<f64>,
+}
+
+impl LoadSample {
+    // what was busy besides the watched process; None without a process to tell apart from the rest
+    pub fn get_background_utilization(&self) -> Option<f64> {
+        return match (self.cpu_utilization, self.process_cpu_utilization) {
+            (Some(cpu), Some(process)) => Some((cpu - process).max(0.0)),
+            _ => None,
+        };
+    }
+}
+
+pub trait LoadSampler: Send {
+    fn sample(&mut self) -> LoadSample;
+
+    // tells the given process' share of the cpu apart from the rest in the samples that follow, until unwatched
+    fn watch_process(&mut self, _pid: Option<u32>) {}
+}
+
+// what the system was doing over the course of one encode
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SystemLoad {
+    pub cpu_utilization: Option<MetricStats>,
+    pub core_mhz: Option<MetricStats>,
+    pub peak_core_mhz: Option<f64>,
+    pub load_average: Option<MetricStats>,
+    // cpu utilization right before the encode started, i.e. what something other than ffmpeg was using
+    pub background_cpu_utilization: Option<f64>,
+    // cpu utilization during the encode that wasn't the encode's own, i.e. something that started part way through
+    pub encode_background_cpu_utilization: Option<MetricStats>,
+    // the background utilization before or during the encode was above what was allowed for a clean measurement
+    pub contaminated: bool,
+    pub samples: usize,
+}
+
+impl SystemLoad {
+    pub fn from_samples(samples: &Vec<LoadSample>) -> Self {
+        let stats_of = |metric: fn(&LoadSample) -> Option<f64>| {
+            return MetricStats::from_values(&samples.iter().filter_map(metric).collect());
+        };
+
+        return Self {
+            cpu_utilization: stats_of(|s| s.cpu_utilization),
+            core_mhz: stats_of(|s| s.avg_core_mhz),
+            peak_core_mhz: stats_of(|s| s.max_core_mhz).map(|stats| stats.max),
+            load_average: stats_of(|s| s.load_average),
+            background_cpu_utilization: None,
+            encode_background_cpu_utilization: stats_of(|s| s.get_background_utilization()),
+            contaminated: false,
+            samples: samples.len(),
+        };
+    }
+
+    // flags the measurement when more than the allowed % of cpu was busy with something else, before the encode
+    // started or at any point while it ran
+    pub fn check_background(&mut self, background: LoadSample, max_background_load: f64) {
+        self.background_cpu_utilization = background.cpu_utilization;
+        self.contaminated = [
+            background.cpu_utilization,
+            self.encode_background_cpu_utilization
+                .map(|stats| stats.max),
+        ]
+        .iter()
+        .flatten()
+        .any(|utilization| *utilization > max_background_load);
+    }
+
+    // i.e. 'CPU 35/42/60% | Cores 3600/4100/4500MHz (peak 5050MHz) | Load 2.10/2.40/3.00 | Background 3%, 1/2/4% during'
+    pub fn to_string(&self) -> String {
+        let mut described: Vec<String> = Vec::new();
+        if let Some(stats) = self.cpu_utilization {
+            described.push(format!("CPU {}%", stats.to_string()));
+        }
+
+        if let Some(stats) = self.core_mhz {
+            let mut cores = format!("Cores {}MHz", stats.to_string());
+            if let Some(peak) = self.peak_core_mhz {
+                cores.push_str(&format!(" (peak {:.0}MHz)", peak));
+            }
+
+            described.push(cores);
+        }
+
+        if let Some(stats) = self.load_average {
+            described.push(format!(
+                "Load {:.2}/{:.2}/{:.2}",
+                stats.min, stats.avg, stats.max
+            ));
+        }
+
+        if let Some(background) = self.background_cpu_utilization {
+            let mut described_background = format!("Background {:.0}%", background);
+            if let Some(stats) = self.encode_background_cpu_utilization {
+                described_background.push_str(&format!(", {}% during", stats.to_string()));
+            }
+
+            described.push(described_background);
+        }
+
+        if described.is_empty() {
+            return String::from("no readings");
+        }
+
+        return described.join(" | ");
+    }
+}
+
+// a sampler for this system, None where there's no /proc to read i.e. windows or macOS
+pub fn get_load_sampler() -> Option<Box<dyn LoadSampler>> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    parse_cpu_times(&stat)?;
+    return Some(Box::new(ProcLoadSampler::new()));
+}
+
+// cpu utilization is the change in /proc/stat's counters between two samples,
+// and a process' share of it the change in its own counters in /proc/<pid>/stat
+pub struct ProcLoadSampler {
+    previous: Option<CpuTimes>,
+    // the watched process, and the cpu time it had used as of the previous sample
+    process: Option<(u32, u64)>,
+}
+
+impl ProcLoadSampler {
+    pub fn new() -> Self {
+        return Self {
+            previous: None,
+            process: None,
+        };
+    }
+}
+
+impl LoadSampler for ProcLoadSampler {
+    fn sample(&mut self) -> LoadSample {
+        let times = read_cpu_times();
+        let cpu_utilization = match (self.previous, times) {
+            (Some(previous), Some(current)) => current.utilization_since(&previous),
+            _ => None,
+        };
+
+        // a process that's gone can't be told apart anymore, so it stops being watched
+        let process = self
+            .process
+            .and_then(|(pid, _)| read_process_ticks(pid).map(|ticks| (pid, ticks)));
+        let process_cpu_utilization = match (self.previous, times, self.process, process) {
+            (Some(previous), Some(current), Some((_, previous_ticks)), Some((_, ticks))) => {
+                current.share_since(&previous, ticks.saturating_sub(previous_ticks))
+            }
+            _ => None,
+        };
+        self.previous = times;
+        self.process = process;
+
+        let core_mhz = parse_core_mhz(&fs::read_to_string("/proc/cpuinfo").unwrap_or_default());
+        let load_average = fs::read_to_string("/proc/loadavg")
+            .ok()
+            .and_then(|loadavg| parse_load_average(&loadavg));
+
+        return LoadSample {
+            cpu_utilization,
+            process_cpu_utilization,
+            avg_core_mhz: mean(&core_mhz),
+            max_core_mhz: core_mhz.iter().cloned().reduce(f64::max),
+            load_average,
+        };
+    }
+
+    fn watch_process(&mut self, pid: Option<u32>) {
+        // both are read now, so the process' share covers the same stretch of time as the whole system's
+        self.previous = read_cpu_times();
+        self.process = pid.and_then(|pid| read_process_ticks(pid).map(|ticks| (pid, ticks)));
+    }
+}
+
+// plays back the given samples in order, repeating the last one once it runs out
+pub struct FakeLoadSampler {
+    samples: Vec<LoadSample>,
+    next: usize,
+}
+
+impl FakeLoadSampler {
+    pub fn new(samples: Vec<LoadSample>) -> Self {
+        return Self { samples, next: 0 };
+    }
+}
+
+impl LoadSampler for FakeLoadSampler {
+    fn sample(&mut self) -> LoadSample {
+        let index = self.next.min(self.samples.len().saturating_sub(1));
+        self.next += 1;
+        return self.samples.get(index).cloned().unwrap_or_default();
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    fn utilization_since(&self, previous: &CpuTimes) -> Option<f64> {
+        return self.share_since(previous, self.busy.saturating_sub(previous.busy));
+    }
+
+    // % of all the cpu time that passed since the previous sample that the given ticks make up
+    fn share_since(&self, previous: &CpuTimes, ticks: u64) -> Option<f64> {
+        let total = self.total.checked_sub(previous.total)?;
+        if total == 0 {
+            return None;
+        }
+
+        return Some(ticks as f64 / total as f64 * 100.0);
+    }
+}
+
+fn read_cpu_times() -> Option<CpuTimes> {
+    return fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|stat| parse_cpu_times(&stat));
+}
+
+fn read_process_ticks(pid: u32) -> Option<u64> {
+    return fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| parse_process_ticks(&stat));
+}
+
+// 'pid (comm) state ppid ...', where utime and stime are the 14th and 15th fields; counted in the same ticks as
+// /proc/stat, across every thread of the process. the name can hold spaces and brackets, so fields are counted
+// from the last ')'
+fn parse_process_ticks(stat: &str) -> Option<u64> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    return Some(utime + stime);
+}
+
+// 'cpu  user nice system idle iowait irq softirq steal guest guest_nice'; guest time is already counted in user
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .filter_map(|field| field.parse::<u64>().ok())
+        .collect();
+    if fields.len() < 4 {
+        return None;
+    }
+
+    let total: u64 = fields.iter().sum();
+    let idle = fields[3] + fields.get(4).unwrap_or(&0);
+    return Some(CpuTimes {
+        busy: total - idle,
+        total,
+    });
+}
+
+// every core lists its current clock as 'cpu MHz : 3600.000'
+fn parse_core_mhz(cpuinfo: &str) -> Vec<f64> {
+    return cpuinfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim() == "cpu MHz")
+        .filter_map(|(_, value)| value.trim().parse::<f64>().ok())
+        .collect();
+}
+
+// '0.52 0.58 0.59 1/1234 5678', the first being the last minute
+fn parse_load_average(loadavg: &str) -> Option<f64> {
+    return loadavg.split_whitespace().next()?.parse::<f64>().ok();
+}
+
+fn mean(values: &Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    return Some(values.iter().sum::<f64>() / values.len() as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::load::{
+        parse_core_mhz, parse_cpu_times, parse_load_average, parse_process_ticks, CpuTimes,
+        FakeLoadSampler, LoadSample, LoadSampler, SystemLoad,
+    };
+
+    fn get_sample(cpu_utilization: f64) -> LoadSample {
+        return LoadSample {
+            cpu_utilization: Some(cpu_utilization),
+            process_cpu_utilization: None,
+            avg_core_mhz: Some(4000.0),
+            max_core_mhz: Some(4800.0),
+            load_average: Some(2.5),
+        };
+    }
+
+    #[test]
+    fn parse_cpu_times_test() {
+        let stat = "cpu  4705 356 584 3699 23 0 32 0 0 0\ncpu0 1393 280 283 1000 5 0 8 0 0 0\nintr 114930548\n";
+        assert_eq!(
+            parse_cpu_times(stat),
+            Some(CpuTimes {
+                busy: 4705 + 356 + 584 + 32,
+                total: 4705 + 356 + 584 + 3699 + 23 + 32,
+            })
+        );
+        assert_eq!(parse_cpu_times("intr 114930548\n"), None);
+    }
+
+    #[test]
+    fn utilization_since_test() {
+        let previous = CpuTimes {
+            busy: 1000,
+            total: 4000,
+        };
+        let current = CpuTimes {
+            busy: 1250,
+            total: 5000,
+        };
+        assert_eq!(current.utilization_since(&previous), Some(25.0));
+        assert_eq!(previous.utilization_since(&previous), None);
+    }
+
+    #[test]
+    fn parse_process_ticks_test() {
+        let stat = "4242 (ffmpeg (enc)) S 4200 4242 4200 0 -1 4194304 1510 0 0 0 1250 310 0 0 20 0 9 0 123 0 0\n";
+        assert_eq!(parse_process_ticks(stat), Some(1250 + 310));
+        assert_eq!(parse_process_ticks("4242 (ffmpeg) S 4200"), None);
+    }
+
+    #[test]
+    fn share_since_test() {
+        let previous = CpuTimes {
+            busy: 1000,
+            total: 4000,
+        };
+        let current = CpuTimes {
+            busy: 1500,
+            total: 5000,
+        };
+        assert_eq!(current.share_since(&previous, 300), Some(30.0));
+    }
+
+    #[test]
+    fn parse_core_mhz_test() {
+        let cpuinfo =
+            "processor\t: 0\ncpu MHz\t\t: 3600.000\n\nprocessor\t: 1\ncpu MHz\t\t: 4800.500\n";
+        assert_eq!(parse_core_mhz(cpuinfo), vec![3600.0, 4800.5]);
+        assert!(parse_core_mhz("processor\t: 0\n").is_empty());
+    }
+
+    #[test]
+    fn parse_load_average_test() {
+        assert_eq!(
+            parse_load_average("0.52 0.58 0.59 1/1234 5678\n"),
+            Some(0.52)
+        );
+        assert_eq!(parse_load_average(""), None);
+    }
+
+    #[test]
+    fn from_samples_test() {
+        let load = SystemLoad::from_samples(&vec![get_sample(30.0), get_sample(50.0)]);
+        assert_eq!(load.samples, 2);
+        assert_eq!(load.cpu_utilization.unwrap().avg, 40.0);
+        assert_eq!(load.peak_core_mhz, Some(4800.0));
+        assert_eq!(
+            load.to_string(),
+            "CPU 30/40/50% | Cores 4000/4000/4000MHz (peak 4800MHz) | Load 2.50/2.50/2.50"
+        );
+    }
+
+    #[test]
+    fn check_background_test() {
+        let mut load = SystemLoad::from_samples(&vec![get_sample(30.0)]);
+        load.check_background(get_sample(5.0), 20.0);
+        assert!(!load.contaminated);
+
+        load.check_background(get_sample(35.0), 20.0);
+        assert!(load.contaminated);
+        assert_eq!(load.background_cpu_utilization, Some(35.0));
+
+        // nothing measured isn't evidence of load
+        load.check_background(LoadSample::default(), 20.0);
+        assert!(!load.contaminated);
+    }
+
+    #[test]
+    fn encode_background_test() {
+        let mut busy = get_sample(90.0);
+        busy.process_cpu_utilization = Some(40.0);
+        let mut quiet = get_sample(60.0);
+        quiet.process_cpu_utilization = Some(58.0);
+
+        // idle before the encode, but something took up half the cpu next to it part way through
+        let mut load = SystemLoad::from_samples(&vec![quiet, busy, get_sample(95.0)]);
+        load.check_background(get_sample(5.0), 20.0);
+        assert_eq!(load.encode_background_cpu_utilization.unwrap().max, 50.0);
+        assert!(load.contaminated);
+        assert!(load.to_string().ends_with("Background 5%, 2/26/50% during"));
+
+        // samples without the encode's own share can't say what else was running
+        let mut load = SystemLoad::from_samples(&vec![quiet, get_sample(95.0)]);
+        load.check_background(get_sample(5.0), 20.0);
+        assert!(!load.contaminated);
+    }
+
+    #[test]
+    fn fake_load_sampler_test() {
+        let mut sampler = FakeLoadSampler::new(vec![get_sample(10.0), get_sample(20.0)]);
+        assert_eq!(sampler.sample(), get_sample(10.0));
+        assert_eq!(sampler.sample(), get_sample(20.0));
+        assert_eq!(sampler.sample(), get_sample(20.0));
+    }
+}
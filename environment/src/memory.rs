@@ -0,0 +1,159 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::StagingError;
+
+// memory-backed filesystem available on most linux distros
+static MEMORY_STAGING_DIR: &str = "/dev/shm";
+
+// prefix for anything we stage, followed by the id of the process that staged it, i.e. encoder-benchmark-4242-1080-60.y4m,
+// so leftovers from a killed run can be told apart from what another run is still using
+static STAGED_PREFIX: &str = "encoder-benchmark-";
+
+// leave room for the os and ffmpeg itself, we don't want to push the system into swap mid-encode
+const MEMORY_HEADROOM_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+pub fn get_memory_staging_dir() -> Option<PathBuf> {
+    let dir = Path::new(MEMORY_STAGING_DIR);
+    if dir.is_dir() {
+        return Some(dir.to_path_buf());
+    }
+
+    return None;
+}
+
+pub fn get_available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    return parse_available_memory(&meminfo);
+}
+
+// MemAvailable is reported in kB, and already accounts for reclaimable page cache
+fn parse_available_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    return Some(kb * 1024);
+}
+
+pub fn has_memory_for(bytes: u64, available: u64) -> bool {
+    return available >= bytes + MEMORY_HEADROOM_BYTES;
+}
+
+// copies the source into memory, returning the path of the staged copy
+pub fn stage_in_memory(source: &str) -> Result<String, StagingError> {
+    let staging_dir = get_memory_staging_dir()
+        .ok_or(StagingError::Unsupported(MEMORY_STAGING_DIR.to_string()))?;
+
+    remove_stale_staged_files(&staging_dir);
+
+    let size = fs::metadata(source)
+        .map_err(|error| StagingError::Io {
+            source: source.to_string(),
+            error,
+        })?
+        .len();
+    let available = get_available_memory().ok_or(StagingError::UnknownAvailableMemory)?;
+
+    if !has_memory_for(size, available) {
+        return Err(StagingError::InsufficientMemory {
+            source: source.to_string(),
+            needed_bytes: size,
+            headroom_bytes: MEMORY_HEADROOM_BYTES,
+            available_bytes: available,
+        });
+    }
+
+    let file_name = Path::new(source).file_name().unwrap().to_str().unwrap();
+    let staged = staging_dir.join(format!("{}{}-{}", STAGED_PREFIX, process::id(), file_name));
+    if let Err(error) = fs::copy(source, &staged) {
+        // a partial copy would just be taking up memory
+        let _ = fs::remove_file(&staged);
+        return Err(StagingError::Io {
+            source: source.to_string(),
+            error,
+        });
+    }
+
+    return Ok(staged.to_str().unwrap().to_string());
+}
+
+pub fn remove_staged(staged: &str) {
+    let _ = fs::remove_file(staged);
+}
+
+// a run that was ctrl-c'd or crashed won't have cleaned up after itself, but one still running needs its source
+fn remove_stale_staged_files(staging_dir: &Path) {
+    let entries = match fs::read_dir(staging_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        if is_stale(&entry.file_name().to_string_lossy(), is_process_running) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+// staged by a process that's gone; files staged before the process id was part of the name can't be claimed by anyone
+fn is_stale(file_name: &str, is_running: impl Fn(u32) -> bool) -> bool {
+    let owner = match file_name.strip_prefix(STAGED_PREFIX) {
+        Some(owner) => owner,
+        None => return false,
+    };
+
+    return match owner
+        .split_once('-')
+        .and_then(|(pid, _)| pid.parse::<u32>().ok())
+    {
+        Some(pid) => !is_running(pid),
+        None => true,
+    };
+}
+
+// /dev/shm is only staged to on linux, where every running process has an entry in /proc
+fn is_process_running(pid: u32) -> bool {
+    return Path::new(&format!("/proc/{}", pid)).exists();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{has_memory_for, is_stale, parse_available_memory};
+
+    static MEMINFO: &str = "MemTotal:       32768000 kB\nMemFree:         1024000 kB\nMemAvailable:   16384000 kB\nBuffers:          512000 kB\n";
+
+    #[test]
+    fn parse_available_memory_test() {
+        assert_eq!(parse_available_memory(MEMINFO), Some(16384000 * 1024));
+    }
+
+    #[test]
+    fn parse_missing_available_memory_test() {
+        assert_eq!(
+            parse_available_memory("MemTotal:       32768000 kB\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn is_stale_test() {
+        let is_running = |pid: u32| pid == 4242;
+
+        // another benchmark is still encoding from this one
+        assert!(!is_stale("encoder-benchmark-4242-1080-60.y4m", is_running));
+        assert!(is_stale("encoder-benchmark-1337-1080-60.y4m", is_running));
+        // left behind by a version that didn't name its owner
+        assert!(is_stale("encoder-benchmark-gameplay.y4m", is_running));
+        assert!(!is_stale("pulse-shm-1234", is_running));
+    }
+
+    #[test]
+    fn has_memory_for_test() {
+        let gb: u64 = 1024 * 1024 * 1024;
+        assert!(has_memory_for(4 * gb, 8 * gb));
+        // the source would fit, but not with room to spare
+        assert!(!has_memory_for(7 * gb, 8 * gb));
+    }
+}
@@ -1,37 +1,51 @@
 use std::ffi::c_float;
 
 use codecs::get_vendor_for_codec;
-use codecs::vendor::Vendor;
+use codecs::gop::get_keyframe_args;
+use codecs::rate_control::RateControl;
+
+use crate::decoder::Decoder;
+use crate::frame_rate::FrameRate;
+use crate::hwaccel::{resolve_decoder, TargetOs};
+use crate::source::with_extension;
 
 pub static TCP_LISTEN: &str = "tcp://localhost:2000?listen&listen_timeout=3000&timeout=1000000";
 pub static NO_OUTPUT: &str = "-f null -";
 
 #[derive(Clone)]
 pub struct FfmpegArgs {
-    fps_limit: u32,
-    report: bool,
+    fps_limit: FrameRate,
     send_progress: bool,
     pub first_input: String,
     second_input: String,
     pub bitrate: u32,
+    // decides which bitrate options are passed along with the bitrate
+    pub rate_control: RateControl,
+    // a fixed keyframe interval, when the encoder's own shouldn't be used
+    pub gop_frames: Option<u32>,
     pub encoder: String,
     pub encoder_args: String,
     pub output_args: String,
     pub is_vmaf: bool,
     pub stats_period: c_float,
     pub decode: bool,
+    // what a decode is run with, in place of the encoder vendor's hwaccel
+    pub decoder: Option<Decoder>,
+    // which of the vendor's hwaccels a decode falls back to depends on the os
+    pub target_os: TargetOs,
     pub ten_bit: bool,
 }
 
 impl Default for FfmpegArgs {
     fn default() -> Self {
         FfmpegArgs {
-            fps_limit: 0,
-            report: false,
+            fps_limit: FrameRate::default(),
             send_progress: true,
             first_input: String::new(),
             second_input: String::new(),
             bitrate: u32::default(),
+            rate_control: RateControl::default(),
+            gop_frames: None,
             encoder: String::new(),
             encoder_args: String::new(),
             output_args: NO_OUTPUT.to_string(),
@@ -40,6 +54,8 @@ impl Default for FfmpegArgs {
             // but your fps calculations might be a little over-inflated
             stats_period: 0.5,
             decode: false,
+            decoder: None,
+            target_os: TargetOs::current(),
             ten_bit: false,
         }
     }
@@ -67,7 +83,7 @@ impl FfmpegArgs {
         return ffmpeg_args;
     }
 
-    pub fn map_to_vmaf(&self, fps: u32) -> FfmpegArgs {
+    pub fn map_to_vmaf(&self, fps: FrameRate) -> FfmpegArgs {
         let mut vmaf_args = self.clone();
 
         // required for having high fps inputs score correctly
@@ -77,12 +93,18 @@ impl FfmpegArgs {
         vmaf_args.output_args = String::from(NO_OUTPUT);
         vmaf_args.is_vmaf = true;
         vmaf_args.send_progress = false;
-        // vmaf needs to report so we can get the vmaf score
-        vmaf_args.report = true;
 
         return vmaf_args;
     }
 
+    // the decoder that was picked, otherwise the encoder vendor's hwaccel for this os, unverified
+    pub fn get_decoder(&self) -> Decoder {
+        return match &self.decoder {
+            Some(decoder) => decoder.clone(),
+            None => resolve_decoder(get_vendor_for_codec(&self.encoder), self.target_os, None),
+        };
+    }
+
     pub fn to_string(&self) -> String {
         let mut output = String::new();
 
@@ -101,34 +123,22 @@ impl FfmpegArgs {
         output.push_str("-y ");
 
         if self.decode {
-            let vendor = get_vendor_for_codec(&self.encoder);
-            let hwaccel = match vendor {
-                Vendor::Nvidia => "cuda",
-                Vendor::AMD => "d3d11va",
-                Vendor::IntelQSV => "qsv",
-                // TODO: implement this
-                Vendor::Apple => "apple",
-                Vendor::Unknown => "error",
-            };
-
-            output.push_str("-hwaccel ");
-            output.push_str(hwaccel);
-            output.push_str(" ");
-        }
-
-        if self.report {
-            output.push_str("-report ");
+            let input_args = self.get_decoder().get_input_args();
+            if !input_args.is_empty() {
+                output.push_str(input_args.as_str());
+                output.push(' ');
+            }
         }
 
-        if self.fps_limit != 0 {
-            output.push_str(format!("-r {} ", self.fps_limit).as_str());
+        if !self.fps_limit.is_zero() {
+            output.push_str(format!("-r {} ", self.fps_limit.to_ffmpeg_arg()).as_str());
         }
 
         output.push_str(["-i", self.first_input.as_str()].join(" ").as_str());
 
         if !self.second_input.is_empty() {
-            if self.fps_limit != 0 {
-                output.push_str(format!(" -r {}", self.fps_limit).as_str());
+            if !self.fps_limit.is_zero() {
+                output.push_str(format!(" -r {}", self.fps_limit.to_ffmpeg_arg()).as_str());
             }
 
             output.push_str([" -i", self.second_input.as_str()].join(" ").as_str());
@@ -142,6 +152,8 @@ impl FfmpegArgs {
                 append_encode_only_args(
                     &mut output,
                     self.bitrate,
+                    &self.rate_control,
+                    self.gop_frames,
                     &self.encoder,
                     &self.encoder_args,
                 );
@@ -161,23 +173,13 @@ impl FfmpegArgs {
     }
 
     pub fn setup_decode_output(&mut self) {
-        let splits = self.first_input.split("y4m").collect::<Vec<&str>>();
         // we'll probably want to change the file extension at some point but, .mp4 is fine for now
-        let file_prefix = splits.get(0).unwrap();
-        let mut output = String::from("");
-        output.push_str(file_prefix);
-        output.push_str("mp4");
-        self.output_args = output;
+        self.output_args = with_extension(&self.first_input, "mp4");
     }
 
     pub fn setup_decode_input(&mut self) {
-        let splits = self.first_input.split("y4m").collect::<Vec<&str>>();
         // we'll probably want to change the file extension at some point but, .mp4 is fine for now
-        let file_prefix = splits.get(0).unwrap();
-        let mut output = String::from("");
-        output.push_str(file_prefix);
-        output.push_str("mp4");
-        self.first_input = output;
+        self.first_input = with_extension(&self.first_input, "mp4");
     }
 
     pub fn set_no_output_for_error(&mut self) {
@@ -193,15 +195,25 @@ impl FfmpegArgs {
 fn append_encode_only_args(
     arg_str: &mut String,
     bitrate: u32,
+    rate_control: &RateControl,
+    gop_frames: Option<u32>,
     encoder: &String,
     encoder_args: &String,
 ) {
-    arg_str.push_str([" -b:v", bitrate.to_string().as_str()].join(" ").as_str());
-    // adding the rate amount to the end of the bitrate
-    arg_str.push('M');
+    // quality-targeted rate controls don't take a bitrate at all
+    let bitrate_args = rate_control.get_bitrate_args(bitrate);
+    if !bitrate_args.is_empty() {
+        arg_str.push(' ');
+        arg_str.push_str(bitrate_args.as_str());
+    }
     arg_str.push_str([" -c:v", encoder.as_str()].join(" ").as_str());
     arg_str.push(' ');
     arg_str.push_str(encoder_args.as_str());
+
+    if let Some(frames) = gop_frames {
+        arg_str.push(' ');
+        arg_str.push_str(&get_keyframe_args(get_vendor_for_codec(encoder), frames));
+    }
 }
 
 fn append_vmaf_only_args(arg_str: &mut String) {
@@ -230,7 +242,12 @@ pub struct Cli {
 
 #[cfg(test)]
 mod tests {
+    use codecs::rate_control::RateControl;
+
     use crate::args::{Cli, FfmpegArgs, NO_OUTPUT, TCP_LISTEN};
+    use crate::decoder::Decoder;
+    use crate::frame_rate::FrameRate;
+    use crate::hwaccel::TargetOs;
 
     static INPUT_ONE: &str = "1080-60.y4m";
     static INPUT_TWO: &str = "1080-60-2.y4m";
@@ -245,9 +262,8 @@ mod tests {
         let args = FfmpegArgs::default();
 
         // check fields that have defaults
-        assert_eq!(args.fps_limit, 0);
+        assert_eq!(args.fps_limit, FrameRate::default());
         assert_eq!(args.send_progress, true);
-        assert_eq!(args.report, false);
         assert_eq!(args.bitrate, u32::default());
         assert_eq!(args.output_args, "-f null -");
         assert_eq!(args.is_vmaf, false);
@@ -296,28 +312,141 @@ mod tests {
         );
     }
 
+    #[test]
+    fn to_string_vbr_test() {
+        let mut args = get_one_input_args();
+        args.rate_control = RateControl::Vbr {
+            max: 1.5,
+            buffer: 2.0,
+        };
+        assert!(args
+            .to_string()
+            .contains(" -b:v 6M -maxrate 9000k -bufsize 12000k -c:v h264_nvenc"));
+    }
+
+    #[test]
+    fn to_string_quality_targeted_test() {
+        let mut args = get_one_input_args();
+        args.rate_control = RateControl::Cqp(23);
+        assert!(args.to_string().contains("-i 1080-60.y4m -c:v h264_nvenc"));
+        assert!(!args.to_string().contains("-b:v"));
+    }
+
+    #[test]
+    fn to_string_gop_test() {
+        let mut args = get_one_input_args();
+        args.gop_frames = Some(120);
+        assert!(args.to_string().ends_with(
+            "-rc-lookahead 8 -g 120 -keyint_min 120 -sc_threshold 0 -forced-idr 1 -no-scenecut 1 -f null -"
+        ));
+
+        // decoding doesn't encode, so it has no keyframes to place
+        args.decode = true;
+        assert!(!args.to_string().contains("-g 120"));
+    }
+
     #[test]
     fn map_to_vmaf_test() {
         let args = get_two_input_args();
-        let vmaf_args = args.map_to_vmaf(FPS_LIMIT);
+        let vmaf_args = args.map_to_vmaf(FrameRate::from(FPS_LIMIT));
 
-        assert_eq!(vmaf_args.fps_limit, FPS_LIMIT);
+        assert_eq!(vmaf_args.fps_limit, FrameRate::from(FPS_LIMIT));
         assert_eq!(vmaf_args.first_input, String::from(TCP_LISTEN));
         assert_eq!(vmaf_args.second_input, args.first_input);
         assert_eq!(vmaf_args.output_args, String::from(NO_OUTPUT));
         assert_eq!(vmaf_args.is_vmaf, true);
         assert_eq!(vmaf_args.send_progress, false);
-        assert_eq!(vmaf_args.report, true);
     }
 
     #[test]
     fn map_to_vmaf_to_string_test() {
-        let vmaf_args = get_two_input_args().map_to_vmaf(FPS_LIMIT);
+        let vmaf_args = get_two_input_args().map_to_vmaf(FrameRate::from(FPS_LIMIT));
         assert_eq!(vmaf_args.to_string(),
-                   format!("-y -report -r {} -i {} -r {} -i 1080-60.y4m -filter_complex libvmaf='n_threads={}:n_subsample=5' -f null -", FPS_LIMIT, TCP_LISTEN, FPS_LIMIT, num_cpus::get().to_string())
+                   format!("-y -r {} -i {} -r {} -i 1080-60.y4m -filter_complex libvmaf='n_threads={}:n_subsample=5' -f null -", FPS_LIMIT, TCP_LISTEN, FPS_LIMIT, num_cpus::get().to_string())
         );
     }
 
+    #[test]
+    fn map_to_vmaf_fractional_fps_test() {
+        let vmaf_args = get_two_input_args().map_to_vmaf(FrameRate::new(60000, 1001));
+        assert!(vmaf_args
+            .to_string()
+            .starts_with(format!("-y -r 60000/1001 -i {} -r 60000/1001 -i", TCP_LISTEN).as_str()));
+    }
+
+    #[test]
+    fn setup_decode_output_test() {
+        let mut args = get_one_input_args();
+        args.setup_decode_output();
+        assert_eq!(args.output_args, "1080-60.mp4");
+    }
+
+    #[test]
+    fn to_string_decoder_test() {
+        let mut args = get_one_input_args();
+        args.decode = true;
+        args.decoder = Some(Decoder::Codec(String::from("hevc_cuvid")));
+        assert_eq!(
+            args.to_string(),
+            "-progress tcp://localhost:1234 -stats_period 0.5 -y -c:v hevc_cuvid -i 1080-60.y4m -f null -"
+        );
+
+        args.decoder = Some(Decoder::Software);
+        assert_eq!(
+            args.to_string(),
+            "-progress tcp://localhost:1234 -stats_period 0.5 -y -i 1080-60.y4m -f null -"
+        );
+    }
+
+    #[test]
+    fn to_string_hwaccel_test() {
+        let expected = [
+            ("h264_nvenc", TargetOs::Windows, "-hwaccel cuda "),
+            ("h264_nvenc", TargetOs::Linux, "-hwaccel cuda "),
+            ("h264_nvenc", TargetOs::MacOs, "-hwaccel videotoolbox "),
+            ("h264_amf", TargetOs::Windows, "-hwaccel d3d11va "),
+            ("h264_amf", TargetOs::Linux, "-hwaccel vaapi "),
+            ("h264_amf", TargetOs::MacOs, "-hwaccel videotoolbox "),
+            ("h264_qsv", TargetOs::Windows, "-hwaccel qsv "),
+            ("h264_qsv", TargetOs::Linux, "-hwaccel vaapi "),
+            ("h264_qsv", TargetOs::MacOs, "-hwaccel videotoolbox "),
+            ("h264_videotoolbox", TargetOs::Windows, ""),
+            ("h264_videotoolbox", TargetOs::Linux, ""),
+            (
+                "h264_videotoolbox",
+                TargetOs::MacOs,
+                "-hwaccel videotoolbox ",
+            ),
+            ("libx264", TargetOs::Windows, ""),
+            ("libx264", TargetOs::Linux, ""),
+            ("libx264", TargetOs::MacOs, ""),
+        ];
+
+        for (encoder, os, hwaccel) in expected {
+            let mut args = get_one_input_args();
+            args.encoder = String::from(encoder);
+            args.target_os = os;
+            args.decode = true;
+            assert_eq!(
+                args.to_string(),
+                format!(
+                    "-progress tcp://localhost:1234 -stats_period 0.5 -y {}-i 1080-60.y4m -f null -",
+                    hwaccel
+                ),
+                "{} on {}",
+                encoder,
+                os
+            );
+        }
+    }
+
+    #[test]
+    fn setup_decode_input_test() {
+        let mut args = get_one_input_args();
+        args.setup_decode_input();
+        assert_eq!(args.first_input, "1080-60.mp4");
+    }
+
     fn get_one_input_args() -> FfmpegArgs {
         let args = Cli {
             encoder: ENCODER.to_string(),
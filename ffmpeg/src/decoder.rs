@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+
+// what decodes a file: the cpu, one of ffmpeg's hwaccels, or a decoder of its own (i.e. hevc_cuvid)
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoder {
+    Software,
+    Hwaccel(String),
+    Codec(String),
+}
+
+impl Decoder {
+    // the options that go in front of the input being decoded
+    pub fn get_input_args(&self) -> String {
+        return match self {
+            Decoder::Software => String::new(),
+            Decoder::Hwaccel(hwaccel) => format!("-hwaccel {}", hwaccel),
+            Decoder::Codec(codec) => format!("-c:v {}", codec),
+        };
+    }
+}
+
+// 'software', a hwaccel like 'cuda' or 'vaapi', or a decoder like 'hevc_cuvid' or 'av1_qsv'
+impl FromStr for Decoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_name = !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_name {
+            return Err(format!(
+                "[{}] is not a decoder, expected software, a hwaccel (i.e. cuda) or a decoder (i.e. hevc_cuvid)",
+                s
+            ));
+        }
+
+        if s == "software" {
+            return Ok(Decoder::Software);
+        }
+
+        // ffmpeg's decoders are named after their codec, hwaccels never have an underscore in them
+        if s.contains('_') {
+            return Ok(Decoder::Codec(s.to_string()));
+        }
+
+        return Ok(Decoder::Hwaccel(s.to_string()));
+    }
+}
+
+impl fmt::Display for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Decoder::Software => write!(f, "software"),
+            Decoder::Hwaccel(name) | Decoder::Codec(name) => write!(f, "{}", name),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decoder::Decoder;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!("software".parse::<Decoder>(), Ok(Decoder::Software));
+        assert_eq!(
+            "cuda".parse::<Decoder>(),
+            Ok(Decoder::Hwaccel(String::from("cuda")))
+        );
+        assert_eq!(
+            "hevc_cuvid".parse::<Decoder>(),
+            Ok(Decoder::Codec(String::from("hevc_cuvid")))
+        );
+        assert!("".parse::<Decoder>().is_err());
+        assert!("cuda -y".parse::<Decoder>().is_err());
+        assert_eq!(
+            Decoder::Codec(String::from("av1_qsv")).to_string(),
+            "av1_qsv"
+        );
+    }
+
+    #[test]
+    fn input_args_test() {
+        assert_eq!(Decoder::Software.get_input_args(), "");
+        assert_eq!(
+            Decoder::Hwaccel(String::from("vaapi")).get_input_args(),
+            "-hwaccel vaapi"
+        );
+        assert_eq!(
+            Decoder::Codec(String::from("h264_qsv")).get_input_args(),
+            "-c:v h264_qsv"
+        );
+    }
+}
@@ -0,0 +1,55 @@
+// what an encoder actually wrote, as ffprobe reads it back from the output
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncodedStream {
+    // i.e. h264, hevc or av1
+    pub codec: String,
+    // as ffprobe names it, i.e. 'High' or 'Main 10'
+    pub profile: String,
+    // in the codec's own units, i.e. 41 for h264 level 4.1 but 123 for hevc
+    pub level: Option<i64>,
+    pub pix_fmt: String,
+    pub i_frames: u64,
+    pub p_frames: u64,
+    pub b_frames: u64,
+    // the longest run of frames between two keyframes, None when there was only the first
+    pub keyframe_interval: Option<u32>,
+}
+
+impl EncodedStream {
+    pub fn get_frames(&self) -> u64 {
+        return self.i_frames + self.p_frames + self.b_frames;
+    }
+
+    // 10-bit formats end in their bit depth, i.e. yuv420p10le, or are named after it, i.e. p010le;
+    // yuv410p only has 10 in its subsampling
+    pub fn is_ten_bit(&self) -> bool {
+        let format = self
+            .pix_fmt
+            .strip_suffix("le")
+            .or(self.pix_fmt.strip_suffix("be"))
+            .unwrap_or(self.pix_fmt.as_str());
+        return format.ends_with("p10") || format == "p010";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoded_stream::EncodedStream;
+
+    fn get_stream(pix_fmt: &str) -> EncodedStream {
+        return EncodedStream {
+            pix_fmt: String::from(pix_fmt),
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    fn is_ten_bit_test() {
+        assert!(get_stream("yuv420p10le").is_ten_bit());
+        assert!(get_stream("yuv444p10be").is_ten_bit());
+        assert!(get_stream("p010le").is_ten_bit());
+        assert!(!get_stream("yuv420p").is_ten_bit());
+        assert!(!get_stream("yuv410p").is_ten_bit());
+        assert!(!get_stream("p016le").is_ten_bit());
+    }
+}
@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// failures reading metadata off a source file with ffprobe
+#[derive(Debug)]
+pub enum ProbeError {
+    // ffprobe couldn't be started at all
+    Spawn(io::Error),
+    // ffprobe ran, but had nothing to say about the file
+    NoOutput(String),
+    MissingField(String),
+    InvalidField { field: String, value: String },
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ProbeError::Spawn(e) => write!(f, "unable to run ffprobe to collect metadata on the input video file: {}", e),
+            ProbeError::NoOutput(file) => write!(f, "ffprobe was not able to read information on [{}]; check your file paths for accuracy", file),
+            ProbeError::MissingField(field) => write!(f, "ffprobe did not report a '{}' for the input video file", field),
+            ProbeError::InvalidField { field, value } => write!(f, "ffprobe reported an unusable '{}' for the input video file: {}", field, value),
+        };
+    }
+}
+
+impl Error for ProbeError {}
+
+// failures turning a compressed source into the raw intermediate we benchmark against
+#[derive(Debug)]
+pub enum SourceError {
+    Spawn(io::Error),
+    DecodeFailed(String),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            SourceError::Spawn(e) => write!(f, "unable to run ffmpeg to decode the input video file: {}", e),
+            SourceError::DecodeFailed(file) => write!(f, "ffmpeg was not able to decode [{}] into a raw source; check that the file is a valid video", file),
+        };
+    }
+}
+
+impl Error for SourceError {}
+
+// failures hashing the frames a decoder produced from a file
+#[derive(Debug)]
+pub enum FrameHashError {
+    Probe(ProbeError),
+    Spawn(io::Error),
+    // ffmpeg couldn't decode the file with the decoder, along with what it had to say about it
+    DecodeFailed(String),
+}
+
+impl fmt::Display for FrameHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            FrameHashError::Probe(e) => write!(f, "{}", e),
+            FrameHashError::Spawn(e) => {
+                write!(f, "unable to run ffmpeg to hash the decoded frames: {}", e)
+            }
+            FrameHashError::DecodeFailed(error_output) => write!(
+                f,
+                "ffmpeg was not able to decode the file to hash its frames: {}",
+                error_output
+            ),
+        };
+    }
+}
+
+impl Error for FrameHashError {}
+
+impl From<ProbeError> for FrameHashError {
+    fn from(e: ProbeError) -> Self {
+        return FrameHashError::Probe(e);
+    }
+}
+
+// failures getting a vmaf score out of ffmpeg's output
+#[derive(Debug)]
+pub enum VmafError {
+    // the output didn't contain a score where we expected one
+    ParseFailed(String),
+    // the vmaf calculation itself failed, along with the last of what ffmpeg wrote; its ffmpeg log has the rest
+    Failed(String),
+}
+
+impl fmt::Display for VmafError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            VmafError::ParseFailed(line) => write!(f, "could not parse score from line: {}", line),
+            VmafError::Failed(details) => write!(f, "vmaf calculation failed: {}", details),
+        };
+    }
+}
+
+impl Error for VmafError {}
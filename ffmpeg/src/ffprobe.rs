@@ -1,10 +1,16 @@
+use std::collections::HashMap;
 use std::process::{Command, Stdio};
 
+use crate::encoded_stream::EncodedStream;
+use crate::error::ProbeError;
+use crate::frame_rate::FrameRate;
 use crate::metadata::MetaData;
+use crate::packet::Packet;
 
-pub fn probe_for_video_metadata(input_file: &String) -> MetaData {
+pub fn probe_for_video_metadata(input_file: &String) -> Result<MetaData, ProbeError> {
     // adding the input file later on, prevents the space split breaking the args
-    let args = format!("-v error -select_streams v:0 -show_entries stream=duration_ts,r_frame_rate,coded_width,coded_height -of csv=p=0");
+    // note: key=value output so we don't depend on the order ffprobe decides to print fields in
+    let args = String::from("-v error -select_streams v:0 -show_entries stream=width,height,r_frame_rate,time_base,duration_ts,duration,nb_frames -of default=noprint_wrappers=1");
     let split_args = args.split(" ");
     let mut vec_args = split_args.collect::<Vec<&str>>();
     vec_args.push(input_file);
@@ -12,62 +18,276 @@ pub fn probe_for_video_metadata(input_file: &String) -> MetaData {
         .args(vec_args)
         .stdout(Stdio::piped())
         .output()
-        .expect("Unable to run ffprobe to collect metadata on the input video file");
+        .map_err(ProbeError::Spawn)?;
 
     let output = String::from_utf8_lossy(&ffprobe.stdout);
     if output.to_string().is_empty() {
-        panic!("ffprobe was not able to read information on the file; check your file paths for accuracy")
+        return Err(ProbeError::NoOutput(input_file.clone()));
     }
 
-    return extract_metadata(output.to_string());
+    let mut metadata = extract_metadata(output.to_string())?;
+
+    // some containers (mkv/webm especially) don't store enough to derive the frame count, so count them
+    if metadata.is_empty() {
+        metadata.frames = count_frames(input_file)?;
+    }
+
+    return Ok(metadata);
+}
+
+// decodes the entire stream to count frames, slow but always correct
+pub fn count_frames(input_file: &String) -> Result<u64, ProbeError> {
+    let ffprobe = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-count_frames"])
+        .args(["-show_entries", "stream=nb_read_frames", "-of", "csv=p=0"])
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(ProbeError::Spawn)?;
+
+    let output = String::from_utf8_lossy(&ffprobe.stdout);
+    return output
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| ProbeError::InvalidField {
+            field: String::from("nb_read_frames"),
+            value: output.trim().to_string(),
+        });
+}
+
+// sizes and timestamps of every packet in the file's video stream, in decode order
+pub fn probe_packets(input_file: &String) -> Result<Vec<Packet>, ProbeError> {
+    // compact output is key=value too, one packet per line
+    let ffprobe = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "packet=dts_time,pts_time,size,flags"])
+        .args(["-of", "compact=p=0"])
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(ProbeError::Spawn)?;
+
+    let output = String::from_utf8_lossy(&ffprobe.stdout);
+    let packets = extract_packets(output.to_string())?;
+    if packets.is_empty() {
+        return Err(ProbeError::NoOutput(input_file.clone()));
+    }
+
+    return Ok(packets);
+}
+
+// the pixel format a software decode of the file's video stream comes out in
+pub fn probe_pix_fmt(input_file: &String) -> Result<String, ProbeError> {
+    let ffprobe = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=pix_fmt", "-of", "csv=p=0"])
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(ProbeError::Spawn)?;
+
+    let output = String::from_utf8_lossy(&ffprobe.stdout);
+    let pix_fmt = output.trim();
+    if pix_fmt.is_empty() {
+        return Err(ProbeError::NoOutput(input_file.clone()));
+    }
+
+    return Ok(pix_fmt.to_string());
 }
 
-fn extract_metadata(line: String) -> MetaData {
-    let splits = line.split(",").collect::<Vec<&str>>();
+// the codec, profile and pixel format of the file's video stream, along with the type of every frame in it;
+// the frames have to be decoded to know their type, so this takes about as long as a decode of the file
+pub fn probe_encoded_stream(input_file: &String) -> Result<EncodedStream, ProbeError> {
+    // compact output prefixes each line with its section, which tells the stream apart from its frames
+    let ffprobe = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args([
+            "-show_entries",
+            "stream=codec_name,profile,level,pix_fmt:frame=key_frame,pict_type",
+        ])
+        .args(["-of", "compact"])
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(ProbeError::Spawn)?;
+
+    let output = String::from_utf8_lossy(&ffprobe.stdout);
+    if output.to_string().is_empty() {
+        return Err(ProbeError::NoOutput(input_file.clone()));
+    }
+
+    return extract_encoded_stream(output.to_string());
+}
+
+fn extract_encoded_stream(output: String) -> Result<EncodedStream, ProbeError> {
+    let mut stream = EncodedStream::default();
+    let mut has_stream = false;
+    let mut frame: u32 = 0;
+    let mut last_keyframe: Option<u32> = None;
+
+    for line in output.lines() {
+        let (section, fields) = match line.split_once('|') {
+            Some(split) => split,
+            None => continue,
+        };
+        let entries = fields
+            .split('|')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect::<HashMap<&str, &str>>();
+
+        if section == "stream" {
+            stream.codec = get_entry(&entries, "codec_name")?.to_string();
+            stream.profile = entries.get("profile").copied().unwrap_or("").to_string();
+            // ffprobe reports -99 when the codec has no notion of a level
+            stream.level = parse_entry::<i64>(&entries, "level").filter(|level| *level >= 0);
+            stream.pix_fmt = get_entry(&entries, "pix_fmt")?.to_string();
+            has_stream = true;
+            continue;
+        }
+
+        if section != "frame" {
+            continue;
+        }
+
+        match entries.get("pict_type").copied() {
+            Some("I") => stream.i_frames += 1,
+            Some("P") => stream.p_frames += 1,
+            Some("B") => stream.b_frames += 1,
+            _ => {}
+        }
+
+        if entries.get("key_frame").copied() == Some("1") {
+            if let Some(last_keyframe) = last_keyframe {
+                let interval = frame - last_keyframe;
+                stream.keyframe_interval =
+                    Some(stream.keyframe_interval.unwrap_or(0).max(interval));
+            }
+
+            last_keyframe = Some(frame);
+        }
+
+        frame += 1;
+    }
+
+    if !has_stream {
+        return Err(ProbeError::MissingField(String::from("codec_name")));
+    }
+
+    return Ok(stream);
+}
+
+fn extract_packets(output: String) -> Result<Vec<Packet>, ProbeError> {
+    let mut packets = Vec::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let entries = line
+            .split('|')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect::<HashMap<&str, &str>>();
+
+        // some muxers leave the dts out, the pts is the next best thing to place the packet with
+        let time = parse_entry::<f64>(&entries, "dts_time")
+            .or(parse_entry::<f64>(&entries, "pts_time"))
+            .ok_or(ProbeError::MissingField(String::from("dts_time")))?;
+        let bytes = parse_required_entry::<u64>(&entries, "size")?;
+        let keyframe = get_entry(&entries, "flags")?.contains('K');
+        packets.push(Packet::new(time, bytes, keyframe));
+    }
+
+    return Ok(packets);
+}
 
-    let fps_splits = splits.get(2).unwrap().split("/").collect::<Vec<&str>>();
+fn extract_metadata(output: String) -> Result<MetaData, ProbeError> {
+    let entries = output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect::<HashMap<&str, &str>>();
+
+    let frame_rate = get_entry(&entries, "r_frame_rate")?;
+    let fps = FrameRate::parse(frame_rate).ok_or(ProbeError::InvalidField {
+        field: String::from("r_frame_rate"),
+        value: frame_rate.to_string(),
+    })?;
 
     let metadata = MetaData {
-        fps: fps_splits.get(0).unwrap().trim().parse::<u32>().unwrap(),
-        frames: splits
-            .get(3)
-            .unwrap()
-            .to_string()
-            .trim()
-            .parse::<u64>()
-            .unwrap(),
-        width: splits
-            .get(0)
-            .unwrap()
-            .to_string()
-            .trim()
-            .parse::<u32>()
-            .unwrap(),
-        height: splits
-            .get(1)
-            .unwrap()
-            .to_string()
-            .trim()
-            .parse::<u32>()
-            .unwrap(),
+        fps,
+        frames: derive_frame_count(&entries, fps),
+        // the coded size is padded out to whole macroblocks, i.e. 1088 for 1080p h264
+        width: parse_required_entry::<u32>(&entries, "width")?,
+        height: parse_required_entry::<u32>(&entries, "height")?,
     };
 
-    return metadata;
+    return Ok(metadata);
+}
+
+fn get_entry<'a>(entries: &HashMap<&str, &'a str>, key: &str) -> Result<&'a str, ProbeError> {
+    return entries
+        .get(key)
+        .copied()
+        .ok_or(ProbeError::MissingField(key.to_string()));
+}
+
+fn parse_required_entry<T: std::str::FromStr>(
+    entries: &HashMap<&str, &str>,
+    key: &str,
+) -> Result<T, ProbeError> {
+    let value = get_entry(entries, key)?;
+    return value.parse::<T>().map_err(|_| ProbeError::InvalidField {
+        field: key.to_string(),
+        value: value.to_string(),
+    });
+}
+
+// duration_ts is only the frame count when the stream's timebase is 1/fps (like y4m)
+// containers like mp4/mkv use their own timebase (e.g. 1/90000), so convert through seconds
+fn derive_frame_count(entries: &HashMap<&str, &str>, fps: FrameRate) -> u64 {
+    // containers that store a frame count are the most reliable, when they have one
+    if let Some(nb_frames) = parse_entry::<u64>(entries, "nb_frames").filter(|frames| *frames != 0)
+    {
+        return nb_frames;
+    }
+
+    let duration_ts = parse_entry::<u64>(entries, "duration_ts");
+    let time_base = entries.get("time_base").and_then(|tb| FrameRate::parse(tb));
+    if let (Some(duration_ts), Some(time_base)) = (duration_ts, time_base) {
+        let seconds = duration_ts as f64 * time_base.as_f64();
+        return (seconds * fps.as_f64()).round() as u64;
+    }
+
+    if let Some(duration) = parse_entry::<f64>(entries, "duration") {
+        return (duration * fps.as_f64()).round() as u64;
+    }
+
+    return duration_ts.unwrap_or(0);
+}
+
+// ffprobe reports N/A for values it doesn't know, which won't parse
+fn parse_entry<T: std::str::FromStr>(entries: &HashMap<&str, &str>, key: &str) -> Option<T> {
+    return entries.get(key).and_then(|value| value.parse::<T>().ok());
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ffprobe::extract_metadata;
+    use crate::encoded_stream::EncodedStream;
+    use crate::error::ProbeError;
+    use crate::ffprobe::{extract_encoded_stream, extract_metadata, extract_packets};
+    use crate::frame_rate::FrameRate;
     use crate::metadata::MetaData;
+    use crate::packet::Packet;
 
-    static PROBE_LINE: &str = "1920,1080,60/1,1923";
+    static PROBE_OUTPUT: &str = "width=1920\nheight=1080\nr_frame_rate=60/1\ntime_base=1/60\nduration_ts=1923\nduration=32.050000\nnb_frames=N/A\n";
+    static NTSC_PROBE_OUTPUT: &str = "width=1920\nheight=1080\nr_frame_rate=60000/1001\ntime_base=1001/60000\nduration_ts=1923\nduration=32.082050\nnb_frames=N/A\n";
+    static MP4_PROBE_OUTPUT: &str = "width=1920\nheight=1080\ncoded_width=1920\ncoded_height=1088\nr_frame_rate=60/1\ntime_base=1/90000\nduration_ts=2884500\nduration=32.050000\nnb_frames=N/A\n";
+    static MKV_PROBE_OUTPUT: &str = "width=1920\nheight=1080\nr_frame_rate=30000/1001\ntime_base=1/1000\nduration_ts=N/A\nduration=N/A\nnb_frames=N/A\n";
 
     #[test]
     fn extract_metadata_test() {
-        let extracted = extract_metadata(String::from(PROBE_LINE));
+        let extracted = extract_metadata(String::from(PROBE_OUTPUT)).unwrap();
 
         let expected = MetaData {
-            fps: 60,
+            fps: FrameRate::from(60),
             frames: 1923,
             width: 1920,
             height: 1080,
@@ -76,6 +296,135 @@ mod tests {
         assert_eq!(equals(&expected, &extracted), true);
     }
 
+    #[test]
+    fn extract_fractional_fps_test() {
+        let extracted = extract_metadata(String::from(NTSC_PROBE_OUTPUT)).unwrap();
+
+        assert_eq!(extracted.fps, FrameRate::new(60000, 1001));
+        assert_eq!(extracted.frames, 1923);
+    }
+
+    #[test]
+    fn extract_container_timebase_test() {
+        let extracted = extract_metadata(String::from(MP4_PROBE_OUTPUT)).unwrap();
+
+        // 2884500 ticks of a 90kHz clock is 32.05 seconds, which at 60fps is 1923 frames
+        assert_eq!(extracted.frames, 1923);
+        // not the 1088 it's coded at
+        assert_eq!(extracted.height, 1080);
+    }
+
+    #[test]
+    fn extract_nb_frames_preferred_test() {
+        let output = MP4_PROBE_OUTPUT.replace("nb_frames=N/A", "nb_frames=1922");
+        let extracted = extract_metadata(output).unwrap();
+
+        assert_eq!(extracted.frames, 1922);
+    }
+
+    #[test]
+    fn extract_unknown_duration_test() {
+        let extracted = extract_metadata(String::from(MKV_PROBE_OUTPUT)).unwrap();
+
+        // nothing to derive the frames from, which callers treat as empty metadata
+        assert_eq!(extracted.frames, 0);
+    }
+
+    #[test]
+    fn extract_missing_field_test() {
+        let output = PROBE_OUTPUT.replace("width=1920\n", "");
+        assert!(matches!(
+            extract_metadata(output),
+            Err(ProbeError::MissingField(_))
+        ));
+    }
+
+    #[test]
+    fn extract_invalid_frame_rate_test() {
+        let output = PROBE_OUTPUT.replace("r_frame_rate=60/1", "r_frame_rate=0/0");
+        assert!(matches!(
+            extract_metadata(output),
+            Err(ProbeError::InvalidField { .. })
+        ));
+    }
+
+    #[test]
+    fn extract_packets_test() {
+        let output = "dts_time=0.000000|pts_time=0.033000|size=48211|flags=K__\ndts_time=0.016000|pts_time=N/A|size=9120|flags=___\ndts_time=N/A|pts_time=0.050000|size=8731|flags=___\n";
+        let packets = extract_packets(String::from(output)).unwrap();
+
+        assert_eq!(
+            packets,
+            vec![
+                Packet::new(0.0, 48211, true),
+                Packet::new(0.016, 9120, false),
+                Packet::new(0.05, 8731, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_packets_missing_size_test() {
+        let output = "dts_time=0.000000|pts_time=0.000000|size=N/A|flags=K__\n";
+        assert!(matches!(
+            extract_packets(String::from(output)),
+            Err(ProbeError::InvalidField { .. })
+        ));
+    }
+
+    #[test]
+    fn extract_encoded_stream_test() {
+        let mut output = String::new();
+        for i in 0..10 {
+            let pict_type = match i % 5 {
+                0 => "I",
+                2 | 4 => "B",
+                _ => "P",
+            };
+            let key_frame = if i % 5 == 0 { 1 } else { 0 };
+            output.push_str(
+                format!("frame|key_frame={}|pict_type={}\n", key_frame, pict_type).as_str(),
+            );
+        }
+        output.push_str("stream|codec_name=h264|profile=High|pix_fmt=yuv420p|level=42\n");
+
+        let stream = extract_encoded_stream(output).unwrap();
+        assert_eq!(
+            stream,
+            EncodedStream {
+                codec: String::from("h264"),
+                profile: String::from("High"),
+                level: Some(42),
+                pix_fmt: String::from("yuv420p"),
+                i_frames: 2,
+                p_frames: 4,
+                b_frames: 4,
+                keyframe_interval: Some(5),
+            }
+        );
+        assert!(!stream.is_ten_bit());
+    }
+
+    #[test]
+    fn extract_single_keyframe_test() {
+        let output = "stream|codec_name=av1|profile=Main|pix_fmt=yuv420p10le|level=-99\nframe|key_frame=1|pict_type=I\nframe|key_frame=0|pict_type=P\n";
+        let stream = extract_encoded_stream(String::from(output)).unwrap();
+
+        assert_eq!(stream.level, None);
+        assert_eq!(stream.keyframe_interval, None);
+        assert_eq!(stream.get_frames(), 2);
+        assert!(stream.is_ten_bit());
+    }
+
+    #[test]
+    fn extract_missing_stream_test() {
+        let output = "frame|key_frame=1|pict_type=I\n";
+        assert!(matches!(
+            extract_encoded_stream(String::from(output)),
+            Err(ProbeError::MissingField(_))
+        ));
+    }
+
     fn equals(original: &MetaData, other: &MetaData) -> bool {
         return original.fps == other.fps
             && original.frames == other.frames
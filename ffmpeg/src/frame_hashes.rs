@@ -0,0 +1,61 @@
+use std::process::{Command, Stdio};
+
+use crate::decoder::Decoder;
+use crate::error::FrameHashError;
+use crate::ffprobe::probe_pix_fmt;
+
+// an md5 of every frame the decoder produces from the file, in order, to hold against another decoder's
+// hardware decoders hand their frames back in a layout of their own (i.e. nv12 for yuv420p), so every decode
+// is converted to the file's pixel format first; that only moves the samples around, it doesn't change them
+pub fn hash_frames(input_file: &String, decoder: &Decoder) -> Result<Vec<String>, FrameHashError> {
+    let pix_fmt = probe_pix_fmt(input_file)?;
+    let ffmpeg = Command::new("ffmpeg")
+        .args(["-v", "error"])
+        .args(decoder.get_input_args().split_whitespace())
+        .args(["-i", input_file.as_str(), "-map", "0:v:0"])
+        .args(["-vf", format!("format={}", pix_fmt).as_str()])
+        .args(["-f", "framemd5", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(FrameHashError::Spawn)?;
+
+    if !ffmpeg.status.success() {
+        return Err(FrameHashError::DecodeFailed(
+            String::from_utf8_lossy(&ffmpeg.stderr).trim().to_string(),
+        ));
+    }
+
+    return Ok(extract_frame_hashes(&String::from_utf8_lossy(
+        &ffmpeg.stdout,
+    )));
+}
+
+// after its '#' header, framemd5 writes a line per frame: stream, dts, pts, duration, size, md5
+fn extract_frame_hashes(output: &str) -> Vec<String> {
+    return output
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.rsplit(',').next())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_hashes::extract_frame_hashes;
+
+    #[test]
+    fn extract_frame_hashes_test() {
+        let output = "#format: frame checksums\n#version: 2\n#hash: MD5\n#tb 0: 1/60\n#media_type 0: video\n#codec_id 0: rawvideo\n#dimensions 0: 1920x1080\n#sar 0: 1/1\n#stream#, dts,        pts, duration,     size, hash\n0,          0,          0,        1,  3110400, 5f7a0a2ba1a0c5d4e3c1d2b8a6f2e9c1\n0,          1,          1,        1,  3110400, 9e107d9d372bb6826bd81d3542a419d6\n";
+        assert_eq!(
+            extract_frame_hashes(output),
+            vec![
+                "5f7a0a2ba1a0c5d4e3c1d2b8a6f2e9c1",
+                "9e107d9d372bb6826bd81d3542a419d6"
+            ]
+        );
+        assert!(extract_frame_hashes("#format: frame checksums\n").is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+// frame rates as ffmpeg reports them, i.e. 60000/1001 instead of a rounded 59.94
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl FrameRate {
+    pub fn new(num: u32, den: u32) -> Self {
+        return Self { num, den };
+    }
+
+    // accepts both the rational form ffprobe uses (60000/1001) and plain integers (60)
+    pub fn parse(input: &str) -> Option<FrameRate> {
+        let splits = input.trim().split('/').collect::<Vec<&str>>();
+        let num = splits.first()?.trim().parse::<u32>().ok()?;
+        let den = match splits.get(1) {
+            Some(den) => den.trim().parse::<u32>().ok()?,
+            None => 1,
+        };
+
+        // ffprobe reports 0/0 for streams where it couldn't determine the frame rate
+        if den == 0 {
+            return None;
+        }
+
+        return Some(FrameRate::new(num, den));
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        if self.den == 0 {
+            return 0.0;
+        }
+
+        return self.num as f64 / self.den as f64;
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.num == 0;
+    }
+
+    // what we hand to ffmpeg's -r, keeping the exact rational value
+    pub fn to_ffmpeg_arg(&self) -> String {
+        if self.den == 1 {
+            return self.num.to_string();
+        }
+
+        return format!("{}/{}", self.num, self.den);
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        return FrameRate::new(0, 1);
+    }
+}
+
+impl From<u32> for FrameRate {
+    fn from(fps: u32) -> Self {
+        return FrameRate::new(fps, 1);
+    }
+}
+
+impl fmt::Display for FrameRate {
+    // whole frame rates print as-is, fractional ones as 59.94, 29.97, etc
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 || self.num % self.den == 0 {
+            return write!(f, "{}", self.as_f64() as u32);
+        }
+
+        return write!(f, "{:.2}", self.as_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_rate::FrameRate;
+
+    #[test]
+    fn parse_rational_test() {
+        assert_eq!(
+            FrameRate::parse("60000/1001"),
+            Some(FrameRate::new(60000, 1001))
+        );
+    }
+
+    #[test]
+    fn parse_integer_test() {
+        assert_eq!(FrameRate::parse("144"), Some(FrameRate::new(144, 1)));
+    }
+
+    #[test]
+    fn parse_invalid_test() {
+        assert_eq!(FrameRate::parse("0/0"), None);
+        assert_eq!(FrameRate::parse("N/A"), None);
+    }
+
+    #[test]
+    fn to_string_test() {
+        assert_eq!(FrameRate::new(60, 1).to_string(), "60");
+        assert_eq!(FrameRate::new(120, 2).to_string(), "60");
+        assert_eq!(FrameRate::new(60000, 1001).to_string(), "59.94");
+        assert_eq!(FrameRate::new(30000, 1001).to_string(), "29.97");
+    }
+
+    #[test]
+    fn to_ffmpeg_arg_test() {
+        assert_eq!(FrameRate::new(60, 1).to_ffmpeg_arg(), "60");
+        assert_eq!(FrameRate::new(60000, 1001).to_ffmpeg_arg(), "60000/1001");
+    }
+}
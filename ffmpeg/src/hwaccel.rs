@@ -0,0 +1,126 @@
+use std::fmt;
+
+use codecs::vendor::Vendor;
+
+use crate::decoder::Decoder;
+
+// ffmpeg's hwaccels are tied to the os' video apis as much as to the gpu
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetOs {
+    Windows,
+    Linux,
+    MacOs,
+}
+
+impl TargetOs {
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            return TargetOs::Windows;
+        } else if cfg!(target_os = "macos") {
+            return TargetOs::MacOs;
+        }
+
+        return TargetOs::Linux;
+    }
+}
+
+impl fmt::Display for TargetOs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            TargetOs::Windows => write!(f, "Windows"),
+            TargetOs::Linux => write!(f, "Linux"),
+            TargetOs::MacOs => write!(f, "macOS"),
+        };
+    }
+}
+
+// the hwaccels that decode on the given vendor's gpu, most preferred first
+pub fn get_hwaccel_candidates(vendor: Vendor, os: TargetOs) -> Vec<&'static str> {
+    return match (vendor, os) {
+        // there's no gpu to decode on when the encode didn't use one
+        (Vendor::Unknown, _) => vec![],
+        // every gpu in a mac decodes through videotoolbox
+        (_, TargetOs::MacOs) => vec!["videotoolbox"],
+        (Vendor::Apple, _) => vec![],
+        (Vendor::Nvidia, _) => vec!["cuda"],
+        (Vendor::AMD, TargetOs::Linux) => vec!["vaapi"],
+        (Vendor::IntelQSV, TargetOs::Linux) => vec!["vaapi", "qsv"],
+        (Vendor::AMD, TargetOs::Windows) => vec!["d3d11va", "dxva2"],
+        (Vendor::IntelQSV, TargetOs::Windows) => vec!["qsv", "d3d11va", "dxva2"],
+    };
+}
+
+// the first of the vendor's hwaccels that ffmpeg has, or the cpu when it has none of them
+// without the list of what ffmpeg has, the most preferred is taken on faith
+pub fn resolve_decoder(vendor: Vendor, os: TargetOs, available: Option<&Vec<String>>) -> Decoder {
+    return get_hwaccel_candidates(vendor, os)
+        .into_iter()
+        .find(|hwaccel| {
+            available
+                .map(|available| available.iter().any(|name| name == hwaccel))
+                .unwrap_or(true)
+        })
+        .map(|hwaccel| Decoder::Hwaccel(hwaccel.to_string()))
+        .unwrap_or(Decoder::Software);
+}
+
+#[cfg(test)]
+mod tests {
+    use codecs::vendor::Vendor;
+
+    use crate::decoder::Decoder;
+    use crate::hwaccel::{get_hwaccel_candidates, resolve_decoder, TargetOs};
+
+    fn hwaccel(name: &str) -> Decoder {
+        return Decoder::Hwaccel(String::from(name));
+    }
+
+    #[test]
+    fn candidates_test() {
+        assert_eq!(
+            get_hwaccel_candidates(Vendor::AMD, TargetOs::Windows),
+            vec!["d3d11va", "dxva2"]
+        );
+        assert_eq!(
+            get_hwaccel_candidates(Vendor::IntelQSV, TargetOs::Linux),
+            vec!["vaapi", "qsv"]
+        );
+        assert!(get_hwaccel_candidates(Vendor::Apple, TargetOs::Linux).is_empty());
+        assert!(get_hwaccel_candidates(Vendor::Unknown, TargetOs::MacOs).is_empty());
+    }
+
+    #[test]
+    fn verified_test() {
+        let available = vec![String::from("cuda"), String::from("dxva2")];
+
+        // d3d11va is preferred, but this build only has dxva2
+        assert_eq!(
+            resolve_decoder(Vendor::AMD, TargetOs::Windows, Some(&available)),
+            hwaccel("dxva2")
+        );
+        assert_eq!(
+            resolve_decoder(Vendor::Nvidia, TargetOs::Windows, Some(&available)),
+            hwaccel("cuda")
+        );
+        assert_eq!(
+            resolve_decoder(Vendor::AMD, TargetOs::Linux, Some(&available)),
+            Decoder::Software
+        );
+        assert_eq!(
+            resolve_decoder(Vendor::Nvidia, TargetOs::Linux, Some(&vec![])),
+            Decoder::Software
+        );
+    }
+
+    #[test]
+    fn unverified_test() {
+        assert_eq!(
+            resolve_decoder(Vendor::IntelQSV, TargetOs::Linux, None),
+            hwaccel("vaapi")
+        );
+        assert_eq!(
+            resolve_decoder(Vendor::Unknown, TargetOs::Linux, None),
+            Decoder::Software
+        );
+    }
+}
@@ -1,4 +1,12 @@
 pub mod args;
+pub mod decoder;
+pub mod encoded_stream;
+pub mod error;
 pub mod ffprobe;
+pub mod frame_hashes;
+pub mod frame_rate;
+pub mod hwaccel;
 pub mod metadata;
+pub mod packet;
 pub mod report_files;
+pub mod source;
@@ -1,6 +1,8 @@
+use crate::frame_rate::FrameRate;
+
 #[derive(Copy, Clone)]
 pub struct MetaData {
-    pub fps: u32,
+    pub fps: FrameRate,
     pub frames: u64,
     pub width: u32,
     pub height: u32,
@@ -9,7 +11,7 @@ pub struct MetaData {
 impl MetaData {
     pub fn new() -> Self {
         return Self {
-            fps: 0,
+            fps: FrameRate::default(),
             frames: 0,
             width: 0,
             height: 0,
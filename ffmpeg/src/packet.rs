@@ -0,0 +1,18 @@
+// one compressed frame of an encoded video stream, as ffprobe reports it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Packet {
+    // decode timestamp, in seconds
+    pub time: f64,
+    pub bytes: u64,
+    pub keyframe: bool,
+}
+
+impl Packet {
+    pub fn new(time: f64, bytes: u64, keyframe: bool) -> Self {
+        return Self {
+            time,
+            bytes,
+            keyframe,
+        };
+    }
+}
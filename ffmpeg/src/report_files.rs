@@ -1,35 +1,19 @@
 use std::ffi::c_float;
-use std::fs;
-use std::fs::{DirEntry, File};
-use std::io::BufRead;
 use std::num::ParseFloatError;
-use std::path::PathBuf;
 
-use filetime::FileTime;
 use regex::Regex;
-use rev_buf_reader::RevBufReader;
-
-pub fn get_latest_ffmpeg_report_file() -> PathBuf {
-    let log_entries = get_logs_in_directory(".");
-    let log_file = get_latest_log(log_entries);
-    return log_file.unwrap().path();
-}
 
 pub fn extract_vmaf_score(line: &str) -> Result<c_float, ParseFloatError> {
     return capture_group(line, r"VMAF score: (\d+\.\d+)").parse::<c_float>();
 }
 
-pub fn read_last_line_at(line_number: i32) -> String {
-    let log_file = File::open(get_latest_ffmpeg_report_file()).unwrap();
-    let reader = RevBufReader::new(log_file);
-    let mut lines = reader.lines();
-
-    // read from bottom to just before the line we need
-    for _ in 0..line_number - 1 {
-        lines.next().unwrap().unwrap();
-    }
-
-    return lines.next().unwrap().unwrap();
+// ffmpeg sums up what it muxed as it exits, i.e. 'video:2048KiB audio:0KiB ...'; older builds write kB for the same 1024 bytes
+pub fn extract_video_size(output: &str) -> Option<u64> {
+    let line = output.lines().rev().find(|line| line.contains("video:"))?;
+    let kibibytes = capture_group(line, r"video:\s*([0-9]+)\s*(?:KiB|kB)")
+        .parse::<u64>()
+        .ok()?;
+    return Some(kibibytes * 1024);
 }
 
 pub fn capture_group(str: &str, regex: &str) -> String {
@@ -42,52 +26,11 @@ pub fn capture_group(str: &str, regex: &str) -> String {
     };
 }
 
-fn get_logs_in_directory(dir: &str) -> Vec<DirEntry> {
-    // Only match ffmpeg log files
-    let re = Regex::new(r"^ffmpeg.*?\.log$").unwrap();
-    let paths = fs::read_dir(dir).unwrap();
-    return paths
-        .filter_map(|e| e.ok())
-        .filter(|p| {
-            p.file_type().unwrap().is_file() && re.is_match(p.file_name().to_str().unwrap())
-        })
-        .collect::<Vec<DirEntry>>();
-}
-
-fn get_latest_log(log_entries: Vec<DirEntry>) -> Option<DirEntry> {
-    let mut log_file: Option<DirEntry> = None;
-    let mut latest_time = FileTime::zero();
-
-    // defining file_time here so we can extend it's scope
-    let mut file_time;
-
-    for entry in log_entries.into_iter() {
-        let metadata = entry.metadata().unwrap();
-        if metadata.created().is_ok() {
-            file_time = FileTime::from_system_time(metadata.created().unwrap());
-        } else {
-            // Platforms that don't support metadata.created() will use the
-            // last modification time as a fallback
-            file_time = FileTime::from_last_modification_time(&metadata);
-        }
-
-        if file_time > latest_time {
-            latest_time = file_time;
-            log_file = Some(entry);
-        }
-    }
-    return log_file;
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::report_files::{extract_vmaf_score, get_latest_log, get_logs_in_directory};
-    use std::fs;
-    use std::path::Path;
+    use crate::report_files::{extract_video_size, extract_vmaf_score};
 
     static VMAF_LINE: &str = "[Parsed_libvmaf_0 @ 00000169cf14fc00] VMAF score: 98.644730";
-    static EXPECTED_DIR_CREATED_MSG: &str = "Unable to create temporary dir for testing";
-    static EXPECTED_TMP_FILE_CREATED_MSG: &str = "Unable to create temporary file for testing";
 
     #[test]
     fn extract_vmaf_score_test() {
@@ -97,68 +40,13 @@ mod tests {
     }
 
     #[test]
-    fn log_files_only_test() {
-        let test_log_dir_path = Path::new("./log-test");
-        let test_log_dir_path_str = test_log_dir_path.to_str().unwrap();
-
-        if test_log_dir_path.exists() {
-            fs::remove_dir_all(test_log_dir_path_str).unwrap();
-        }
-        fs::create_dir(test_log_dir_path_str).expect(EXPECTED_DIR_CREATED_MSG);
-        let ffmpeg_log_1 = test_log_dir_path_str.to_string() + "/ffmpeg-1.log";
-        fs::File::create(ffmpeg_log_1).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-
-        let ffmpeg_log_2 = test_log_dir_path_str.to_string() + "/ffmpeg-2.log";
-        fs::File::create(ffmpeg_log_2).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-        let some_other_log = test_log_dir_path_str.to_string() + "/some-other.log";
-        fs::File::create(some_other_log).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-        let text_file = test_log_dir_path_str.to_string() + "/diff-file-ext.txt";
-        fs::File::create(text_file).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-        let log_files = get_logs_in_directory(test_log_dir_path_str);
-        // Only the ffmpeg*.log files should be in log_files
-        assert!(log_files.len() == 2);
-        for file in log_files {
-            assert!(file.file_name().to_str().unwrap().contains("ffmpeg"));
-            assert!(file
-                .path()
-                .extension()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .contains("log"));
-        }
-
-        fs::remove_dir_all(test_log_dir_path_str).unwrap();
-    }
-
-    #[test]
-    fn latest_log_file_test() {
-        let test_latest_log_dir_path = Path::new("./latest-log-test");
-        let test_latest_log_dir_path_str = test_latest_log_dir_path.to_str().unwrap();
-        if test_latest_log_dir_path.exists() {
-            fs::remove_dir_all(test_latest_log_dir_path_str).unwrap();
-        }
-
-        fs::create_dir(test_latest_log_dir_path_str).expect(EXPECTED_DIR_CREATED_MSG);
-        let old_log_path_str = test_latest_log_dir_path_str.to_string() + "/ffmpeg-1.log";
-        let old_log_file = fs::File::create(old_log_path_str).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-        old_log_file.sync_all().unwrap();
-
-        // Added sleep to ensure the 2nd file gets created at a later timestamp
-        std::thread::sleep(std::time::Duration::from_millis(3000));
-
-        let new_log_path_str = test_latest_log_dir_path_str.to_string() + "/ffmpeg-2.log";
-        let new_log_file = fs::File::create(new_log_path_str).expect(EXPECTED_TMP_FILE_CREATED_MSG);
-        new_log_file.sync_all().unwrap();
-        let log_files = get_logs_in_directory(test_latest_log_dir_path_str);
-        let latest_log_file = get_latest_log(log_files);
-        // assert latest log file name
-        assert!(latest_log_file
-            .unwrap()
-            .file_name()
-            .to_str()
-            .unwrap()
-            .contains("ffmpeg-2.log"));
-        fs::remove_dir_all(test_latest_log_dir_path.to_str().unwrap()).unwrap();
+    fn extract_video_size_test() {
+        let output = "frame=  240 fps=480 q=-0.0 Lsize=N/A time=00:00:04.00 bitrate=N/A speed=8x\n[out#0/null @ 0x55d5] video:2048KiB audio:0KiB subtitle:0KiB other streams:0KiB global headers:0KiB muxing overhead: unknown";
+        assert_eq!(extract_video_size(output), Some(2048 * 1024));
+        assert_eq!(
+            extract_video_size("video:112kB audio:0kB subtitle:0kB"),
+            Some(112 * 1024)
+        );
+        assert_eq!(extract_video_size("Conversion failed!"), None);
     }
 }
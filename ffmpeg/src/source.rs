@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::SourceError;
+
+// raw sources can be fed to the encoder directly, everything else gets decoded up front
+static RAW_EXTENSION: &str = "y4m";
+
+// suffix given to the raw intermediate we decode compressed sources into
+static DECODED_SUFFIX: &str = "-decoded";
+
+// suffix given to an encode's output, when it's kept around to be inspected; mkv takes every codec
+static ENCODED_SUFFIX: &str = "-encoded.mkv";
+
+pub fn is_raw_source(input_file: &str) -> bool {
+    return Path::new(input_file)
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case(RAW_EXTENSION))
+        .unwrap_or(false);
+}
+
+// path of the raw intermediate for a compressed source, next to the source so it's on the same drive
+pub fn get_decoded_source_path(input_file: &str) -> String {
+    let path = Path::new(input_file);
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let decoded = path.with_file_name(format!("{}{}.{}", stem, DECODED_SUFFIX, RAW_EXTENSION));
+    return decoded.to_str().unwrap().to_string();
+}
+
+// path an encode of the source is written to when its output is needed after the fact, next to the source
+pub fn get_encoded_output_path(input_file: &str) -> String {
+    let path = Path::new(input_file);
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let encoded = path.with_file_name(format!("{}{}", stem, ENCODED_SUFFIX));
+    return encoded.to_str().unwrap().to_string();
+}
+
+// mp4/mkv/mov/ivf sources are decoded to a raw y4m before any timed encode, so decode cost doesn't
+// end up in the encoder measurement; the decoded file is cached and re-used until the source changes
+pub fn prepare_source(input_file: &String, verbose: bool) -> Result<String, SourceError> {
+    if is_raw_source(input_file) {
+        return Ok(input_file.clone());
+    }
+
+    let decoded = get_decoded_source_path(input_file);
+    if is_cache_fresh(input_file, &decoded) {
+        if verbose {
+            println!("V: Re-using previously decoded source: {}", decoded);
+        }
+
+        return Ok(decoded);
+    }
+
+    println!(
+        "Decoding [{}] to a raw source before benchmarking; this only happens once per source file...",
+        input_file
+    );
+
+    // -strict -1 allows y4m to carry 10-bit and other non-standard pixel formats through untouched
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i", input_file.as_str()])
+        .args(["-map", "0:v:0", "-strict", "-1", "-f", "yuv4mpegpipe"])
+        .arg(decoded.as_str())
+        .stdout(Stdio::null())
+        .status()
+        .map_err(SourceError::Spawn)?;
+
+    if !status.success() {
+        // don't leave a partial file around that would look like a valid cache next time
+        let _ = fs::remove_file(&decoded);
+        return Err(SourceError::DecodeFailed(input_file.clone()));
+    }
+
+    return Ok(decoded);
+}
+
+fn is_cache_fresh(input_file: &str, decoded: &str) -> bool {
+    let source_modified = fs::metadata(input_file).and_then(|m| m.modified());
+    let decoded_modified = fs::metadata(decoded).and_then(|m| m.modified());
+
+    return match (source_modified, decoded_modified) {
+        (Ok(source), Ok(decoded)) => decoded >= source,
+        _ => false,
+    };
+}
+
+// swaps the extension of a file, leaving the rest of the path untouched
+pub fn with_extension(input_file: &str, extension: &str) -> String {
+    return PathBuf::from(input_file)
+        .with_extension(extension)
+        .to_str()
+        .unwrap()
+        .to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{
+        get_decoded_source_path, get_encoded_output_path, is_raw_source, with_extension,
+    };
+
+    #[test]
+    fn raw_source_test() {
+        assert!(is_raw_source("1080-60.y4m"));
+        assert!(is_raw_source("sources/1080-60.Y4M"));
+        assert!(!is_raw_source("gameplay.mp4"));
+        assert!(!is_raw_source("gameplay.mkv"));
+        assert!(!is_raw_source("no-extension"));
+    }
+
+    #[test]
+    fn decoded_source_path_test() {
+        assert_eq!(
+            get_decoded_source_path("sources/gameplay.mkv"),
+            "sources/gameplay-decoded.y4m"
+        );
+    }
+
+    #[test]
+    fn encoded_output_path_test() {
+        assert_eq!(
+            get_encoded_output_path("sources/1080-60.y4m"),
+            "sources/1080-60-encoded.mkv"
+        );
+    }
+
+    #[test]
+    fn with_extension_test() {
+        assert_eq!(
+            with_extension("some.y4m.dir/1080-60.y4m", "mp4"),
+            "some.y4m.dir/1080-60.mp4"
+        );
+    }
+}
@@ -1,16 +1,52 @@
-use nvml_wrapper::enum_wrappers::device::Brand;
+use codecs::vendor::Vendor;
 
 // our own wrapper for device information to get around lifetime issues
+#[derive(Clone, Debug, PartialEq)]
 pub struct Device {
-    pub brand: Brand,
+    pub vendor: Vendor,
     pub name: String,
+    // kernel driver bound to the device, i.e. amdgpu, i915, nvidia
+    pub driver: String,
+    pub driver_version: Option<String>,
+    pub pci_vendor_id: u16,
+    pub pci_device_id: u16,
+    // i.e. 0000:01:00.0
+    pub pci_bus_id: String,
+    // i.e. /dev/dri/renderD128, what vaapi and qsv open
+    pub render_node: Option<String>,
+    // i.e. card0 under /sys/class/drm; empty when the GPU wasn't found through sysfs
+    pub card: String,
+    // position among the GPU's of the same vendor, which is what an encoder's '-gpu' option counts
+    pub index: u8,
 }
 
 impl Device {
     pub fn new() -> Self {
         return Self {
-            brand: Brand::Unknown,
+            vendor: Vendor::Unknown,
             name: String::from(""),
+            driver: String::from(""),
+            driver_version: None,
+            pci_vendor_id: 0,
+            pci_device_id: 0,
+            pci_bus_id: String::from(""),
+            render_node: None,
+            card: String::from(""),
+            index: 0,
         };
     }
+
+    pub fn to_string(&self) -> String {
+        let mut description = self.name.clone();
+        if !self.driver.is_empty() {
+            description.push_str(&format!(" [{}", self.driver));
+            if let Some(version) = &self.driver_version {
+                description.push_str(&format!(" {}", version));
+            }
+
+            description.push(']');
+        }
+
+        return description;
+    }
 }
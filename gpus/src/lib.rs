@@ -1,24 +1,121 @@
+use std::path::Path;
+
 use nvml_wrapper::Nvml;
 
+use codecs::vendor::Vendor;
+
+use crate::device::Device;
+use crate::sysfs::{enumerate_drm_devices, SYSFS_ROOT};
+
 pub mod device;
+pub mod sysfs;
+pub mod telemetry;
 
-pub fn get_gpus() -> Vec<String> {
-    let nvml = match Nvml::init() {
-        Ok(nvml) => nvml,
-        Err(_) => {
-            println!("Warning: Unable to auto-detect multiple GPU's, falling back to using first GPU or provided one via '-gpu' option if specified");
-            return Vec::new();
-        }
-    };
+pub fn get_gpus() -> Vec<Device> {
+    let gpus = detect_gpus();
+    if gpus.is_empty() {
+        println!("Warning: Unable to auto-detect multiple GPU's, falling back to using first GPU or provided one via '-gpu' option if specified");
+    }
+
+    return gpus;
+}
 
-    let device_count = nvml.device_count().unwrap();
+// name and driver of every gpu, for the host details in the results; quiet when none can be found
+pub fn get_gpu_descriptions() -> Vec<String> {
+    return detect_gpus().iter().map(|gpu| gpu.to_string()).collect();
+}
 
+// sysfs sees every vendor's GPU's on linux; nvml only adds nicer names for nvidia ones, or is all we have elsewhere
+fn detect_gpus() -> Vec<Device> {
+    let mut devices = enumerate_drm_devices(Path::new(SYSFS_ROOT));
+    let nvml = Nvml::init().ok();
+
+    if devices.is_empty() {
+        return match nvml {
+            Some(nvml) => get_nvml_devices(&nvml),
+            None => Vec::new(),
+        };
+    }
+
+    if let Some(nvml) = nvml {
+        enrich_from_nvml(&nvml, &mut devices);
+    }
+
+    return devices;
+}
+
+fn get_nvml_devices(nvml: &Nvml) -> Vec<Device> {
+    let device_count = match nvml.device_count() {
+        Ok(count) => count,
+        Err(_) => return Vec::new(),
+    };
+
+    let driver_version = nvml.sys_driver_version().ok();
     let mut list = Vec::new();
 
     for i in 0..device_count {
-        let nvml_device = nvml.device_by_index(i).unwrap();
-        list.push(nvml_device.name().unwrap());
+        // a device we can't query is one we can't offer as a choice either
+        let device = match nvml.device_by_index(i) {
+            Ok(device) => device,
+            Err(_) => continue,
+        };
+        let name = match device.name() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+
+        let mut gpu = Device::new();
+        gpu.vendor = Vendor::Nvidia;
+        gpu.name = name;
+        gpu.driver = String::from("nvidia");
+        gpu.driver_version = driver_version.clone();
+        gpu.index = list.len() as u8;
+        if let Ok(pci_info) = device.pci_info() {
+            gpu.pci_vendor_id = (pci_info.pci_device_id & 0xffff) as u16;
+            gpu.pci_device_id = (pci_info.pci_device_id >> 16) as u16;
+            gpu.pci_bus_id = normalize_bus_id(&pci_info.bus_id);
+        }
+
+        list.push(gpu);
     }
 
     return list;
 }
+
+// sysfs only knows nvidia cards by their PCI ids, nvml knows their names
+fn enrich_from_nvml(nvml: &Nvml, devices: &mut Vec<Device>) {
+    for nvml_device in get_nvml_devices(nvml) {
+        let device = devices
+            .iter_mut()
+            .find(|d| d.vendor == Vendor::Nvidia && d.pci_bus_id == nvml_device.pci_bus_id);
+
+        if let Some(device) = device {
+            device.name = nvml_device.name;
+            device.driver_version = nvml_device.driver_version;
+        }
+    }
+}
+
+// nvml reports an 8 digit PCI domain, i.e. 00000000:01:00.0, sysfs a 4 digit one
+fn normalize_bus_id(bus_id: &str) -> String {
+    let bus_id = bus_id.to_lowercase();
+    return match bus_id.split_once(':') {
+        Some((domain, rest)) => match u32::from_str_radix(domain, 16) {
+            Ok(domain) => format!("{:04x}:{}", domain, rest),
+            Err(_) => bus_id.clone(),
+        },
+        None => bus_id.clone(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::normalize_bus_id;
+
+    #[test]
+    fn normalize_bus_id_test() {
+        assert_eq!(normalize_bus_id("00000000:01:00.0"), "0000:01:00.0");
+        assert_eq!(normalize_bus_id("00000000:0A:00.0"), "0000:0a:00.0");
+        assert_eq!(normalize_bus_id("0000:03:00.0"), "0000:03:00.0");
+    }
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::Path;
+
+use codecs::vendor::Vendor;
+
+use crate::device::Device;
+
+pub static SYSFS_ROOT: &str = "/sys";
+
+const NVIDIA_VENDOR_ID: u16 = 0x10de;
+const AMD_VENDOR_ID: u16 = 0x1002;
+const INTEL_VENDOR_ID: u16 = 0x8086;
+
+// every GPU the kernel exposes under <sysfs>/class/drm, ordered by card number; empty where there's no sysfs
+pub fn enumerate_drm_devices(sysfs_root: &Path) -> Vec<Device> {
+    let entries = match fs::read_dir(sysfs_root.join("class/drm")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut cards: Vec<(u32, String)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter_map(|name| Some((get_card_number(&name)?, name)))
+        .collect();
+    cards.sort();
+
+    let mut devices: Vec<Device> = Vec::new();
+    for (_, card) in cards {
+        let device_dir = sysfs_root.join("class/drm").join(&card).join("device");
+        let mut device = match read_drm_device(sysfs_root, &device_dir) {
+            Some(device) => device,
+            None => continue,
+        };
+
+        device.card = card;
+        device.index = devices.iter().filter(|d| d.vendor == device.vendor).count() as u8;
+        devices.push(device);
+    }
+
+    return devices;
+}
+
+pub fn get_vendor_for_pci_id(pci_vendor_id: u16) -> Vendor {
+    return match pci_vendor_id {
+        NVIDIA_VENDOR_ID => Vendor::Nvidia,
+        AMD_VENDOR_ID => Vendor::AMD,
+        INTEL_VENDOR_ID => Vendor::IntelQSV,
+        _ => Vendor::Unknown,
+    };
+}
+
+fn get_vendor_name(vendor: Vendor) -> &'static str {
+    return match vendor {
+        Vendor::Nvidia => "NVIDIA",
+        Vendor::AMD => "AMD",
+        Vendor::IntelQSV => "Intel",
+        Vendor::Apple => "Apple",
+        Vendor::Unknown => "Unknown",
+    };
+}
+
+// card0 is a GPU, card0-HDMI-A-1 is one of its connectors
+fn get_card_number(name: &str) -> Option<u32> {
+    return name.strip_prefix("card")?.parse::<u32>().ok();
+}
+
+// None for anything that isn't a PCI GPU we know how to encode on, i.e. simpledrm or a virtual display adapter
+fn read_drm_device(sysfs_root: &Path, device_dir: &Path) -> Option<Device> {
+    let pci_vendor_id = read_hex_id(&device_dir.join("vendor"))?;
+    let vendor = get_vendor_for_pci_id(pci_vendor_id);
+    if vendor == Vendor::Unknown {
+        return None;
+    }
+
+    let pci_device_id = read_hex_id(&device_dir.join("device")).unwrap_or(0);
+    let driver = read_link_name(&device_dir.join("driver")).unwrap_or_default();
+    let driver_version =
+        fs::read_to_string(sysfs_root.join("module").join(&driver).join("version"))
+            .ok()
+            .map(|version| version.trim().to_string())
+            .filter(|version| !driver.is_empty() && !version.is_empty());
+
+    // amdgpu knows the marketing name, for everything else the PCI ids are all we have without a pci.ids database
+    let name = fs::read_to_string(device_dir.join("product_name"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(format!(
+            "{} GPU [{:04x}:{:04x}]",
+            get_vendor_name(vendor),
+            pci_vendor_id,
+            pci_device_id
+        ));
+
+    return Some(Device {
+        vendor,
+        name,
+        driver,
+        driver_version,
+        pci_vendor_id,
+        pci_device_id,
+        pci_bus_id: read_link_name(device_dir).unwrap_or_default(),
+        render_node: get_render_node(device_dir),
+        card: String::new(),
+        index: 0,
+    });
+}
+
+// sysfs ids look like 0x10de
+fn read_hex_id(path: &Path) -> Option<u16> {
+    let content = fs::read_to_string(path).ok()?;
+    let id = content.trim().trim_start_matches("0x");
+    return u16::from_str_radix(id, 16).ok();
+}
+
+fn read_link_name(path: &Path) -> Option<String> {
+    let target = fs::read_link(path).ok()?;
+    return Some(target.file_name()?.to_string_lossy().to_string());
+}
+
+fn get_render_node(device_dir: &Path) -> Option<String> {
+    let entries = fs::read_dir(device_dir.join("drm")).ok()?;
+    let render_node = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .find(|name| name.starts_with("renderD"))?;
+
+    return Some(format!("/dev/dri/{}", render_node));
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    use codecs::vendor::Vendor;
+
+    use crate::sysfs::{enumerate_drm_devices, get_vendor_for_pci_id};
+
+    fn get_sysfs_root(test_name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fake-sysfs-{}", test_name));
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+
+        fs::create_dir_all(root.join("class/drm")).unwrap();
+        return root;
+    }
+
+    // lays out a card the way the kernel does; the card's device is a link to its PCI device
+    fn add_card(
+        root: &Path,
+        card: &str,
+        bus_id: &str,
+        vendor: &str,
+        device: &str,
+        driver: &str,
+        render_node: &str,
+    ) {
+        let pci_dir = root.join("devices/pci0000:00").join(bus_id);
+        fs::create_dir_all(pci_dir.join("drm").join(render_node)).unwrap();
+        fs::write(pci_dir.join("vendor"), format!("{}\n", vendor)).unwrap();
+        fs::write(pci_dir.join("device"), format!("{}\n", device)).unwrap();
+
+        let driver_dir = root.join("bus/pci/drivers").join(driver);
+        fs::create_dir_all(&driver_dir).unwrap();
+        symlink(&driver_dir, pci_dir.join("driver")).unwrap();
+
+        let card_dir = root.join("class/drm").join(card);
+        fs::create_dir_all(&card_dir).unwrap();
+        symlink(&pci_dir, card_dir.join("device")).unwrap();
+    }
+
+    #[test]
+    fn enumerate_multi_vendor_test() {
+        let root = get_sysfs_root("multi-vendor");
+        add_card(
+            &root,
+            "card0",
+            "0000:00:02.0",
+            "0x8086",
+            "0x4680",
+            "i915",
+            "renderD128",
+        );
+        add_card(
+            &root,
+            "card1",
+            "0000:03:00.0",
+            "0x1002",
+            "0x744c",
+            "amdgpu",
+            "renderD129",
+        );
+        add_card(
+            &root,
+            "card2",
+            "0000:04:00.0",
+            "0x1002",
+            "0x73bf",
+            "amdgpu",
+            "renderD130",
+        );
+        fs::create_dir_all(root.join("class/drm/card1-DP-1")).unwrap();
+        fs::create_dir_all(root.join("module/amdgpu")).unwrap();
+        fs::write(root.join("module/amdgpu/version"), "6.3.6\n").unwrap();
+
+        let devices = enumerate_drm_devices(&root);
+        assert_eq!(devices.len(), 3);
+
+        assert_eq!(devices[0].vendor, Vendor::IntelQSV);
+        assert_eq!(devices[0].driver, "i915");
+        assert_eq!(devices[0].driver_version, None);
+        assert_eq!(devices[0].index, 0);
+        assert_eq!(
+            devices[0].render_node.as_deref(),
+            Some("/dev/dri/renderD128")
+        );
+        assert_eq!(devices[0].name, "Intel GPU [8086:4680]");
+
+        assert_eq!(devices[1].vendor, Vendor::AMD);
+        assert_eq!(devices[1].pci_device_id, 0x744c);
+        assert_eq!(devices[1].pci_bus_id, "0000:03:00.0");
+        assert_eq!(devices[1].driver_version.as_deref(), Some("6.3.6"));
+        assert_eq!(devices[1].index, 0);
+
+        // the second AMD card is the second one '-gpu' would count
+        assert_eq!(devices[2].vendor, Vendor::AMD);
+        assert_eq!(devices[2].index, 1);
+    }
+
+    #[test]
+    fn enumerate_skips_unknown_devices_test() {
+        let root = get_sysfs_root("unknown-devices");
+        // a virtio display adapter, and a card without any PCI information like simpledrm
+        add_card(
+            &root,
+            "card0",
+            "0000:00:01.0",
+            "0x1af4",
+            "0x1050",
+            "virtio-pci",
+            "renderD128",
+        );
+        fs::create_dir_all(root.join("class/drm/card1/device")).unwrap();
+        add_card(
+            &root,
+            "card2",
+            "0000:01:00.0",
+            "0x10de",
+            "0x2206",
+            "nvidia",
+            "renderD129",
+        );
+
+        let devices = enumerate_drm_devices(&root);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].vendor, Vendor::Nvidia);
+        assert_eq!(devices[0].index, 0);
+    }
+
+    #[test]
+    fn enumerate_product_name_test() {
+        let root = get_sysfs_root("product-name");
+        add_card(
+            &root,
+            "card0",
+            "0000:03:00.0",
+            "0x1002",
+            "0x744c",
+            "amdgpu",
+            "renderD128",
+        );
+        fs::write(
+            root.join("devices/pci0000:00/0000:03:00.0/product_name"),
+            "AMD Radeon RX 7900 XTX\n",
+        )
+        .unwrap();
+
+        let devices = enumerate_drm_devices(&root);
+        assert_eq!(devices[0].name, "AMD Radeon RX 7900 XTX");
+    }
+
+    #[test]
+    fn enumerate_without_sysfs_test() {
+        assert!(enumerate_drm_devices(Path::new("/does/not/exist")).is_empty());
+    }
+
+    #[test]
+    fn get_vendor_for_pci_id_test() {
+        assert_eq!(get_vendor_for_pci_id(0x10de), Vendor::Nvidia);
+        assert_eq!(get_vendor_for_pci_id(0x1002), Vendor::AMD);
+        assert_eq!(get_vendor_for_pci_id(0x8086), Vendor::IntelQSV);
+        assert_eq!(get_vendor_for_pci_id(0x1af4), Vendor::Unknown);
+    }
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nvml_wrapper::enum_wrappers::device::{Clock, TemperatureSensor};
+use nvml_wrapper::Nvml;
+
+use codecs::vendor::Vendor;
+
+use crate::sysfs::{enumerate_drm_devices, SYSFS_ROOT};
+
+const BYTES_IN_MB: f64 = 1024.0 * 1024.0;
+
+// one reading of the GPU; anything the driver doesn't expose is None
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TelemetrySample {
+    // % of time the video encoder block was busy, only nvml reports this
+    pub encoder_utilization: Option<f64>,
+    pub gpu_utilization: Option<f64>,
+    pub clock_mhz: Option<f64>,
+    pub temperature_c: Option<f64>,
+    pub power_watts: Option<f64>,
+    pub vram_used_mb: Option<f64>,
+}
+
+pub trait TelemetrySampler: Send {
+    fn sample(&mut self) -> TelemetrySample;
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MetricStats {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl MetricStats {
+    // None when there were no readings of this metric at all
+    pub fn from_values(values: &Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let avg = values.iter().sum::<f64>() / values.len() as f64;
+
+        return Some(Self { min, avg, max });
+    }
+
+    pub fn to_string(&self) -> String {
+        return format!("{:.0}/{:.0}/{:.0}", self.min, self.avg, self.max);
+    }
+}
+
+// min/avg/max of every metric sampled over the course of one encode
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GpuTelemetry {
+    pub encoder_utilization: Option<MetricStats>,
+    pub gpu_utilization: Option<MetricStats>,
+    pub clock_mhz: Option<MetricStats>,
+    pub temperature_c: Option<MetricStats>,
+    pub power_watts: Option<MetricStats>,
+    pub vram_used_mb: Option<MetricStats>,
+    pub samples: usize,
+}
+
+impl GpuTelemetry {
+    pub fn from_samples(samples: &Vec<TelemetrySample>) -> Self {
+        let stats_of = |metric: fn(&TelemetrySample) -> Option<f64>| {
+            return MetricStats::from_values(&samples.iter().filter_map(metric).collect());
+        };
+
+        return Self {
+            encoder_utilization: stats_of(|s| s.encoder_utilization),
+            gpu_utilization: stats_of(|s| s.gpu_utilization),
+            clock_mhz: stats_of(|s| s.clock_mhz),
+            temperature_c: stats_of(|s| s.temperature_c),
+            power_watts: stats_of(|s| s.power_watts),
+            vram_used_mb: stats_of(|s| s.vram_used_mb),
+            samples: samples.len(),
+        };
+    }
+
+    // i.e. 'Encoder 45/52/60% | GPU 30/35/41% | Clock 1905/1950/1980MHz', as min/avg/max; skips what wasn't sampled
+    pub fn to_string(&self) -> String {
+        let metrics = [
+            ("Encoder", self.encoder_utilization, "%"),
+            ("GPU", self.gpu_utilization, "%"),
+            ("Clock", self.clock_mhz, "MHz"),
+            ("Temp", self.temperature_c, "C"),
+            ("Power", self.power_watts, "W"),
+            ("VRAM", self.vram_used_mb, "MB"),
+        ];
+
+        let described: Vec<String> = metrics
+            .iter()
+            .filter_map(|(name, stats, unit)| {
+                stats.map(|stats| format!("{} {}{}", name, stats.to_string(), unit))
+            })
+            .collect();
+
+        if described.is_empty() {
+            return String::from("no readings");
+        }
+
+        return described.join(" | ");
+    }
+}
+
+// the sampler for the GPU an encoder's '-gpu' index points at; None when there's no way to read it
+pub fn get_sampler_for(vendor: Vendor, gpu: u8) -> Option<Box<dyn TelemetrySampler>> {
+    if vendor == Vendor::Nvidia {
+        if let Ok(nvml) = Nvml::init() {
+            return Some(Box::new(NvmlSampler::new(nvml, gpu as u32)));
+        }
+    }
+
+    let device = enumerate_drm_devices(Path::new(SYSFS_ROOT))
+        .into_iter()
+        .find(|device| device.vendor == vendor && device.index == gpu)?;
+
+    let card_dir = Path::new(SYSFS_ROOT).join("class/drm").join(device.card);
+    return Some(Box::new(SysfsSampler::new(&card_dir)));
+}
+
+pub struct NvmlSampler {
+    nvml: Nvml,
+    index: u32,
+}
+
+impl NvmlSampler {
+    pub fn new(nvml: Nvml, index: u32) -> Self {
+        return Self { nvml, index };
+    }
+}
+
+impl TelemetrySampler for NvmlSampler {
+    fn sample(&mut self) -> TelemetrySample {
+        // the device borrows nvml, so it's looked up again for every sample
+        let device = match self.nvml.device_by_index(self.index) {
+            Ok(device) => device,
+            Err(_) => return TelemetrySample::default(),
+        };
+
+        return TelemetrySample {
+            encoder_utilization: device
+                .encoder_utilization()
+                .ok()
+                .map(|u| u.utilization as f64),
+            gpu_utilization: device.utilization_rates().ok().map(|u| u.gpu as f64),
+            clock_mhz: device.clock_info(Clock::Graphics).ok().map(|c| c as f64),
+            temperature_c: device
+                .temperature(TemperatureSensor::Gpu)
+                .ok()
+                .map(|t| t as f64),
+            // reported in milliwatts
+            power_watts: device.power_usage().ok().map(|p| p as f64 / 1000.0),
+            vram_used_mb: device
+                .memory_info()
+                .ok()
+                .map(|m| m.used as f64 / BYTES_IN_MB),
+        };
+    }
+}
+
+// amdgpu and i915 expose their readings as files under the card, and under the card's hwmon directory
+pub struct SysfsSampler {
+    card_dir: PathBuf,
+    hwmon_dir: Option<PathBuf>,
+}
+
+impl SysfsSampler {
+    pub fn new(card_dir: &Path) -> Self {
+        return Self {
+            card_dir: card_dir.to_path_buf(),
+            hwmon_dir: find_hwmon_dir(&card_dir.join("device")),
+        };
+    }
+
+    fn read_hwmon(&self, file: &str) -> Option<f64> {
+        return read_number(&self.hwmon_dir.as_ref()?.join(file));
+    }
+}
+
+impl TelemetrySampler for SysfsSampler {
+    fn sample(&mut self) -> TelemetrySample {
+        let device_dir = self.card_dir.join("device");
+
+        // amdgpu reports its shader clock through hwmon in Hz, i915 its gt clock on the card in MHz
+        let clock_mhz = self
+            .read_hwmon("freq1_input")
+            .map(|hz| hz / 1_000_000.0)
+            .or(read_number(&self.card_dir.join("gt_cur_freq_mhz")));
+
+        // hwmon reports power in microwatts; some cards only have the instantaneous reading
+        let power_watts = self
+            .read_hwmon("power1_average")
+            .or(self.read_hwmon("power1_input"))
+            .map(|uw| uw / 1_000_000.0);
+
+        return TelemetrySample {
+            encoder_utilization: None,
+            gpu_utilization: read_number(&device_dir.join("gpu_busy_percent")),
+            clock_mhz,
+            // millidegrees
+            temperature_c: self.read_hwmon("temp1_input").map(|t| t / 1000.0),
+            power_watts,
+            vram_used_mb: read_number(&device_dir.join("mem_info_vram_used"))
+                .map(|bytes| bytes / BYTES_IN_MB),
+        };
+    }
+}
+
+fn find_hwmon_dir(device_dir: &Path) -> Option<PathBuf> {
+    let entries = fs::read_dir(device_dir.join("hwmon")).ok()?;
+    return entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().starts_with("hwmon"))
+                .unwrap_or(false)
+        });
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    return fs::read_to_string(path).ok()?.trim().parse::<f64>().ok();
+}
+
+// plays back the given samples in order, repeating the last one once it runs out
+pub struct FakeSampler {
+    samples: Vec<TelemetrySample>,
+    next: usize,
+}
+
+impl FakeSampler {
+    pub fn new(samples: Vec<TelemetrySample>) -> Self {
+        return Self { samples, next: 0 };
+    }
+}
+
+impl TelemetrySampler for FakeSampler {
+    fn sample(&mut self) -> TelemetrySample {
+        let index = self.next.min(self.samples.len().saturating_sub(1));
+        self.next += 1;
+        return self.samples.get(index).cloned().unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::telemetry::{
+        FakeSampler, GpuTelemetry, MetricStats, SysfsSampler, TelemetrySample, TelemetrySampler,
+    };
+
+    fn get_card_dir(test_name: &str) -> PathBuf {
+        let card_dir = std::env::temp_dir()
+            .join(format!("fake-sysfs-telemetry-{}", test_name))
+            .join("card0");
+        if card_dir.exists() {
+            fs::remove_dir_all(&card_dir).unwrap();
+        }
+
+        fs::create_dir_all(card_dir.join("device/hwmon/hwmon3")).unwrap();
+        return card_dir;
+    }
+
+    fn get_sample(encoder_utilization: f64, power_watts: f64) -> TelemetrySample {
+        return TelemetrySample {
+            encoder_utilization: Some(encoder_utilization),
+            power_watts: Some(power_watts),
+            ..TelemetrySample::default()
+        };
+    }
+
+    #[test]
+    fn metric_stats_test() {
+        let stats = MetricStats::from_values(&vec![40.0, 60.0, 50.0]).unwrap();
+        assert_eq!(stats.min, 40.0);
+        assert_eq!(stats.avg, 50.0);
+        assert_eq!(stats.max, 60.0);
+        assert_eq!(MetricStats::from_values(&vec![]), None);
+    }
+
+    #[test]
+    fn from_samples_test() {
+        let telemetry =
+            GpuTelemetry::from_samples(&vec![get_sample(40.0, 100.0), get_sample(60.0, 120.0)]);
+
+        assert_eq!(telemetry.samples, 2);
+        assert_eq!(telemetry.encoder_utilization.unwrap().avg, 50.0);
+        assert_eq!(telemetry.power_watts.unwrap().max, 120.0);
+        assert_eq!(telemetry.temperature_c, None);
+        assert_eq!(
+            telemetry.to_string(),
+            "Encoder 40/50/60% | Power 100/110/120W"
+        );
+    }
+
+    #[test]
+    fn no_samples_test() {
+        let telemetry = GpuTelemetry::from_samples(&vec![]);
+        assert_eq!(telemetry.samples, 0);
+        assert_eq!(telemetry.to_string(), "no readings");
+    }
+
+    #[test]
+    fn fake_sampler_repeats_last_sample_test() {
+        let mut sampler = FakeSampler::new(vec![get_sample(40.0, 100.0), get_sample(60.0, 120.0)]);
+        assert_eq!(sampler.sample(), get_sample(40.0, 100.0));
+        assert_eq!(sampler.sample(), get_sample(60.0, 120.0));
+        assert_eq!(sampler.sample(), get_sample(60.0, 120.0));
+    }
+
+    #[test]
+    fn sysfs_amdgpu_sample_test() {
+        let card_dir = get_card_dir("amdgpu");
+        let device_dir = card_dir.join("device");
+        fs::write(device_dir.join("gpu_busy_percent"), "87\n").unwrap();
+        fs::write(device_dir.join("mem_info_vram_used"), "1073741824\n").unwrap();
+        fs::write(device_dir.join("hwmon/hwmon3/temp1_input"), "65000\n").unwrap();
+        fs::write(
+            device_dir.join("hwmon/hwmon3/power1_average"),
+            "215000000\n",
+        )
+        .unwrap();
+        fs::write(device_dir.join("hwmon/hwmon3/freq1_input"), "2500000000\n").unwrap();
+
+        let sample = SysfsSampler::new(&card_dir).sample();
+        assert_eq!(sample.encoder_utilization, None);
+        assert_eq!(sample.gpu_utilization, Some(87.0));
+        assert_eq!(sample.vram_used_mb, Some(1024.0));
+        assert_eq!(sample.temperature_c, Some(65.0));
+        assert_eq!(sample.power_watts, Some(215.0));
+        assert_eq!(sample.clock_mhz, Some(2500.0));
+    }
+
+    #[test]
+    fn sysfs_i915_sample_test() {
+        let card_dir = get_card_dir("i915");
+        fs::write(card_dir.join("gt_cur_freq_mhz"), "2050\n").unwrap();
+        fs::write(
+            card_dir.join("device/hwmon/hwmon3/power1_input"),
+            "35000000\n",
+        )
+        .unwrap();
+
+        let sample = SysfsSampler::new(&card_dir).sample();
+        assert_eq!(sample.clock_mhz, Some(2050.0));
+        assert_eq!(sample.power_watts, Some(35.0));
+        assert_eq!(sample.temperature_c, None);
+        assert_eq!(sample.gpu_utilization, None);
+    }
+}
@@ -1 +1,2 @@
 pub mod permutation;
+pub mod platform;
@@ -1,12 +1,24 @@
+use codecs::gop::Gop;
+use codecs::rate_control::RateControl;
+use environment::disk::ReadThroughput;
+use ffmpeg::decoder::Decoder;
+use ffmpeg::error::ProbeError;
 use ffmpeg::ffprobe::probe_for_video_metadata;
 use ffmpeg::metadata::MetaData;
 
+// a second's worth of the bitrate, which is what obs and the streaming platforms assume for cbr
+pub const DEFAULT_VBV_BUFFER_SECONDS: f64 = 1.0;
+
 #[derive(Clone)]
 pub struct Permutation {
     pub video_file: String,
     pub encoder: String,
     pub encoder_settings: String,
     pub bitrate: u32,
+    // quality-targeted rate controls ignore the bitrate, and report the one they ended up at
+    pub rate_control: RateControl,
+    // a fixed keyframe interval, converted to frames once the source's fps is known
+    pub gop: Option<Gop>,
     pub metadata: MetaData,
     pub check_quality: bool,
     pub allow_duplicates: bool,
@@ -15,8 +27,28 @@ pub struct Permutation {
     // determines whether this permutation is the decode run or not
     pub decode_run: bool,
     pub ten_bit: bool,
+    // b frames were turned off, which the encoder's output is checked for
+    pub no_b_frame: bool,
     // whether we are doing any decoding at all
     pub is_decoding: bool,
+    // decodes an existing file instead of the previous encode's output; the file is left as it is
+    pub decode_only: bool,
+    // what the decode is run with, instead of the encoder vendor's hwaccel
+    pub decoder: Option<Decoder>,
+    // refuse to run if the source's storage can't keep up with its frame rate
+    pub fail_on_slow_storage: bool,
+    // measured before the run starts, so we know whether storage was a bottleneck
+    pub read_throughput: Option<ReadThroughput>,
+    // copy the source into memory before its permutations run, to take storage out of the measurement
+    pub stage_in_memory: bool,
+    // keep the encoded output long enough to measure the bitrate it actually came out at
+    pub check_bitrate: bool,
+    // how many seconds of the target bitrate the decoder buffers, for checking cbr encodes against
+    pub vbv_buffer: f64,
+    // keep the encoded output long enough to check the encoder applied the settings it was given
+    pub verify_settings: bool,
+    // hash every frame a decode produces and hold them against a software decode of the same file
+    pub verify_decode: bool,
 }
 
 impl Permutation {
@@ -26,6 +58,8 @@ impl Permutation {
             encoder,
             encoder_settings: String::from(""),
             bitrate: 0,
+            rate_control: RateControl::default(),
+            gop: None,
             metadata: MetaData::new(),
             check_quality: false,
             allow_duplicates: false,
@@ -33,15 +67,30 @@ impl Permutation {
             verbose: false,
             decode_run: false,
             is_decoding: false,
+            decode_only: false,
+            decoder: None,
             ten_bit: false,
+            no_b_frame: false,
+            fail_on_slow_storage: false,
+            read_throughput: None,
+            stage_in_memory: false,
+            check_bitrate: false,
+            vbv_buffer: DEFAULT_VBV_BUFFER_SECONDS,
+            verify_settings: false,
+            verify_decode: false,
         }
     }
 
-    pub fn get_metadata(&mut self) -> MetaData {
+    // the decode of the previous encode's output, which deletes that output once it's done
+    pub fn decodes_previous_encode(&self) -> bool {
+        return self.decode_run && !self.decode_only;
+    }
+
+    pub fn get_metadata(&mut self) -> Result<MetaData, ProbeError> {
         if self.metadata.is_empty() {
-            self.metadata = probe_for_video_metadata(&self.video_file);
+            self.metadata = probe_for_video_metadata(&self.video_file)?;
         }
 
-        return self.metadata;
+        return Ok(self.metadata);
     }
 }
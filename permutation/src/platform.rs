@@ -0,0 +1,476 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use codecs::rate_control::RateControl;
+use ffmpeg::metadata::MetaData;
+
+use crate::permutation::Permutation;
+
+// the profiles that ship with the tools; a directory of .profile files can override them when platforms change rules
+static BUILT_IN_PROFILES: [(&str, &str); 4] = [
+    ("twitch", include_str!("../../platforms/twitch.profile")),
+    (
+        "youtube-live",
+        include_str!("../../platforms/youtube-live.profile"),
+    ),
+    ("kick", include_str!("../../platforms/kick.profile")),
+    ("rtmp", include_str!("../../platforms/rtmp.profile")),
+];
+
+// two frame rates closer than this are the same one, i.e. 59.94 is taken as 60
+const FPS_TOLERANCE: f64 = 0.5;
+
+#[derive(Debug)]
+pub enum PlatformError {
+    // the platform has neither a built-in profile nor a file
+    Unknown(String),
+    Unreadable(String, io::Error),
+    // the profile's file and what is wrong with it
+    Invalid(String, String),
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            PlatformError::Unknown(platform) => write!(
+                f,
+                "[{}] is not a known platform, expected one of: {}, or a <name>.profile file in the platform directory",
+                platform,
+                get_built_in_platforms().join(", ")
+            ),
+            PlatformError::Unreadable(file, e) => write!(f, "unable to read the [{}] platform profile: {}", file, e),
+            PlatformError::Invalid(file, reason) => write!(f, "the [{}] platform profile is invalid: {}", file, reason),
+        };
+    }
+}
+
+impl Error for PlatformError {}
+
+// the highest bitrate a platform takes at up to this resolution and frame rate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MaxBitrate {
+    pub height: u32,
+    pub fps: f64,
+    pub bitrate: f64,
+}
+
+// what a streaming platform takes from a broadcast
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlatformProfile {
+    pub name: String,
+    pub codecs: Vec<String>,
+    // h264/hevc profiles; any of them when empty
+    pub profiles: Vec<String>,
+    // cbr, vbr, etc; any of them when empty
+    pub rate_controls: Vec<String>,
+    // in seconds
+    pub keyframe_interval: f64,
+    pub b_frames: bool,
+    // every resolution and bitrate is taken when empty
+    pub max_bitrates: Vec<MaxBitrate>,
+}
+
+// whether a permutation is usable on a platform, and why not if it isn't
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlatformCheck {
+    pub platform: String,
+    pub violation: Option<String>,
+}
+
+impl PlatformCheck {
+    pub fn is_satisfied(&self) -> bool {
+        return self.violation.is_none();
+    }
+}
+
+impl PlatformProfile {
+    // 'key = value' lines, '#' comments, and '<height>p<fps> = <bitrate>' for the max bitrates
+    pub fn parse(contents: &str) -> Result<PlatformProfile, String> {
+        let mut name = None;
+        let mut codecs = Vec::new();
+        let mut profiles = Vec::new();
+        let mut rate_controls = Vec::new();
+        let mut keyframe_interval = None;
+        let mut b_frames = true;
+        let mut max_bitrates = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("[{}] should be 'key = value'", line)),
+            };
+
+            match key {
+                "name" => name = Some(String::from(value)),
+                "codecs" => codecs = parse_list(value),
+                "profiles" => profiles = parse_list(value),
+                "rate_controls" => rate_controls = parse_list(value),
+                "keyframe_interval" => {
+                    keyframe_interval = Some(
+                        value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|seconds| *seconds > 0.0)
+                            .ok_or(format!("[{}] is not a keyframe interval in seconds", value))?,
+                    )
+                }
+                "b_frames" => {
+                    b_frames = value
+                        .parse::<bool>()
+                        .map_err(|_| format!("[{}] should be true or false", value))?
+                }
+                _ => max_bitrates.push(parse_max_bitrate(key, value)?),
+            }
+        }
+
+        if codecs.is_empty() {
+            return Err(String::from("no codecs are listed"));
+        }
+
+        return Ok(PlatformProfile {
+            name: name.ok_or(String::from("it has no name"))?,
+            codecs,
+            profiles,
+            rate_controls,
+            keyframe_interval: keyframe_interval
+                .ok_or(String::from("it has no keyframe_interval"))?,
+            b_frames,
+            max_bitrates,
+        });
+    }
+
+    // the bitrate limit of the lowest resolution and frame rate listed that the source fits in;
+    // None when the platform doesn't take the source at all
+    pub fn get_max_bitrate(&self, height: u32, fps: f64) -> Option<f64> {
+        if self.max_bitrates.is_empty() {
+            return Some(f64::MAX);
+        }
+
+        return self
+            .max_bitrates
+            .iter()
+            .filter(|max| max.height >= height && max.fps + FPS_TOLERANCE >= fps)
+            .min_by(|a, b| {
+                a.height
+                    .cmp(&b.height)
+                    .then(a.fps.partial_cmp(&b.fps).unwrap())
+            })
+            .map(|max| max.bitrate);
+    }
+
+    pub fn takes_codec(&self, encoder: &str) -> bool {
+        let codec = get_codec(encoder);
+        return self.codecs.iter().any(|allowed| allowed == codec);
+    }
+
+    // the first rule the permutation breaks; the bitrate of quality-targeted rate controls is only known once encoded
+    pub fn check(
+        &self,
+        p: &Permutation,
+        metadata: &MetaData,
+        achieved_bitrate: Option<f64>,
+    ) -> Result<(), String> {
+        if !self.takes_codec(&p.encoder) {
+            return Err(format!("doesn't take {}", get_codec(&p.encoder)));
+        }
+
+        if let Some(profile) = get_encoder_profile(&p.encoder_settings) {
+            if !self.profiles.is_empty() && !self.profiles.iter().any(|allowed| allowed == profile)
+            {
+                return Err(format!("doesn't take the {} profile", profile));
+            }
+        }
+
+        if !self.b_frames && !p.encoder_settings.contains("-bf 0") {
+            return Err(String::from(
+                "doesn't take b frames, which need '-bf 0' to be turned off",
+            ));
+        }
+
+        let rate_control = p.rate_control.to_string();
+        let rate_control = rate_control.split(':').next().unwrap();
+        if !self.rate_controls.is_empty()
+            && !self
+                .rate_controls
+                .iter()
+                .any(|allowed| allowed == rate_control)
+        {
+            return Err(format!("doesn't take {}", rate_control));
+        }
+
+        let has_keyframe_interval = p
+            .gop
+            .map(|gop| (gop.seconds - self.keyframe_interval).abs() < 0.001)
+            .unwrap_or(false);
+        if !has_keyframe_interval {
+            return Err(format!(
+                "needs a keyframe every {}s",
+                self.keyframe_interval
+            ));
+        }
+
+        let fps = metadata.fps.as_f64();
+        let max_bitrate = match self.get_max_bitrate(metadata.height, fps) {
+            Some(max_bitrate) => max_bitrate,
+            None => return Err(format!("doesn't take {}p{}", metadata.height, metadata.fps)),
+        };
+
+        // vbr is held to its peak
+        let bitrate = match p.rate_control {
+            RateControl::Cbr => p.bitrate as f64,
+            RateControl::Vbr { max, .. } => p.bitrate as f64 * max,
+            RateControl::Cqp(_) | RateControl::Cq(_) | RateControl::Icq(_) => {
+                match achieved_bitrate {
+                    Some(achieved_bitrate) => achieved_bitrate,
+                    None => return Err(String::from("its bitrate isn't known until it's encoded")),
+                }
+            }
+        };
+        if bitrate > max_bitrate {
+            return Err(format!(
+                "takes up to {}Mb/s at {}p{}",
+                max_bitrate, metadata.height, metadata.fps
+            ));
+        }
+
+        return Ok(());
+    }
+
+    pub fn check_permutation(
+        &self,
+        p: &Permutation,
+        metadata: &MetaData,
+        achieved_bitrate: Option<f64>,
+    ) -> PlatformCheck {
+        return PlatformCheck {
+            platform: self.name.clone(),
+            violation: self.check(p, metadata, achieved_bitrate).err(),
+        };
+    }
+}
+
+// each platform is looked for as <platform>.profile in the directory first, then among the built-in ones
+pub fn load_platform_profiles(
+    platforms: &Vec<String>,
+    directory: &str,
+) -> Result<Vec<PlatformProfile>, PlatformError> {
+    let mut profiles = Vec::new();
+    for platform in platforms {
+        let platform = platform.to_lowercase();
+        let file = Path::new(directory).join(format!("{}.profile", platform));
+        let (source, contents) = if !directory.is_empty() && file.exists() {
+            let source = file.to_string_lossy().to_string();
+            match fs::read_to_string(&file) {
+                Ok(contents) => (source, contents),
+                Err(e) => return Err(PlatformError::Unreadable(source, e)),
+            }
+        } else {
+            match BUILT_IN_PROFILES.iter().find(|(name, _)| *name == platform) {
+                Some((_, contents)) => (format!("{}.profile", platform), contents.to_string()),
+                None => return Err(PlatformError::Unknown(platform)),
+            }
+        };
+
+        profiles.push(
+            PlatformProfile::parse(&contents)
+                .map_err(|reason| PlatformError::Invalid(source, reason))?,
+        );
+    }
+
+    return Ok(profiles);
+}
+
+pub fn get_built_in_platforms() -> Vec<&'static str> {
+    return BUILT_IN_PROFILES.iter().map(|(name, _)| *name).collect();
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    return value
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect();
+}
+
+// i.e. '1080p60 = 6'
+fn parse_max_bitrate(key: &str, value: &str) -> Result<MaxBitrate, String> {
+    let invalid = || {
+        format!(
+            "[{} = {}] should be '<height>p<fps> = <bitrate>'",
+            key, value
+        )
+    };
+    let (height, fps) = key.split_once('p').ok_or_else(invalid)?;
+    return Ok(MaxBitrate {
+        height: height.parse().map_err(|_| invalid())?,
+        fps: fps.parse().map_err(|_| invalid())?,
+        bitrate: value.parse().map_err(|_| invalid())?,
+    });
+}
+
+// h264_nvenc -> h264
+fn get_codec(encoder: &str) -> &str {
+    return encoder.split('_').next().unwrap_or(encoder);
+}
+
+fn get_encoder_profile(encoder_settings: &str) -> Option<&str> {
+    let mut args = encoder_settings.split_whitespace();
+    args.find(|arg| *arg == "-profile:v")?;
+    return args.next();
+}
+
+#[cfg(test)]
+mod tests {
+    use codecs::gop::Gop;
+    use codecs::rate_control::RateControl;
+    use ffmpeg::frame_rate::FrameRate;
+    use ffmpeg::metadata::MetaData;
+
+    use crate::permutation::Permutation;
+    use crate::platform::{
+        get_built_in_platforms, load_platform_profiles, PlatformError, PlatformProfile,
+    };
+
+    static PROFILE: &str = "# a comment\nname = Test\ncodecs = h264, hevc\nprofiles = main, high\nrate_controls = cbr\nkeyframe_interval = 2\nb_frames = false\n\n720p60 = 4.5\n1080p30 = 4.5\n1080p60 = 6\n";
+
+    fn get_metadata(height: u32, fps: u32) -> MetaData {
+        return MetaData {
+            fps: FrameRate::from(fps),
+            frames: 600,
+            width: height * 16 / 9,
+            height,
+        };
+    }
+
+    fn get_compliant_permutation() -> Permutation {
+        let mut permutation =
+            Permutation::new(String::from("1080-60.y4m"), String::from("h264_nvenc"));
+        permutation.encoder_settings = String::from("-preset p1 -profile:v high -rc cbr -bf 0");
+        permutation.bitrate = 6;
+        permutation.gop = Some(Gop::new(2.0));
+        return permutation;
+    }
+
+    #[test]
+    fn parse_test() {
+        let profile = PlatformProfile::parse(PROFILE).unwrap();
+        assert_eq!(profile.name, "Test");
+        assert_eq!(profile.codecs, vec!["h264", "hevc"]);
+        assert_eq!(profile.keyframe_interval, 2.0);
+        assert!(!profile.b_frames);
+        assert_eq!(profile.max_bitrates.len(), 3);
+    }
+
+    #[test]
+    fn parse_invalid_test() {
+        assert!(PlatformProfile::parse("codecs = h264\nkeyframe_interval = 2").is_err());
+        assert!(PlatformProfile::parse("name = Test\ncodecs = h264").is_err());
+        assert!(PlatformProfile::parse(
+            "name = Test\ncodecs = h264\nkeyframe_interval = 2\n1080 = 6"
+        )
+        .is_err());
+        assert!(PlatformProfile::parse(
+            "name = Test\ncodecs = h264\nkeyframe_interval = 2\nb_frames"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn max_bitrate_test() {
+        let profile = PlatformProfile::parse(PROFILE).unwrap();
+        assert_eq!(profile.get_max_bitrate(720, 30.0), Some(4.5));
+        assert_eq!(profile.get_max_bitrate(1080, 30.0), Some(4.5));
+        assert_eq!(profile.get_max_bitrate(1080, 59.94), Some(6.0));
+        assert_eq!(profile.get_max_bitrate(1080, 120.0), None);
+        assert_eq!(profile.get_max_bitrate(2160, 60.0), None);
+    }
+
+    #[test]
+    fn check_test() {
+        let profile = PlatformProfile::parse(PROFILE).unwrap();
+        let metadata = get_metadata(1080, 60);
+        assert_eq!(
+            profile.check(&get_compliant_permutation(), &metadata, None),
+            Ok(())
+        );
+
+        let mut permutation = get_compliant_permutation();
+        permutation.bitrate = 10;
+        assert_eq!(
+            profile.check(&permutation, &metadata, None),
+            Err(String::from("takes up to 6Mb/s at 1080p60"))
+        );
+
+        let mut permutation = get_compliant_permutation();
+        permutation.gop = None;
+        assert_eq!(
+            profile.check(&permutation, &metadata, None),
+            Err(String::from("needs a keyframe every 2s"))
+        );
+
+        let mut permutation = get_compliant_permutation();
+        permutation.encoder = String::from("av1_nvenc");
+        assert!(profile.check(&permutation, &metadata, None).is_err());
+
+        let mut permutation = get_compliant_permutation();
+        permutation.encoder_settings = String::from("-preset p1 -profile:v high -rc cbr");
+        assert!(profile.check(&permutation, &metadata, None).is_err());
+
+        let mut permutation = get_compliant_permutation();
+        permutation.encoder_settings = String::from("-profile:v constrained_baseline -bf 0");
+        assert!(profile.check(&permutation, &metadata, None).is_err());
+
+        assert!(profile
+            .check(&get_compliant_permutation(), &get_metadata(1080, 120), None)
+            .is_err());
+    }
+
+    #[test]
+    fn check_rate_control_test() {
+        let mut profile = PlatformProfile::parse(PROFILE).unwrap();
+        let metadata = get_metadata(1080, 60);
+        let mut permutation = get_compliant_permutation();
+        permutation.rate_control = RateControl::Cqp(23);
+        assert_eq!(
+            profile.check(&permutation, &metadata, None),
+            Err(String::from("doesn't take cqp"))
+        );
+
+        // without a say in rate controls, quality-targeted ones are held to the bitrate they ended up at
+        profile.rate_controls.clear();
+        assert!(profile.check(&permutation, &metadata, None).is_err());
+        assert_eq!(profile.check(&permutation, &metadata, Some(5.5)), Ok(()));
+
+        permutation.rate_control = "vbr".parse().unwrap();
+        permutation.bitrate = 6;
+        assert!(profile.check(&permutation, &metadata, None).is_err());
+    }
+
+    #[test]
+    fn built_in_profiles_test() {
+        let platforms = get_built_in_platforms()
+            .iter()
+            .map(|platform| platform.to_string())
+            .collect();
+        let profiles = load_platform_profiles(&platforms, "").unwrap();
+        assert_eq!(profiles.len(), get_built_in_platforms().len());
+        assert_eq!(profiles[0].name, "Twitch");
+
+        // a generic rtmp server takes every resolution
+        assert_eq!(profiles[3].get_max_bitrate(2160, 120.0), Some(f64::MAX));
+    }
+
+    #[test]
+    fn unknown_platform_test() {
+        let result = load_platform_profiles(&vec![String::from("myspace")], "");
+        assert!(matches!(result, Err(PlatformError::Unknown(_))));
+    }
+}
@@ -1,16 +1,25 @@
 use clap::Parser;
 
-use cli::cli_util::log_cli_header;
+use cli::cli_util::{collect_host_fingerprint, error_with_ack, log_cli_header};
 use codecs::amf::Amf;
 use codecs::apple_silicon::Apple;
+use codecs::av1_amf::AV1Amf;
+use codecs::av1_nvenc::AV1Nvenc;
 use codecs::av1_qsv::AV1QSV;
 use codecs::get_vendor_for_codec;
+use codecs::gop::Gop;
 use codecs::nvenc::Nvenc;
 use codecs::permute::Permute;
 use codecs::qsv::QSV;
+use codecs::rate_control::RateControl;
 use codecs::vendor::Vendor;
+use engine::error::EngineError;
 use engine::permutation_engine::PermutationEngine;
+use environment::load::get_load_sampler;
+use ffmpeg::source::prepare_source;
+use gpus::telemetry::get_sampler_for;
 use permutation::permutation::Permutation;
+use permutation::platform::{load_platform_profiles, PlatformProfile};
 
 use crate::permutor_cli::PermutorCli;
 
@@ -19,35 +28,125 @@ mod permutor_cli;
 fn main() {
     log_cli_header(String::from("Permutation Tool"));
     let mut cli = PermutorCli::parse();
-    cli.validate();
+    if let Err(e) = cli.validate() {
+        println!("Error: {}", e);
+        error_with_ack(false);
+    }
+
+    // compressed sources get decoded up front so decoding isn't part of the encode measurement
+    cli.source_file = match prepare_source(&cli.source_file, cli.verbose) {
+        Ok(source_file) => source_file,
+        Err(e) => {
+            println!("Error: {}", e);
+            error_with_ack(false);
+            return;
+        }
+    };
+
+    let platforms = match load_platform_profiles(&cli.platform, &cli.platform_directory) {
+        Ok(platforms) => platforms,
+        Err(e) => {
+            println!("Error: {}", e);
+            error_with_ack(false);
+            return;
+        }
+    };
 
     log_special_arguments(&cli);
 
     let mut engine = PermutationEngine::new(cli.log_output_directory.clone());
+    engine.set_ffmpeg_log_retention(cli.ffmpeg_logs);
+    engine.set_host_fingerprint(collect_host_fingerprint());
+    if let Some(sampler) = get_sampler_for(get_vendor_for_codec(&cli.encoder), cli.gpu) {
+        engine.set_telemetry_sampler(sampler);
+    }
+    if let Some(sampler) = get_load_sampler() {
+        engine.set_load_sampler(sampler);
+    }
+    engine.set_background_load_check(cli.max_background_load, cli.rerun_on_background_load);
+    engine.set_trials(cli.trials, cli.warmup);
+    engine.set_platforms(platforms.clone(), cli.restrict_to_platform);
     let vendor = get_vendor_for_codec(&cli.encoder.clone());
-    for bitrate in get_bitrate_permutations(cli.bitrate, cli.max_bitrate_permutation.unwrap()) {
-        match vendor {
-            Vendor::Nvidia => {
-                build_nvenc_setting_permutations(&mut engine, &cli, bitrate);
-            }
-            Vendor::AMD => {
-                build_amf_setting_permutations(&mut engine, &cli, bitrate);
-            }
-            Vendor::IntelQSV => {
-                if cli.encoder.contains("av1") {
-                    build_intel_av1_permutations(&mut engine, &cli, bitrate);
-                } else {
-                    build_intel_igpu_permutations(&mut engine, &cli, bitrate);
+    for gop in get_gops(&cli, &platforms) {
+        for rate_control in cli.rate_control.clone() {
+            for bitrate in get_rate_control_bitrates(&cli, rate_control) {
+                match vendor {
+                    Vendor::Nvidia => {
+                        if cli.encoder.contains("av1") {
+                            build_nvenc_av1_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        } else {
+                            build_nvenc_setting_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        }
+                    }
+                    Vendor::AMD => {
+                        if cli.encoder.contains("av1") {
+                            build_amf_av1_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        } else {
+                            build_amf_setting_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        }
+                    }
+                    Vendor::IntelQSV => {
+                        if cli.encoder.contains("av1") {
+                            build_intel_av1_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        } else {
+                            build_intel_igpu_permutations(
+                                &mut engine,
+                                &cli,
+                                bitrate,
+                                rate_control,
+                                gop,
+                            );
+                        }
+                    }
+                    Vendor::Apple => {
+                        build_apple_silicon_h264_permutations(&mut engine, &cli, bitrate, gop);
+                    }
+                    Vendor::Unknown => {}
                 }
             }
-            Vendor::Apple => {
-                build_apple_silicon_h264_permutations(&mut engine, &cli, bitrate);
-            }
-            Vendor::Unknown => {}
         }
     }
 
-    engine.run();
+    match engine.run() {
+        Ok(()) => {}
+        Err(EngineError::Cancelled) => {
+            println!("Ctrl-C acknowledged, program exiting...");
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            error_with_ack(false);
+        }
+    }
 }
 
 fn log_special_arguments(cli: &PermutorCli) {
@@ -72,31 +171,95 @@ fn log_special_arguments(cli: &PermutorCli) {
         if cli.test_run {
             println!("  -test run, will only run 1 permutation");
         }
+
+        if !cli.gop.is_empty() {
+            println!(
+                "  -keyframe interval(s) of {}",
+                cli.gop
+                    .iter()
+                    .map(|gop| gop.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+        }
+
+        if !cli.platform.is_empty() {
+            let action = if cli.restrict_to_platform {
+                "only running what can be streamed to"
+            } else {
+                "checking results against"
+            };
+            println!("  -{} {}", action, cli.platform.join(", "));
+        }
+
+        if cli.verify_settings {
+            println!("  -checking the encoder applied the settings of every permutation");
+        }
+
+        if cli.check_bitrate {
+            println!(
+                "  -checking the bitrate of every output, against a {}s buffer for cbr",
+                cli.vbv_buffer
+            );
+        }
+
+        if !cli.nvenc_axes.is_empty() {
+            println!(
+                "  -also permuting over nvenc's {}",
+                cli.nvenc_axes
+                    .iter()
+                    .map(|axis| axis.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+        }
     }
 }
 
+// the permutation of the given settings, with everything else taken from what was passed in on the cli
+fn new_permutation(
+    cli: &PermutorCli,
+    settings: String,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) -> Permutation {
+    let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
+    permutation.video_file = cli.source_file.clone();
+    permutation.encoder_settings = settings;
+    permutation.bitrate = bitrate;
+    permutation.rate_control = rate_control;
+    permutation.gop = gop;
+    permutation.check_quality = cli.check_quality;
+    permutation.verbose = cli.verbose;
+    permutation.detect_overload = cli.detect_overload;
+    permutation.allow_duplicates = cli.allow_duplicate_scores;
+    permutation.ten_bit = cli.ten_bit;
+    permutation.fail_on_slow_storage = cli.fail_on_slow_storage;
+    permutation.stage_in_memory = cli.stage_in_memory;
+    permutation.check_bitrate = cli.check_bitrate;
+    permutation.vbv_buffer = cli.vbv_buffer;
+    permutation.verify_settings = cli.verify_settings;
+    permutation.no_b_frame = cli.no_b_frame;
+    return permutation;
+}
+
 fn build_nvenc_setting_permutations(
     engine: &mut PermutationEngine,
     cli: &PermutorCli,
     bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
 ) {
     let mut nvenc = Nvenc::new(cli.encoder == "hevc_nvenc", cli.gpu, cli.no_b_frame);
+    nvenc.set_rate_control(rate_control);
+    nvenc.enable_axes(cli.nvenc_axes.clone());
 
     // initialize the permutations each time
     nvenc.init();
 
     while let Some((_encoder_index, settings)) = nvenc.next() {
-        let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
-        permutation.video_file = cli.source_file.clone();
-        permutation.encoder_settings = settings;
-        permutation.bitrate = bitrate;
-        permutation.check_quality = cli.check_quality;
-        permutation.verbose = cli.verbose;
-        permutation.detect_overload = cli.detect_overload;
-        permutation.allow_duplicates = cli.allow_duplicate_scores;
-        permutation.verbose = cli.verbose;
-        permutation.ten_bit = cli.ten_bit;
-        engine.add(permutation);
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
 
         // break out early here to just make 1 permutation
         if cli.test_run {
@@ -105,23 +268,21 @@ fn build_nvenc_setting_permutations(
     }
 }
 
-fn build_amf_setting_permutations(engine: &mut PermutationEngine, cli: &PermutorCli, bitrate: u32) {
+fn build_amf_setting_permutations(
+    engine: &mut PermutationEngine,
+    cli: &PermutorCli,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) {
     let mut amf = Amf::new(cli.encoder == "hevc_amf", cli.gpu);
+    amf.set_rate_control(rate_control);
 
     // initialize the permutations each time
     amf.init();
 
     while let Some((_encoder_index, settings)) = amf.next() {
-        let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
-        permutation.video_file = cli.source_file.clone();
-        permutation.encoder_settings = settings;
-        permutation.bitrate = bitrate;
-        permutation.check_quality = cli.check_quality;
-        permutation.verbose = cli.verbose;
-        permutation.detect_overload = cli.detect_overload;
-        permutation.allow_duplicates = cli.allow_duplicate_scores;
-        permutation.ten_bit = cli.ten_bit;
-        engine.add(permutation);
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
 
         // break out early here to just make 1 permutation
         if cli.test_run {
@@ -130,23 +291,67 @@ fn build_amf_setting_permutations(engine: &mut PermutationEngine, cli: &Permutor
     }
 }
 
-fn build_intel_av1_permutations(engine: &mut PermutationEngine, cli: &PermutorCli, bitrate: u32) {
+fn build_nvenc_av1_permutations(
+    engine: &mut PermutationEngine,
+    cli: &PermutorCli,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) {
+    let mut nvenc_av1 = AV1Nvenc::new(cli.gpu);
+    nvenc_av1.set_rate_control(rate_control);
+
+    // initialize the permutations each time
+    nvenc_av1.init();
+
+    while let Some((_encoder_index, settings)) = nvenc_av1.next() {
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
+
+        // break out early here to just make 1 permutation
+        if cli.test_run {
+            break;
+        }
+    }
+}
+
+fn build_amf_av1_permutations(
+    engine: &mut PermutationEngine,
+    cli: &PermutorCli,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) {
+    let mut amf_av1 = AV1Amf::new(cli.gpu);
+    amf_av1.set_rate_control(rate_control);
+
+    // initialize the permutations each time
+    amf_av1.init();
+
+    while let Some((_encoder_index, settings)) = amf_av1.next() {
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
+
+        // break out early here to just make 1 permutation
+        if cli.test_run {
+            break;
+        }
+    }
+}
+
+fn build_intel_av1_permutations(
+    engine: &mut PermutationEngine,
+    cli: &PermutorCli,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) {
     let mut intel_av1 = AV1QSV::new();
+    intel_av1.set_rate_control(rate_control);
 
     // initialize the permutations each time
     intel_av1.init();
 
     while let Some((_encoder_index, settings)) = intel_av1.next() {
-        let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
-        permutation.video_file = cli.source_file.clone();
-        permutation.encoder_settings = settings;
-        permutation.bitrate = bitrate;
-        permutation.check_quality = cli.check_quality;
-        permutation.verbose = cli.verbose;
-        permutation.detect_overload = cli.detect_overload;
-        permutation.allow_duplicates = cli.allow_duplicate_scores;
-        permutation.ten_bit = cli.ten_bit;
-        engine.add(permutation);
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
 
         // break out early here to just make 1 permutation
         if cli.test_run {
@@ -155,23 +360,21 @@ fn build_intel_av1_permutations(engine: &mut PermutationEngine, cli: &PermutorCl
     }
 }
 
-fn build_intel_igpu_permutations(engine: &mut PermutationEngine, cli: &PermutorCli, bitrate: u32) {
+fn build_intel_igpu_permutations(
+    engine: &mut PermutationEngine,
+    cli: &PermutorCli,
+    bitrate: u32,
+    rate_control: RateControl,
+    gop: Option<Gop>,
+) {
     let mut intel_i_gpu = QSV::new(cli.encoder == "hevc_qsv");
+    intel_i_gpu.set_rate_control(rate_control);
 
     // initialize the permutations each time
     intel_i_gpu.init();
 
     while let Some((_encoder_index, settings)) = intel_i_gpu.next() {
-        let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
-        permutation.video_file = cli.source_file.clone();
-        permutation.encoder_settings = settings;
-        permutation.bitrate = bitrate;
-        permutation.check_quality = cli.check_quality;
-        permutation.verbose = cli.verbose;
-        permutation.detect_overload = cli.detect_overload;
-        permutation.allow_duplicates = cli.allow_duplicate_scores;
-        permutation.ten_bit = cli.ten_bit;
-        engine.add(permutation);
+        engine.add(new_permutation(cli, settings, bitrate, rate_control, gop));
 
         // break out early here to just make 1 permutation
         if cli.test_run {
@@ -185,6 +388,7 @@ fn build_apple_silicon_h264_permutations(
     engine: &mut PermutationEngine,
     cli: &PermutorCli,
     bitrate: u32,
+    gop: Option<Gop>,
 ) {
     // this can probably be simplified with a type
     let h264 = cli.encoder.contains("h264");
@@ -195,16 +399,13 @@ fn build_apple_silicon_h264_permutations(
     apple_silicon.init();
 
     while let Some((_encoder_index, settings)) = apple_silicon.next() {
-        let mut permutation = Permutation::new(cli.source_file.clone(), cli.encoder.clone());
-        permutation.video_file = cli.source_file.clone();
-        permutation.encoder_settings = settings;
-        permutation.bitrate = bitrate;
-        permutation.check_quality = cli.check_quality;
-        permutation.verbose = cli.verbose;
-        permutation.detect_overload = cli.detect_overload;
-        permutation.allow_duplicates = cli.allow_duplicate_scores;
-        permutation.ten_bit = cli.ten_bit;
-        engine.add(permutation);
+        engine.add(new_permutation(
+            cli,
+            settings,
+            bitrate,
+            RateControl::default(),
+            gop,
+        ));
 
         // break out early here to just make 1 permutation
         if cli.test_run {
@@ -213,6 +414,36 @@ fn build_apple_silicon_h264_permutations(
     }
 }
 
+// without any keyframe intervals given, the encoder's own is used, unless the platforms need their own
+fn get_gops(cli: &PermutorCli, platforms: &Vec<PlatformProfile>) -> Vec<Option<Gop>> {
+    if cli.gop.is_empty() && cli.restrict_to_platform {
+        let mut gops: Vec<Option<Gop>> = Vec::new();
+        for platform in platforms {
+            let gop = Some(Gop::new(platform.keyframe_interval));
+            if !gops.contains(&gop) {
+                gops.push(gop);
+            }
+        }
+
+        return gops;
+    }
+
+    if cli.gop.is_empty() {
+        return vec![None];
+    }
+
+    return cli.gop.iter().map(|gop| Some(*gop)).collect();
+}
+
+// quality-targeted rate controls don't use a bitrate, so they only need to run once
+fn get_rate_control_bitrates(cli: &PermutorCli, rate_control: RateControl) -> Vec<u32> {
+    if rate_control.is_quality_targeted() {
+        return vec![0];
+    }
+
+    return get_bitrate_permutations(cli.bitrate, cli.max_bitrate_permutation.unwrap());
+}
+
 fn get_bitrate_permutations(starting_bitrate: u32, max_bitrate: u32) -> Vec<u32> {
     let interval = 5;
     let mut bitrates = Vec::new();
@@ -1,6 +1,13 @@
 use clap::Parser;
 
-use cli::cli_util::{error_with_ack, standard_cli_check};
+use cli::cli_util::standard_cli_check;
+use cli::error::ConfigError;
+use codecs::get_vendor_for_codec;
+use codecs::gop::Gop;
+use codecs::nvenc::NvencAxis;
+use codecs::rate_control::RateControl;
+use codecs::vendor::Vendor;
+use engine::ffmpeg_logs::LogRetention;
 
 #[derive(Parser)]
 pub struct PermutorCli {
@@ -10,6 +17,20 @@ pub struct PermutorCli {
     /// target bitrate (in Mb/s) to output; in combination with --bitrate-max-permutation, this is the starting permutation
     #[arg(short, long, value_name = "bitrate", default_value = "10")]
     pub bitrate: u32,
+    /// comma separated rate controls to permute over: cbr, vbr[:<max>:<buffer>], cqp:<qp>, cq:<quality> (nvenc) or icq:<quality> (qsv)
+    #[arg(
+        long,
+        value_name = "cbr,cqp:23",
+        default_value = "cbr",
+        value_delimiter = ','
+    )]
+    pub rate_control: Vec<RateControl>,
+    /// comma separated keyframe intervals (in seconds) to permute over, i.e. 2 for what twitch and youtube ask for; the encoder's own is used if not given
+    #[arg(long, value_name = "1,2", value_delimiter = ',')]
+    pub gop: Vec<Gop>,
+    /// comma separated nvenc options to permute over as well: lookahead, aq, multipass, bf, b_ref_mode, weighted_pred, zerolatency; each one multiplies the number of permutations
+    #[arg(long, value_name = "lookahead,aq", value_delimiter = ',')]
+    pub nvenc_axes: Vec<NvencAxis>,
     /// whether to run vmaf score on each permutation or not
     #[arg(short, long)]
     pub check_quality: bool,
@@ -19,7 +40,7 @@ pub struct PermutorCli {
     // stop an encoding session if the encoder can't keep up with the input file's FPS
     #[arg(short, long)]
     pub detect_overload: bool,
-    /// the source file you wish to benchmark; if not provided, will run standard benchmark on all supported resolutions
+    /// the source file you wish to benchmark (y4m, or mp4/mkv/mov/ivf which get decoded to y4m first); if not provided, will run standard benchmark on all supported resolutions
     #[arg(short, long, value_name = "source.y4m", default_value = "")]
     pub source_file: String,
     /// the directory you wish the benchmark to look for your encoder files; can be used with --source_file/-s if you wish
@@ -28,6 +49,44 @@ pub struct PermutorCli {
     /// the directory you wish for the logs this tool produces to go into; defaults to the current directory. Does NOT support spaces in directories
     #[arg(long, value_name = "folder/to/log/output", default_value = "")]
     pub log_output_directory: String,
+    /// which ffmpeg logs to keep in the log output directory once a permutation is done: failures, all or none
+    #[arg(long, value_name = "failures|all|none", default_value = "failures")]
+    pub ffmpeg_logs: LogRetention,
+    /// % of cpu that may be busy right before an encode; above it the encode's results are flagged with [B]
+    #[arg(long, value_name = "PERCENT", default_value = "20")]
+    pub max_background_load: f64,
+    /// runs a permutation again, once, when background load was present during it
+    #[arg(long)]
+    pub rerun_on_background_load: bool,
+    /// runs every encode this many times and reports the mean and spread of their FPS
+    #[arg(long, value_name = "N", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub trials: u32,
+    /// runs every encode once more before its trials, without counting it, so the GPU's clocks have settled
+    #[arg(long)]
+    pub warmup: bool,
+    /// comma separated streaming platforms to check every permutation against: twitch, youtube-live, kick or rtmp
+    #[arg(long, value_name = "twitch,kick", value_delimiter = ',')]
+    pub platform: Vec<String>,
+    /// only runs what at least one of the platforms would take
+    #[arg(long, requires = "platform")]
+    pub restrict_to_platform: bool,
+    /// writes each encode to a file next to the source and checks the bitrate it came out at: its average, peaks and a vbv buffer check
+    #[arg(long)]
+    pub check_bitrate: bool,
+    /// how many seconds of the target bitrate the decoder's buffer holds, for the cbr vbv check
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "1",
+        requires = "check_bitrate"
+    )]
+    pub vbv_buffer: f64,
+    /// writes each encode to a file next to the source and checks the encoder applied its profile, pixel format, b frame and keyframe settings
+    #[arg(long)]
+    pub verify_settings: bool,
+    /// a directory of <platform>.profile files, used over the built-in ones when platforms change their rules
+    #[arg(long, value_name = "folder/to/profiles", default_value = "")]
+    pub platform_directory: String,
     /// runs just the first permutation for given encoder; useful for testing the tool & output
     #[arg(short, long)]
     pub test_run: bool,
@@ -40,7 +99,7 @@ pub struct PermutorCli {
     /// logs useful information to help troubleshooting
     #[arg(short, long)]
     pub verbose: bool,
-    /// lists the supported/implemented supported that this tool supports
+    /// lists the encoders this tool supports, and whether each one can run on this machine
     #[arg(short, long)]
     pub list_supported_encoders: bool,
     /// the GPU you wish to run the encode on; defaults to the first/only GPU found in your system
@@ -49,21 +108,44 @@ pub struct PermutorCli {
     /// opt-out of using b frames for either H264 or HEVC encoders; currently only supported for Nvidia GPUs
     #[arg(short, long)]
     pub no_b_frame: bool,
+    /// stop instead of warning when the source file's storage is too slow to read it in realtime
+    #[arg(long)]
+    pub fail_on_slow_storage: bool,
+    /// copies the source file into memory (/dev/shm) before encoding it, so storage speed doesn't affect the results
+    #[arg(long)]
+    pub stage_in_memory: bool,
 }
 
 impl PermutorCli {
-    pub fn validate(&mut self) {
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
         standard_cli_check(
             self.list_supported_encoders,
             &self.encoder,
             &self.source_file,
             &self.files_directory,
-            false,
-        );
+        )?;
 
         if self.source_file.is_empty() {
-            println!("Error: No source file was provided to run on, please specify an input file");
-            error_with_ack(false);
+            return Err(ConfigError::NoSourceFile);
+        }
+
+        let vendor = get_vendor_for_codec(&self.encoder);
+        for rate_control in &self.rate_control {
+            if rate_control.get_encoder_args(vendor).is_none() {
+                return Err(ConfigError::UnsupportedRateControl(
+                    self.encoder.clone(),
+                    rate_control.to_string(),
+                ));
+            }
+        }
+
+        // the av1 encoder has its own, smaller set of options
+        if !self.nvenc_axes.is_empty() && (vendor != Vendor::Nvidia || self.encoder.contains("av1"))
+        {
+            return Err(ConfigError::UnsupportedOption(
+                String::from("--nvenc-axes"),
+                self.encoder.clone(),
+            ));
         }
 
         if self.max_bitrate_permutation.is_none() {
@@ -74,6 +156,8 @@ impl PermutorCli {
             // internally map the source_file and source_files_directory together
             self.source_file = format!("{}/{}", self.files_directory, self.source_file);
         }
+
+        return Ok(());
     }
 
     pub fn has_special_options(&self) -> bool {
@@ -81,6 +165,11 @@ impl PermutorCli {
             || self.detect_overload
             || self.verbose
             || self.test_run
-            || self.allow_duplicate_scores;
+            || self.allow_duplicate_scores
+            || !self.nvenc_axes.is_empty()
+            || !self.gop.is_empty()
+            || !self.platform.is_empty()
+            || self.check_bitrate
+            || self.verify_settings;
     }
 }
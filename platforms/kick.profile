@@ -0,0 +1,13 @@
+# Kick's recommended streaming settings, from its help center
+name = Kick
+codecs = h264
+profiles = main, high
+rate_controls = cbr
+keyframe_interval = 2
+b_frames = true
+
+# the highest bitrate (in Mb/s) taken at each resolution and frame rate, as <height>p<fps> = <bitrate>
+720p30 = 4.5
+720p60 = 6
+1080p30 = 6
+1080p60 = 8
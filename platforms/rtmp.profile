@@ -0,0 +1,6 @@
+# what any rtmp ingest server can take; without any bitrates listed, every resolution and bitrate is allowed
+name = Generic RTMP
+codecs = h264
+rate_controls = cbr
+keyframe_interval = 2
+b_frames = true
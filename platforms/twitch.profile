@@ -0,0 +1,13 @@
+# Twitch's broadcasting guidelines: https://help.twitch.tv/s/article/broadcasting-guidelines
+name = Twitch
+codecs = h264
+profiles = main, high
+rate_controls = cbr
+keyframe_interval = 2
+b_frames = true
+
+# the highest bitrate (in Mb/s) taken at each resolution and frame rate, as <height>p<fps> = <bitrate>
+720p30 = 3
+720p60 = 4.5
+1080p30 = 4.5
+1080p60 = 6
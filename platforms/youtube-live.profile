@@ -0,0 +1,16 @@
+# YouTube Live's encoder settings: https://support.google.com/youtube/answer/2853702
+name = YouTube Live
+codecs = h264, hevc, av1
+rate_controls = cbr
+keyframe_interval = 2
+b_frames = true
+
+# the highest bitrate (in Mb/s) taken at each resolution and frame rate, as <height>p<fps> = <bitrate>
+720p30 = 4
+720p60 = 6
+1080p30 = 10
+1080p60 = 12
+1440p30 = 15
+1440p60 = 24
+2160p30 = 35
+2160p60 = 51